@@ -0,0 +1,400 @@
+// Group-by aggregation: records grouped by one or more fields, with count, sum, avg,
+// min, max and distinct per group, optionally pivoted on a field's values into columns.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use crate::commands::field_path;
+use crate::commands::file_parser::FileFormat;
+use crate::commands::histogram::numeric_value;
+use crate::commands::search::{scan_matching_records, SearchQuery};
+use crate::commands::sort::{compare_sort_keys, extract_sort_keys, SortColumn, SortSpec, SortValue};
+
+const DEFAULT_MAX_GROUPS: usize = 50_000;
+/// Distinct values a pivot field may have (each one adds a set of columns)
+const MAX_PIVOT_VALUES: usize = 200;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum AggregateFunction {
+    /// Records in the group, or non-null values of the field when one is given
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    /// Distinct non-null values of the field
+    Distinct,
+}
+
+impl AggregateFunction {
+    fn name(self) -> &'static str {
+        match self {
+            AggregateFunction::Count => "count",
+            AggregateFunction::Sum => "sum",
+            AggregateFunction::Avg => "avg",
+            AggregateFunction::Min => "min",
+            AggregateFunction::Max => "max",
+            AggregateFunction::Distinct => "distinct",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Aggregation {
+    /// JSON Pointer to the aggregated field; sum, avg, min and max read numbers
+    /// and numeric strings
+    #[serde(default)]
+    pub field: Option<String>,
+    pub function: AggregateFunction,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct AggregateOptions {
+    /// JSON Pointers of the fields to group by; no fields gives a single group
+    #[serde(default)]
+    pub group_by: Vec<String>,
+    /// A record count when empty
+    #[serde(default)]
+    pub aggregations: Vec<Aggregation>,
+    /// Field whose values become columns (one set of aggregations per value)
+    #[serde(default)]
+    pub pivot: Option<String>,
+    #[serde(default)]
+    pub max_groups: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AggregateTable {
+    /// JSON Pointers naming the columns: the group-by fields, then one per aggregation,
+    /// e.g. `/count` or `/bytes/sum`. With a pivot they are prefixed with the pivot
+    /// field and value (`/method/GET/bytes/sum`), so they can't clash with a group-by field.
+    pub columns: Vec<String>,
+    /// One row per group, sorted by the group values
+    pub rows: Vec<Vec<serde_json::Value>>,
+    /// Records aggregated into the rows (of the matching ones when a query was given);
+    /// records of groups left out by `max_groups` aren't counted
+    pub records: usize,
+    pub invalid_lines: usize,
+    /// More groups than `max_groups`: records of the extra groups were left out
+    pub groups_truncated: bool,
+}
+
+/// Command to group the records of a file, or the ones matching `query`, and
+/// aggregate each group
+#[tauri::command]
+pub async fn aggregate(
+    path: String,
+    file_format: FileFormat,
+    options: AggregateOptions,
+    query: Option<SearchQuery>,
+) -> Result<AggregateTable, String> {
+    build_table(&PathBuf::from(&path), &file_format, &options, query.as_ref()).await
+}
+
+/// The command's work, on borrowed arguments
+async fn build_table(
+    file_path: &std::path::Path,
+    file_format: &FileFormat,
+    options: &AggregateOptions,
+    query: Option<&SearchQuery>,
+) -> Result<AggregateTable, String> {
+    let aggregations = if options.aggregations.is_empty() {
+        vec![Aggregation {
+            field: None,
+            function: AggregateFunction::Count,
+        }]
+    } else {
+        options.aggregations.clone()
+    };
+    for aggregation in &aggregations {
+        if aggregation.field.is_none() && aggregation.function != AggregateFunction::Count {
+            return Err(format!("The {} aggregation needs a field", aggregation.function.name()));
+        }
+    }
+    let max_groups = options.max_groups.unwrap_or(DEFAULT_MAX_GROUPS).max(1);
+
+    // Groups and pivot columns are ordered like an ascending sort on their fields
+    let group_specs = SortSpec::compile(&ascending(&options.group_by))?;
+    let pivot_specs = SortSpec::compile(&ascending(options.pivot.as_slice()))?;
+
+    let mut groups: Vec<Group> = Vec::new();
+    let mut group_index: HashMap<Vec<String>, usize> = HashMap::new();
+    let mut pivot_values: HashMap<String, Vec<SortValue>> = HashMap::new();
+    let mut records = 0;
+    let mut groups_truncated = false;
+
+    let invalid_lines = scan_matching_records(file_path, file_format, query, |_, json| {
+        let key: Vec<String> = options.group_by.iter().map(|path| value_key(field_path::resolve(json, path))).collect();
+        let index = match group_index.get(&key) {
+            Some(&index) => index,
+            None if groups.len() >= max_groups => {
+                groups_truncated = true;
+                return Ok(());
+            }
+            None => {
+                groups.push(Group {
+                    values: options
+                        .group_by
+                        .iter()
+                        .map(|path| field_path::resolve(json, path).cloned().unwrap_or(serde_json::Value::Null))
+                        .collect(),
+                    sort_keys: extract_sort_keys(json, &group_specs),
+                    cells: HashMap::new(),
+                });
+                group_index.insert(key, groups.len() - 1);
+                groups.len() - 1
+            }
+        };
+
+        let pivot_key = match options.pivot {
+            Some(ref pivot) => {
+                let pivot_key = value_key(field_path::resolve(json, pivot));
+                if !pivot_values.contains_key(&pivot_key) {
+                    if pivot_values.len() >= MAX_PIVOT_VALUES {
+                        return Err(format!(
+                            "{} has more than {} distinct values; pivot on a field with fewer values",
+                            field_path::label(pivot),
+                            MAX_PIVOT_VALUES
+                        ));
+                    }
+                    pivot_values.insert(pivot_key.clone(), extract_sort_keys(json, &pivot_specs));
+                }
+                pivot_key
+            }
+            None => String::new(),
+        };
+
+        let cells = groups[index]
+            .cells
+            .entry(pivot_key)
+            .or_insert_with(|| aggregations.iter().map(|_| Accumulator::default()).collect());
+        for (aggregation, accumulator) in aggregations.iter().zip(cells.iter_mut()) {
+            accumulator.add(aggregation, json);
+        }
+        records += 1;
+        Ok(())
+    })
+    .await?;
+
+    let mut pivot_keys: Vec<(String, Vec<SortValue>)> = pivot_values.into_iter().collect();
+    pivot_keys.sort_by(|a, b| compare_sort_keys(&a.1, &b.1, &pivot_specs).then_with(|| a.0.cmp(&b.0)));
+    if options.pivot.is_none() {
+        pivot_keys.push((String::new(), Vec::new()));
+    }
+
+    let mut columns = options.group_by.clone();
+    for (pivot_key, _) in &pivot_keys {
+        let prefix = match options.pivot {
+            Some(ref pivot) => field_path::child(pivot, &pivot_label(pivot_key)),
+            None => String::new(),
+        };
+        for aggregation in &aggregations {
+            let field = aggregation.field.as_deref().unwrap_or("");
+            columns.push(format!("{}{}", prefix, field_path::child(field, aggregation.function.name())));
+        }
+    }
+
+    groups.sort_by(|a, b| compare_sort_keys(&a.sort_keys, &b.sort_keys, &group_specs));
+    let empty: Vec<Accumulator> = aggregations.iter().map(|_| Accumulator::default()).collect();
+    let rows = groups
+        .into_iter()
+        .map(|group| {
+            let mut row = group.values;
+            for (pivot_key, _) in &pivot_keys {
+                let cells = group.cells.get(pivot_key).unwrap_or(&empty);
+                row.extend(aggregations.iter().zip(cells).map(|(aggregation, acc)| acc.result(aggregation.function)));
+            }
+            row
+        })
+        .collect();
+
+    Ok(AggregateTable {
+        columns,
+        rows,
+        records,
+        invalid_lines,
+        groups_truncated,
+    })
+}
+
+struct Group {
+    values: Vec<serde_json::Value>,
+    sort_keys: Vec<SortValue>,
+    /// Accumulators per pivot value (a single `""` entry without a pivot)
+    cells: HashMap<String, Vec<Accumulator>>,
+}
+
+#[derive(Default)]
+struct Accumulator {
+    count: usize,
+    numbers: usize,
+    sum: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+    /// Grouping keys of the values, kept whole so the count is exact
+    distinct: HashSet<String>,
+}
+
+impl Accumulator {
+    fn add(&mut self, aggregation: &Aggregation, json: &serde_json::Value) {
+        let value = match aggregation.field {
+            Some(ref field) => match field_path::resolve(json, field) {
+                Some(value) if !value.is_null() => value,
+                _ => return,
+            },
+            None => {
+                self.count += 1;
+                return;
+            }
+        };
+
+        match aggregation.function {
+            AggregateFunction::Count => self.count += 1,
+            AggregateFunction::Distinct => {
+                self.distinct.insert(value_key(Some(value)));
+            }
+            AggregateFunction::Sum | AggregateFunction::Avg | AggregateFunction::Min | AggregateFunction::Max => {
+                if let Some(n) = numeric_value(value) {
+                    self.numbers += 1;
+                    self.sum += n;
+                    self.min = Some(self.min.map_or(n, |m| m.min(n)));
+                    self.max = Some(self.max.map_or(n, |m| m.max(n)));
+                }
+            }
+        }
+    }
+
+    /// The aggregated value; null when a numeric aggregation saw no numbers
+    fn result(&self, function: AggregateFunction) -> serde_json::Value {
+        match function {
+            AggregateFunction::Count => self.count.into(),
+            AggregateFunction::Distinct => self.distinct.len().into(),
+            AggregateFunction::Sum if self.numbers > 0 => number(self.sum),
+            AggregateFunction::Avg if self.numbers > 0 => number(self.sum / self.numbers as f64),
+            AggregateFunction::Min => self.min.map(number).unwrap_or_default(),
+            AggregateFunction::Max => self.max.map(number).unwrap_or_default(),
+            _ => serde_json::Value::Null,
+        }
+    }
+}
+
+fn ascending(paths: &[String]) -> Vec<SortColumn> {
+    paths
+        .iter()
+        .map(|path| SortColumn {
+            column: path.clone(),
+            direction: "asc".to_string(),
+            value_type: None,
+            collation: None,
+            locale: None,
+            expression: None,
+        })
+        .collect()
+}
+
+/// Grouping key of a value: its JSON text, so `1` and `"1"` stay apart; missing counts as null
+fn value_key(value: Option<&serde_json::Value>) -> String {
+    value.unwrap_or(&serde_json::Value::Null).to_string()
+}
+
+/// Column label for a pivot value: strings without their quotes, unless that would
+/// read as another JSON value (`"1"` stays apart from `1`)
+fn pivot_label(key: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(key) {
+        Ok(serde_json::Value::String(s)) if serde_json::from_str::<serde_json::Value>(&s).is_err() => s,
+        _ => key.to_string(),
+    }
+}
+
+/// Whole numbers as integers so `3` doesn't come out as `3.0`
+fn number(n: f64) -> serde_json::Value {
+    if n.fract() == 0.0 && n.abs() < 9_007_199_254_740_992.0 {
+        (n as i64).into()
+    } else {
+        serde_json::Number::from_f64(n).map(serde_json::Value::Number).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_util::temp_file;
+
+    async fn table(content: &str, options: AggregateOptions) -> AggregateTable {
+        let path = temp_file("records.jsonl", content);
+        let table = build_table(&path, &FileFormat::JsonL, &options, None).await;
+        std::fs::remove_file(&path).unwrap();
+        table.unwrap()
+    }
+
+    fn aggregation(field: &str, function: AggregateFunction) -> Aggregation {
+        Aggregation {
+            field: Some(field.to_string()),
+            function,
+        }
+    }
+
+    #[tokio::test]
+    async fn distinct_is_exact() {
+        let content: String = (0..100_000).map(|i| format!("{{\"id\":{}}}\n{{\"id\":\"{}\"}}\n", i, i)).collect();
+        let options = AggregateOptions {
+            aggregations: vec![aggregation("/id", AggregateFunction::Distinct)],
+            ..Default::default()
+        };
+        let table = table(&content, options).await;
+        // Numbers and numeric strings are different values
+        assert_eq!(table.rows, vec![vec![serde_json::json!(200_000)]]);
+    }
+
+    #[tokio::test]
+    async fn pivot_columns_are_prefixed_with_the_pivot_field() {
+        let content = r#"{"method":"GET","GET":"x"}
+{"method":"count","GET":"y"}
+{"method":1,"GET":"x"}
+{"method":"1","GET":"x"}
+"#;
+        let options = AggregateOptions {
+            group_by: vec!["/GET".to_string()],
+            pivot: Some("/method".to_string()),
+            ..Default::default()
+        };
+        let table = table(content, options).await;
+        assert_eq!(
+            table.columns,
+            vec!["/GET", "/method/\"1\"/count", "/method/1/count", "/method/count/count", "/method/GET/count"]
+        );
+        let unique: HashSet<&String> = table.columns.iter().collect();
+        assert_eq!(unique.len(), table.columns.len());
+        assert_eq!(
+            table.rows,
+            vec![
+                vec![serde_json::json!("x"), 1.into(), 1.into(), 0.into(), 1.into()],
+                vec![serde_json::json!("y"), 0.into(), 0.into(), 1.into(), 0.into()],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn records_of_dropped_groups_are_not_counted() {
+        let content = "{\"k\":\"a\"}\n{\"k\":\"b\"}\n{\"k\":\"a\"}\n{\"k\":\"c\"}\n{\"k\":\"c\"}\n";
+        let options = AggregateOptions {
+            group_by: vec!["/k".to_string()],
+            max_groups: Some(2),
+            ..Default::default()
+        };
+        let table = table(content, options).await;
+        assert!(table.groups_truncated);
+        assert_eq!(table.rows, vec![vec![serde_json::json!("a"), 2.into()], vec![serde_json::json!("b"), 1.into()]]);
+        assert_eq!(table.records, 3);
+    }
+
+    #[test]
+    fn pivot_labels() {
+        assert_eq!(pivot_label(&value_key(Some(&serde_json::json!("GET")))), "GET");
+        assert_eq!(pivot_label(&value_key(Some(&serde_json::json!("a/b")))), "a/b");
+        assert_eq!(pivot_label(&value_key(Some(&serde_json::json!(1)))), "1");
+        assert_eq!(pivot_label(&value_key(Some(&serde_json::json!("1")))), "\"1\"");
+        assert_eq!(pivot_label(&value_key(Some(&serde_json::json!("null")))), "\"null\"");
+        assert_eq!(pivot_label(&value_key(None)), "null");
+    }
+}
@@ -0,0 +1,497 @@
+// Downloads cached by URL: revalidated with ETag / Last-Modified so an unchanged file
+// isn't fetched again, resumed with a Range request after an interruption, pruned by size and age.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use reqwest::header::{
+    HeaderMap, ACCEPT_RANGES, CONTENT_RANGE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE,
+    LAST_MODIFIED, RANGE,
+};
+use reqwest::{RequestBuilder, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use tauri::ipc::Channel;
+use tauri::State;
+use uuid::Uuid;
+use crate::commands::http::{self, HttpOptions};
+use crate::commands::network::{self, DownloadProgress, DownloadedFile};
+
+/// Size the cache is pruned down to after each download
+pub const DEFAULT_CACHE_MAX_MB: u64 = 1024;
+/// Entries (and leftover temp files) unused for longer than this are removed
+pub const DEFAULT_CACHE_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Metadata kept next to each cached file
+const ENTRY_FILE: &str = "entry.json";
+/// Prefix of the temp files and directories the app creates
+const TEMP_PREFIX: &str = "jsonl-viewer-";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct CacheEntry {
+    url: String,
+    file_name: String,
+    content_type: Option<String>,
+    decompressed: bool,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// The server advertised `Accept-Ranges: bytes`
+    accept_ranges: bool,
+    /// False while downloading, and after an interrupted download
+    complete: bool,
+    /// Unix seconds of the last download or revalidation, for pruning
+    last_used: u64,
+}
+
+impl CacheEntry {
+    /// A partial body can continue with Range only when it was stored as received
+    /// (not unpacked from gzip) and the server can confirm it didn't change since
+    fn is_resumable(&self) -> bool {
+        self.accept_ranges && !self.decompressed && self.if_range().is_some()
+    }
+
+    /// Validator for `If-Range`, which only takes a strong ETag or a date
+    fn if_range(&self) -> Option<&str> {
+        self.etag
+            .as_deref()
+            .filter(|etag| !etag.starts_with("W/"))
+            .or(self.last_modified.as_deref())
+    }
+
+    fn downloaded_file(&self, path: &Path, from_cache: bool, resumed: bool) -> DownloadedFile {
+        DownloadedFile {
+            path: path.to_string_lossy().to_string(),
+            file_name: self.file_name.clone(),
+            url: self.url.clone(),
+            content_type: self.content_type.clone(),
+            decompressed: self.decompressed,
+            from_cache,
+            resumed,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CacheStats {
+    /// Entries left after pruning
+    pub entries: usize,
+    pub total_bytes: u64,
+    /// Cache entries and leftover temp files removed
+    pub removed: usize,
+}
+
+/// Download cache, one directory per URL (managed Tauri state)
+pub struct DownloadCache {
+    root: PathBuf,
+    /// Keys of entries a download is using right now
+    busy: Mutex<HashSet<String>>,
+    /// File the viewer has open: search, sort, export and stats reopen it by path
+    viewed: Mutex<Option<PathBuf>>,
+}
+
+impl DownloadCache {
+    pub fn new(root: PathBuf) -> DownloadCache {
+        DownloadCache {
+            root,
+            busy: Mutex::new(HashSet::new()),
+            viewed: Mutex::new(None),
+        }
+    }
+
+    /// Record the file the viewer has open, so pruning keeps it
+    pub(crate) fn set_viewed(&self, path: Option<PathBuf>) {
+        *self.viewed.lock().unwrap() = path;
+    }
+
+    pub(crate) fn viewed(&self) -> Option<PathBuf> {
+        self.viewed.lock().unwrap().clone()
+    }
+
+    /// Only plain GET requests are cached: anything else may not be repeatable
+    pub(crate) fn is_cacheable(options: &HttpOptions) -> bool {
+        options.body.is_none() && options.method.as_deref().is_none_or(|m| m.trim().eq_ignore_ascii_case("GET"))
+    }
+
+    /// Take the entry for `url` for one download; None while another download uses it
+    pub(crate) fn claim(&self, url: &Url) -> Option<CacheSlot<'_>> {
+        let key = Uuid::new_v5(&Uuid::NAMESPACE_URL, url.as_str().as_bytes()).to_string();
+        if !self.busy.lock().unwrap().insert(key.clone()) {
+            return None;
+        }
+        Some(CacheSlot {
+            cache: self,
+            dir: self.root.join(&key),
+            key,
+        })
+    }
+
+    /// Remove entries unused for longer than `max_age`, then the least recently
+    /// used ones until the cache fits in `max_bytes`. Entries a download is using and
+    /// the one holding the viewed file are kept, and so is the most recent entry even
+    /// when it alone is larger than `max_bytes` (unless `max_bytes` is 0, which empties the cache).
+    pub fn prune(&self, max_bytes: u64, max_age: Duration) -> CacheStats {
+        let busy = self.busy.lock().unwrap().clone();
+        let viewed = self.viewed();
+        let now = unix_now();
+        let mut stats = CacheStats::default();
+        let mut kept = Vec::new();
+
+        let Ok(dirs) = std::fs::read_dir(&self.root) else {
+            return stats;
+        };
+        for dir in dirs.flatten() {
+            let key = dir.file_name().to_string_lossy().to_string();
+            let path = dir.path();
+            let pinned = busy.contains(&key) || viewed.as_ref().is_some_and(|v| v.starts_with(&path));
+            if pinned {
+                stats.entries += 1;
+                stats.total_bytes += dir_size(&path);
+                continue;
+            }
+            let last_used = read_entry(&path).map(|e| e.last_used);
+            match last_used {
+                Some(last_used) if now.saturating_sub(last_used) <= max_age.as_secs() => {
+                    kept.push((last_used, dir_size(&path), path));
+                }
+                // Expired, or not an entry at all
+                _ => {
+                    if std::fs::remove_dir_all(&path).is_ok() {
+                        stats.removed += 1;
+                    }
+                }
+            }
+        }
+
+        // Newest first, so the oldest entries are the ones evicted
+        kept.sort_by_key(|(last_used, _, _)| std::cmp::Reverse(*last_used));
+        for (i, (_, size, path)) in kept.into_iter().enumerate() {
+            let fits = stats.total_bytes + size <= max_bytes || (i == 0 && max_bytes > 0);
+            if !fits && std::fs::remove_dir_all(&path).is_ok() {
+                stats.removed += 1;
+                continue;
+            }
+            stats.entries += 1;
+            stats.total_bytes += size;
+        }
+
+        stats
+    }
+}
+
+/// A cache entry held by one download
+pub(crate) struct CacheSlot<'a> {
+    cache: &'a DownloadCache,
+    key: String,
+    dir: PathBuf,
+}
+
+impl CacheSlot<'_> {
+    /// Download into the cache. A complete entry is revalidated and reused when the
+    /// server answers 304; a resumable partial one is continued from where it stopped.
+    pub(crate) async fn fetch(
+        &self,
+        mut request: RequestBuilder,
+        options: &HttpOptions,
+        max_bytes: Option<u64>,
+        channel: &Channel<DownloadProgress>,
+    ) -> Result<DownloadedFile, String> {
+        let previous = read_entry(&self.dir)
+            .and_then(|entry| std::fs::metadata(self.dir.join(&entry.file_name)).ok().map(|m| (entry, m.len())));
+
+        let mut resume_from = 0;
+        match previous {
+            Some((ref entry, _)) if entry.complete => {
+                if let Some(ref etag) = entry.etag {
+                    request = request.header(IF_NONE_MATCH, etag);
+                }
+                if let Some(ref date) = entry.last_modified {
+                    request = request.header(IF_MODIFIED_SINCE, date);
+                }
+            }
+            Some((ref entry, len)) if len > 0 && entry.is_resumable() => {
+                request = request
+                    .header(RANGE, format!("bytes={}-", len))
+                    .header(IF_RANGE, entry.if_range().unwrap_or_default());
+                resume_from = len;
+            }
+            _ => {}
+        }
+
+        let response = http::execute(request, options).await?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some(&(ref entry, len)) = previous.as_ref().filter(|(entry, _)| entry.complete) {
+                let entry = CacheEntry { last_used: unix_now(), ..entry.clone() };
+                self.write_entry(&entry)?;
+                let _ = channel.send(DownloadProgress { received: len, total: Some(len) });
+                return Ok(entry.downloaded_file(&self.dir.join(&entry.file_name), true, false));
+            }
+        }
+        http::check_status(&response)?;
+
+        let resumed = response.status() == StatusCode::PARTIAL_CONTENT;
+        if resumed && (resume_from == 0 || content_range_start(response.headers()) != Some(resume_from)) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+            return Err("Server resumed the download at an unexpected position".to_string());
+        }
+
+        let headers = response.headers().clone();
+        let url = response.url().to_string();
+        let mut body = network::open_body(response, options).await?;
+
+        let mut entry = match previous {
+            Some((entry, _)) if resumed => {
+                // The rest of the body as stored: raw bytes under the name picked at first
+                body.file_name = entry.file_name.clone();
+                body.gzip = false;
+                entry
+            }
+            _ => {
+                let _ = std::fs::remove_dir_all(&self.dir);
+                std::fs::create_dir_all(&self.dir).map_err(|e| format!("Failed to create cache directory: {}", e))?;
+                let entry = CacheEntry {
+                    url,
+                    file_name: body.file_name.clone(),
+                    content_type: body.content_type.clone(),
+                    decompressed: body.gzip,
+                    etag: header_string(&headers, ETAG),
+                    last_modified: header_string(&headers, LAST_MODIFIED),
+                    accept_ranges: header_string(&headers, ACCEPT_RANGES).is_some_and(|v| v.eq_ignore_ascii_case("bytes")),
+                    complete: false,
+                    last_used: unix_now(),
+                };
+                self.write_entry(&entry)?;
+                entry
+            }
+        };
+
+        let path = self.dir.join(&entry.file_name);
+        network::write_body(body, options, &path, if resumed { resume_from } else { 0 }, max_bytes, channel).await?;
+
+        entry.complete = true;
+        entry.last_used = unix_now();
+        self.write_entry(&entry)?;
+        Ok(entry.downloaded_file(&path, false, resumed))
+    }
+
+    /// After a failed download: drop a partial file that can't be resumed later
+    pub(crate) fn discard_unresumable(&self) {
+        let keep = read_entry(&self.dir).is_some_and(|entry| entry.complete || entry.is_resumable());
+        if !keep {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn write_entry(&self, entry: &CacheEntry) -> Result<(), String> {
+        let content = serde_json::to_vec_pretty(entry).map_err(|e| format!("Failed to serialize cache entry: {}", e))?;
+        std::fs::write(self.dir.join(ENTRY_FILE), content).map_err(|e| format!("Failed to write cache entry: {}", e))
+    }
+}
+
+impl Drop for CacheSlot<'_> {
+    fn drop(&mut self) {
+        self.cache.busy.lock().unwrap().remove(&self.key);
+    }
+}
+
+fn read_entry(dir: &Path) -> Option<CacheEntry> {
+    let content = std::fs::read(dir.join(ENTRY_FILE)).ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+fn header_string(headers: &HeaderMap, name: reqwest::header::HeaderName) -> Option<String> {
+    headers.get(name).and_then(|v| v.to_str().ok()).map(|v| v.to_string())
+}
+
+/// First byte position of `Content-Range: bytes <start>-<end>/<size>`
+fn content_range_start(headers: &HeaderMap) -> Option<u64> {
+    let range = header_string(headers, CONTENT_RANGE)?;
+    let positions = range.trim().strip_prefix("bytes")?.trim_start();
+    positions.split('-').next()?.trim().parse().ok()
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|e| e.metadata().ok())
+                .filter(|m| m.is_file())
+                .map(|m| m.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Remove `jsonl-viewer-*` files and directories older than `max_age` from the
+/// temp directory: uncached downloads, API datasets, stream recordings and sort runs.
+/// The one holding `keep` (the viewed file) stays.
+pub fn cleanup_temp_files(max_age: Duration, keep: Option<&Path>) -> usize {
+    let Ok(entries) = std::fs::read_dir(std::env::temp_dir()) else {
+        return 0;
+    };
+
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        if !entry.file_name().to_string_lossy().starts_with(TEMP_PREFIX) {
+            continue;
+        }
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > max_age);
+        if !expired {
+            continue;
+        }
+        let path = entry.path();
+        if keep.is_some_and(|keep| keep.starts_with(&path)) {
+            continue;
+        }
+        let result = if path.is_dir() { std::fs::remove_dir_all(&path) } else { std::fs::remove_file(&path) };
+        if result.is_ok() {
+            removed += 1;
+        }
+    }
+    removed
+}
+
+/// Command to prune the download cache and old temp files; limits default to
+/// the ones applied after every download
+#[tauri::command]
+pub async fn prune_download_cache(
+    max_size_mb: Option<u64>,
+    max_age_hours: Option<u64>,
+    cache: State<'_, DownloadCache>,
+) -> Result<CacheStats, String> {
+    let max_age = max_age_hours.map_or(DEFAULT_CACHE_MAX_AGE, |hours| Duration::from_secs(hours * 60 * 60));
+    let mut stats = cache.prune(max_size_mb.unwrap_or(DEFAULT_CACHE_MAX_MB) * 1024 * 1024, max_age);
+    stats.removed += cleanup_temp_files(max_age, cache.viewed().as_deref());
+    Ok(stats)
+}
+
+/// Command to tell the backend which file the viewer has open (None once it's closed),
+/// so cache pruning and temp file cleanup leave it alone
+#[tauri::command]
+pub async fn set_viewed_file(path: Option<String>, cache: State<'_, DownloadCache>) -> Result<(), String> {
+    cache.set_viewed(path.map(PathBuf::from));
+    Ok(())
+}
+
+/// Command to empty the download cache (entries in use by a download are kept)
+#[tauri::command]
+pub async fn clear_download_cache(cache: State<'_, DownloadCache>) -> Result<CacheStats, String> {
+    Ok(cache.prune(0, Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_util::temp_path;
+
+    /// A complete entry of `size` bytes last used `age` seconds ago
+    fn add_entry(root: &Path, key: &str, size: usize, age: u64) -> PathBuf {
+        let dir = root.join(key);
+        std::fs::create_dir_all(&dir).unwrap();
+        let entry = CacheEntry {
+            url: format!("https://example.com/{}", key),
+            file_name: "data.jsonl".to_string(),
+            content_type: None,
+            decompressed: false,
+            etag: None,
+            last_modified: None,
+            accept_ranges: false,
+            complete: true,
+            last_used: unix_now() - age,
+        };
+        std::fs::write(dir.join(ENTRY_FILE), serde_json::to_vec(&entry).unwrap()).unwrap();
+        std::fs::write(dir.join(&entry.file_name), vec![b'x'; size]).unwrap();
+        dir.join(&entry.file_name)
+    }
+
+    fn entry_size(root: &Path, key: &str) -> u64 {
+        dir_size(&root.join(key))
+    }
+
+    #[test]
+    fn prune_evicts_oldest_entries_first() {
+        let root = temp_path("cache");
+        add_entry(&root, "old", 1000, 300);
+        add_entry(&root, "middle", 1000, 200);
+        add_entry(&root, "new", 1000, 100);
+        let limit = entry_size(&root, "new") + entry_size(&root, "middle");
+
+        let cache = DownloadCache::new(root.clone());
+        let stats = cache.prune(limit, DEFAULT_CACHE_MAX_AGE);
+
+        assert_eq!(stats.removed, 1);
+        assert_eq!(stats.entries, 2);
+        assert!(!root.join("old").exists());
+        assert!(root.join("middle").exists() && root.join("new").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn prune_keeps_a_single_oversized_newest_entry() {
+        let root = temp_path("cache");
+        add_entry(&root, "old", 10, 200);
+        add_entry(&root, "huge", 5000, 100);
+
+        let cache = DownloadCache::new(root.clone());
+        let stats = cache.prune(1000, DEFAULT_CACHE_MAX_AGE);
+
+        assert_eq!(stats.entries, 1);
+        assert!(root.join("huge").exists());
+        assert!(!root.join("old").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn prune_keeps_the_viewed_and_busy_entries() {
+        let root = temp_path("cache");
+        let viewed = add_entry(&root, "viewed", 5000, 300);
+        add_entry(&root, "expired", 10, 10 * 24 * 60 * 60);
+
+        let cache = DownloadCache::new(root.clone());
+        let url = Url::parse("https://example.com/busy").unwrap();
+        let slot = cache.claim(&url).unwrap();
+        std::fs::create_dir_all(&slot.dir).unwrap();
+        cache.set_viewed(Some(viewed.clone()));
+
+        let stats = cache.prune(0, Duration::ZERO);
+        assert!(viewed.exists());
+        assert!(slot.dir.exists());
+        assert!(!root.join("expired").exists());
+        assert_eq!((stats.entries, stats.removed), (2, 1));
+
+        // Once closed and released, clearing removes them
+        drop(slot);
+        cache.set_viewed(None);
+        cache.prune(0, Duration::ZERO);
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 0);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn cleanup_keeps_the_viewed_temp_file() {
+        let day = Duration::from_secs(24 * 60 * 60);
+        let old = SystemTime::now() - 2 * day;
+        let make = |name: &str| {
+            let path = std::env::temp_dir().join(format!("{}test-{}-{}.jsonl", TEMP_PREFIX, name, Uuid::new_v4()));
+            std::fs::File::create(&path).unwrap().set_modified(old).unwrap();
+            path
+        };
+        let viewed = make("viewed");
+        let stale = make("stale");
+
+        cleanup_temp_files(day, Some(&viewed));
+        assert!(viewed.exists());
+        assert!(!stale.exists());
+        std::fs::remove_file(&viewed).unwrap();
+    }
+}
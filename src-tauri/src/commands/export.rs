@@ -1,14 +1,26 @@
+use crate::commands::aggregate::AggregateTable;
+use crate::commands::field_path;
+use crate::commands::file_parser::{self, FileFormat};
 use crate::commands::search::SearchQuery;
+use crate::commands::sort::{check_seekable, read_rows};
+use crate::commands::sort_view::SortViews;
+use crate::commands::validation::{self, RecordValidation, ValidationOptions};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashSet, HashMap};
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tauri::State;
 use tokio::io::AsyncBufReadExt;
 use rust_xlsxwriter::*;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ExportFilter {
+    /// Lines to export, in this order
     pub line_ids: Option<Vec<usize>>,
     pub search_query: Option<SearchQuery>,
+    /// Sorted view the lines are read through; the whole view in its order when
+    /// `line_ids` is unset
+    #[serde(default)]
+    pub view_id: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -20,10 +32,28 @@ pub struct ExportStats {
 #[tauri::command]
 pub async fn export_to_csv(
     path: String,
-    _filter: ExportFilter,
+    filter: ExportFilter,
     output_path: String,
+    views: State<'_, SortViews>,
 ) -> Result<ExportStats, String> {
-    let file_path = PathBuf::from(&path);
+    export_csv(&path, &filter, &output_path, &views).await
+}
+
+async fn export_csv(
+    path: &str,
+    filter: &ExportFilter,
+    output_path: &str,
+    views: &SortViews,
+) -> Result<ExportStats, String> {
+    if let Some(selection) = Selection::from_filter(Path::new(path), filter, views).await? {
+        let output = output_path.to_string();
+        let lines_exported = tokio::task::spawn_blocking(move || selection.write_csv(&output))
+            .await
+            .map_err(|e| format!("Export task failed: {}", e))??;
+        return export_stats(lines_exported, output_path).await;
+    }
+
+    let file_path = PathBuf::from(path);
     let file = tokio::fs::File::open(&file_path)
         .await
         .map_err(|e| format!("Failed to open file: {}", e))?;
@@ -52,13 +82,7 @@ pub async fn export_to_csv(
     let mut headers: Vec<String> = headers_set.into_iter().collect();
     headers.sort();
 
-    // Use CSV crate for valid output
-    let mut wtr = csv::Writer::from_path(&output_path)
-        .map_err(|e| format!("Failed to create CSV writer: {}", e))?;
-
-    // Write header
-    wtr.write_record(&headers)
-        .map_err(|e| format!("Failed to write CSV headers: {}", e))?;
+    let mut wtr = csv_writer(output_path, &headers)?;
 
     let mut lines_exported = 0;
 
@@ -86,7 +110,7 @@ pub async fn export_to_csv(
 
     wtr.flush().map_err(|e| format!("Failed to flush CSV: {}", e))?;
 
-    let file_size = tokio::fs::metadata(&output_path)
+    let file_size = tokio::fs::metadata(output_path)
         .await
         .map_err(|e| format!("Failed to get metadata: {}", e))?
         .len();
@@ -100,10 +124,28 @@ pub async fn export_to_csv(
 #[tauri::command]
 pub async fn export_to_excel(
     path: String,
-    _filter: ExportFilter,
+    filter: ExportFilter,
     output_path: String,
+    views: State<'_, SortViews>,
 ) -> Result<ExportStats, String> {
-    let file_path = PathBuf::from(&path);
+    export_excel(&path, &filter, &output_path, &views).await
+}
+
+async fn export_excel(
+    path: &str,
+    filter: &ExportFilter,
+    output_path: &str,
+    views: &SortViews,
+) -> Result<ExportStats, String> {
+    if let Some(selection) = Selection::from_filter(Path::new(path), filter, views).await? {
+        let output = output_path.to_string();
+        let lines_exported = tokio::task::spawn_blocking(move || selection.write_excel(&output))
+            .await
+            .map_err(|e| format!("Export task failed: {}", e))??;
+        return export_stats(lines_exported, output_path).await;
+    }
+
+    let file_path = PathBuf::from(path);
     let file = tokio::fs::File::open(&file_path)
         .await
         .map_err(|e| format!("Failed to open file: {}", e))?;
@@ -135,82 +177,7 @@ pub async fn export_to_excel(
     let mut workbook = Workbook::new();
     let worksheet = workbook.add_worksheet();
 
-    // Formats
-    let header_format = Format::new()
-        .set_bold()
-        .set_border(FormatBorder::Thin)
-        .set_align(FormatAlign::Center)
-        .set_align(FormatAlign::VerticalCenter)
-        .set_background_color(Color::Silver);
-
-    let subheader_format = Format::new()
-        .set_bold()
-        .set_border(FormatBorder::Thin)
-        .set_align(FormatAlign::Left)
-        .set_background_color(Color::Gray);
-
-    // Group headers logic
-    let mut current_top_key: Option<String> = None;
-    let mut start_col: u16 = 0;
-
-    for (i, header) in headers.iter().enumerate() {
-        let parts: Vec<&str> = header.splitn(2, '_').collect();
-        let top_key = if parts.len() > 1 { parts[0].to_string() } else { "".to_string() };
-
-        let is_last = i == headers.len() - 1;
-        let col_idx = i as u16;
-
-        if let Some(ref current) = current_top_key {
-            if top_key != *current {
-                // End of previous group
-                let end_col = col_idx - 1;
-
-                // Write Header for previous group
-                if current.is_empty() {
-                    // No grouping, just write full headers in row 1??
-                    // Or merge vertically? Let's merge vertically R1:R2 for root keys
-                     for c in start_col..=end_col {
-                        worksheet.merge_range(0, c, 1, c, &headers[c as usize], &header_format)
-                             .map_err(|e| e.to_string())?;
-                     }
-                } else {
-                    // Group header
-                    worksheet.merge_range(0, start_col, 0, end_col, current, &header_format)
-                        .map_err(|e| e.to_string())?;
-                    // Sub headers
-                    for c in start_col..=end_col {
-                        let sub = headers[c as usize].trim_start_matches(&format!("{}_", current));
-                        worksheet.write_string_with_format(1, c, sub, &subheader_format).map_err(|e| e.to_string())?;
-                    }
-                }
-
-                start_col = col_idx;
-                current_top_key = Some(top_key.clone());
-            }
-        } else {
-            current_top_key = Some(top_key.clone());
-        }
-
-        if is_last {
-            // Write last group
-            if let Some(ref current) = current_top_key {
-                let end_col = col_idx;
-                if current.is_empty() {
-                    for c in start_col..=end_col {
-                        worksheet.merge_range(0, c, 1, c, &headers[c as usize], &header_format)
-                             .map_err(|e| e.to_string())?;
-                    }
-                } else {
-                     worksheet.merge_range(0, start_col, 0, end_col, current, &header_format)
-                        .map_err(|e| e.to_string())?;
-                     for c in start_col..=end_col {
-                        let sub = headers[c as usize].trim_start_matches(&format!("{}_", current));
-                        worksheet.write_string_with_format(1, c, sub, &subheader_format).map_err(|e| e.to_string())?;
-                    }
-                }
-            }
-        }
-    }
+    write_excel_headers(worksheet, &headers)?;
 
     // Write Data (Row index starts at 2)
     let mut row_idx = 2;
@@ -235,6 +202,317 @@ pub async fn export_to_excel(
         }
     }
 
+    workbook.save(output_path).map_err(|e| e.to_string())?;
+
+    let file_size = tokio::fs::metadata(output_path)
+        .await
+        .map_err(|e| format!("Failed to get metadata: {}", e))?
+        .len();
+
+    Ok(ExportStats {
+        lines_exported: row_idx as usize - 2,
+        file_size
+    })
+}
+
+/// Command to export an aggregate table, as computed by `aggregate`, as CSV
+#[tauri::command]
+pub async fn export_aggregate_to_csv(
+    table: AggregateTable,
+    output_path: String,
+) -> Result<ExportStats, String> {
+
+    let mut wtr = csv_writer(&output_path, &table.columns)?;
+    for row in &table.rows {
+        wtr.write_record(row.iter().map(|v| cell_text(Some(v))))
+            .map_err(|e| format!("Failed to write CSV record: {}", e))?;
+    }
+    wtr.flush().map_err(|e| format!("Failed to flush CSV: {}", e))?;
+
+    let file_size = tokio::fs::metadata(&output_path)
+        .await
+        .map_err(|e| format!("Failed to get metadata: {}", e))?
+        .len();
+
+    Ok(ExportStats {
+        lines_exported: table.rows.len(),
+        file_size
+    })
+}
+
+/// Command to export an aggregate table as an Excel sheet, numbers as number cells
+#[tauri::command]
+pub async fn export_aggregate_to_excel(
+    table: AggregateTable,
+    output_path: String,
+) -> Result<ExportStats, String> {
+
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    write_excel_headers(worksheet, &table.columns)?;
+
+    for (i, row) in table.rows.iter().enumerate() {
+        let row_idx = i as u32 + 2;
+        for (col_idx, value) in row.iter().enumerate() {
+            match value.as_f64() {
+                Some(n) => worksheet.write_number(row_idx, col_idx as u16, n),
+                None => worksheet.write_string(row_idx, col_idx as u16, cell_text(Some(value))),
+            }
+            .map_err(|e| e.to_string())?;
+        }
+    }
+
+    workbook.save(&output_path).map_err(|e| e.to_string())?;
+
+    let file_size = tokio::fs::metadata(&output_path)
+        .await
+        .map_err(|e| format!("Failed to get metadata: {}", e))?
+        .len();
+
+    Ok(ExportStats {
+        lines_exported: table.rows.len(),
+        file_size
+    })
+}
+
+/// Records scanned for the columns of a selection
+const HEADER_SAMPLE_SIZE: usize = 1000;
+
+/// Rows picked by an `ExportFilter`, read back by byte offset in the order given
+struct Selection {
+    path: PathBuf,
+    format: FileFormat,
+    rows: Vec<(usize, u64)>,
+}
+
+impl Selection {
+    /// Rows located through the sorted view when there is one, else with one pass over
+    /// the file. None when the filter picks no lines (the whole file is exported).
+    async fn from_filter(path: &Path, filter: &ExportFilter, views: &SortViews) -> Result<Option<Selection>, String> {
+        if let Some(ref view_id) = filter.view_id {
+            let view = views.get(view_id)?;
+            if view.path != path {
+                return Err("The sorted view belongs to another file".to_string());
+            }
+            let rows = match filter.line_ids {
+                Some(ref line_ids) => view.rows_for(line_ids),
+                None => view.rows(0, usize::MAX).collect::<Result<_, _>>()?,
+            };
+            return Ok(Some(Selection { path: view.path.clone(), format: view.format, rows }));
+        }
+
+        let Some(line_ids) = filter.line_ids.clone() else {
+            return Ok(None);
+        };
+        check_seekable(path)?;
+        let format = file_parser::detect_file_format(path).await?;
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let rows = match format {
+                // Array items are found by index
+                FileFormat::JsonArray => line_ids.iter().map(|&id| (id, 0)).collect(),
+                FileFormat::JsonL => line_offsets(&path, &line_ids)?,
+            };
+            Ok(Some(Selection { path, format, rows }))
+        })
+        .await
+        .map_err(|e| format!("Export task failed: {}", e))?
+    }
+
+    fn headers(&self) -> Result<Vec<String>, String> {
+        let mut headers_set = HashSet::new();
+        let sample = self.rows.iter().take(HEADER_SAMPLE_SIZE).map(|&row| Ok(row));
+        read_rows(&self.path, self.format, sample, |line| {
+            collect_headers(&line.parsed, "", &mut headers_set);
+            Ok(())
+        })?;
+        let mut headers: Vec<String> = headers_set.into_iter().collect();
+        headers.sort();
+        Ok(headers)
+    }
+
+    fn for_each<F>(&self, mut visit: F) -> Result<(), String>
+    where
+        F: FnMut(&serde_json::Value) -> Result<(), String>,
+    {
+        read_rows(&self.path, self.format, self.rows.iter().map(|&row| Ok(row)), |line| visit(&line.parsed))
+    }
+
+    /// Write the rows as CSV (blocking), returning how many were written
+    fn write_csv(&self, output_path: &str) -> Result<usize, String> {
+        let headers = self.headers()?;
+        let mut wtr = csv_writer(output_path, &headers)?;
+        let mut lines_exported = 0;
+        self.for_each(|json| {
+            wtr.write_record(headers.iter().map(|h| get_flat_value(json, h)))
+                .map_err(|e| format!("Failed to write CSV record: {}", e))?;
+            lines_exported += 1;
+            Ok(())
+        })?;
+        wtr.flush().map_err(|e| format!("Failed to flush CSV: {}", e))?;
+        Ok(lines_exported)
+    }
+
+    /// Write the rows as an Excel sheet (blocking), returning how many were written
+    fn write_excel(&self, output_path: &str) -> Result<usize, String> {
+        let headers = self.headers()?;
+        let mut workbook = Workbook::new();
+        let worksheet = workbook.add_worksheet();
+        write_excel_headers(worksheet, &headers)?;
+
+        // Row index starts at 2
+        let mut row_idx: u32 = 2;
+        self.for_each(|json| {
+            for (col_idx, header) in headers.iter().enumerate() {
+                worksheet.write_string(row_idx, col_idx as u16, get_flat_value(json, header))
+                    .map_err(|e| e.to_string())?;
+            }
+            row_idx += 1;
+            Ok(())
+        })?;
+        workbook.save(output_path).map_err(|e| e.to_string())?;
+        Ok(row_idx as usize - 2)
+    }
+}
+
+/// `(line_id, byte_offset)` of the JSONL lines in `line_ids`, in that order. Missing
+/// and unparseable lines are left out.
+fn line_offsets(path: &Path, line_ids: &[usize]) -> Result<Vec<(usize, u64)>, String> {
+    use std::io::BufRead;
+
+    let wanted: HashSet<usize> = line_ids.iter().copied().collect();
+    let file = std::fs::File::open(path).map_err(|e| format!("Failed to open file: {}", e))?;
+    let mut reader = std::io::BufReader::new(file);
+    let mut offsets = HashMap::new();
+    let mut buf = Vec::new();
+    let mut line_id = 0;
+    let mut byte_offset: u64 = 0;
+
+    loop {
+        buf.clear();
+        let read = reader
+            .read_until(b'\n', &mut buf)
+            .map_err(|e| format!("Failed to read file: {}", e))?;
+        if read == 0 {
+            break;
+        }
+        if wanted.contains(&line_id) && serde_json::from_slice::<serde::de::IgnoredAny>(&buf).is_ok() {
+            offsets.insert(line_id, byte_offset);
+        }
+        byte_offset += read as u64;
+        line_id += 1;
+    }
+
+    Ok(line_ids.iter().filter_map(|&id| offsets.get(&id).map(|&offset| (id, offset))).collect())
+}
+
+async fn export_stats(lines_exported: usize, output_path: &str) -> Result<ExportStats, String> {
+    let file_size = tokio::fs::metadata(output_path)
+        .await
+        .map_err(|e| format!("Failed to get metadata: {}", e))?
+        .len();
+
+    Ok(ExportStats {
+        lines_exported,
+        file_size
+    })
+}
+
+/// Columns of a validation export: one row per violation
+const VIOLATION_HEADERS: [&str; 5] = ["/line", "/instance_path", "/keyword", "/message", "/schema_path"];
+
+fn violation_rows(record: &RecordValidation) -> impl Iterator<Item = [String; 5]> + '_ {
+    record.violations.iter().map(move |v| {
+        [
+            record.line_id.to_string(),
+            v.instance_path.clone(),
+            v.keyword.clone(),
+            v.message.clone(),
+            v.schema_path.clone(),
+        ]
+    })
+}
+
+/// Command to export the schema violations of a file (see `validate_file`) as CSV
+#[tauri::command]
+pub async fn export_violations_to_csv(
+    path: String,
+    file_format: FileFormat,
+    schema_path: String,
+    options: Option<ValidationOptions>,
+    query: Option<SearchQuery>,
+    output_path: String,
+) -> Result<ExportStats, String> {
+    let headers = VIOLATION_HEADERS.map(String::from);
+    let mut wtr = csv_writer(&output_path, &headers)?;
+    let mut lines_exported = 0;
+
+    validation::validate_records(
+        &PathBuf::from(&path),
+        &file_format,
+        Path::new(&schema_path),
+        &options.unwrap_or_default(),
+        query.as_ref(),
+        |record| {
+            for row in violation_rows(&record) {
+                wtr.write_record(&row)
+                    .map_err(|e| format!("Failed to write CSV record: {}", e))?;
+                lines_exported += 1;
+            }
+            Ok(())
+        },
+    )
+    .await?;
+
+    wtr.flush().map_err(|e| format!("Failed to flush CSV: {}", e))?;
+
+    let file_size = tokio::fs::metadata(&output_path)
+        .await
+        .map_err(|e| format!("Failed to get metadata: {}", e))?
+        .len();
+
+    Ok(ExportStats {
+        lines_exported,
+        file_size
+    })
+}
+
+/// Command to export the schema violations of a file as an Excel sheet
+#[tauri::command]
+pub async fn export_violations_to_excel(
+    path: String,
+    file_format: FileFormat,
+    schema_path: String,
+    options: Option<ValidationOptions>,
+    query: Option<SearchQuery>,
+    output_path: String,
+) -> Result<ExportStats, String> {
+    let headers = VIOLATION_HEADERS.map(String::from);
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    write_excel_headers(worksheet, &headers)?;
+
+    // Row index starts at 2
+    let mut row_idx: u32 = 2;
+    validation::validate_records(
+        &PathBuf::from(&path),
+        &file_format,
+        Path::new(&schema_path),
+        &options.unwrap_or_default(),
+        query.as_ref(),
+        |record| {
+            for row in violation_rows(&record) {
+                worksheet.write_number(row_idx, 0, record.line_id as f64).map_err(|e| e.to_string())?;
+                for (col_idx, value) in row.iter().enumerate().skip(1) {
+                    worksheet.write_string(row_idx, col_idx as u16, value).map_err(|e| e.to_string())?;
+                }
+                row_idx += 1;
+            }
+            Ok(())
+        },
+    )
+    .await?;
+
     workbook.save(&output_path).map_err(|e| e.to_string())?;
 
     let file_size = tokio::fs::metadata(&output_path)
@@ -255,11 +533,7 @@ fn collect_headers(json: &serde_json::Value, prefix: &str, headers: &mut HashSet
     match json {
         serde_json::Value::Object(map) => {
             for (key, value) in map {
-                let new_prefix = if prefix.is_empty() {
-                    key.clone()
-                } else {
-                    format!("{}_{}", prefix, key)
-                };
+                let new_prefix = field_path::child(prefix, key);
                 match value {
                     serde_json::Value::Object(_) | serde_json::Value::Array(_) => {
                         collect_headers(value, &new_prefix, headers);
@@ -272,7 +546,7 @@ fn collect_headers(json: &serde_json::Value, prefix: &str, headers: &mut HashSet
         }
         serde_json::Value::Array(arr) => {
              for (index, item) in arr.iter().enumerate() {
-                let new_prefix = format!("{}_{}", prefix, index);
+                let new_prefix = field_path::child(prefix, &index.to_string());
                 match item {
                     serde_json::Value::Object(_) | serde_json::Value::Array(_) => {
                         collect_headers(item, &new_prefix, headers);
@@ -291,28 +565,231 @@ fn collect_headers(json: &serde_json::Value, prefix: &str, headers: &mut HashSet
 
 #[allow(dead_code)]
 fn get_flat_value(json: &serde_json::Value, path: &str) -> String {
-    let parts: Vec<&str> = path.split('_').collect();
-    let mut current = json;
-
-    for part in parts {
-        if let Ok(index) = part.parse::<usize>() {
-            if let Some(arr) = current.as_array() {
-                if let Some(item) = arr.get(index) {
-                    current = item;
-                } else { return "".to_string(); }
-            } else { return "".to_string(); }
+    cell_text(field_path::resolve(json, path))
+}
+
+/// Text of a cell: strings as is, null and missing values empty, the rest as JSON
+fn cell_text(value: Option<&serde_json::Value>) -> String {
+    match value {
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(serde_json::Value::Null) | None => "".to_string(),
+        Some(v) => v.to_string()
+    }
+}
+
+/// CSV writer with the header row written (`field_path::label` of each column)
+fn csv_writer(output_path: &str, headers: &[String]) -> Result<csv::Writer<std::fs::File>, String> {
+    // Use CSV crate for valid output
+    let mut wtr = csv::Writer::from_path(output_path)
+        .map_err(|e| format!("Failed to create CSV writer: {}", e))?;
+
+    wtr.write_record(headers.iter().map(|h| field_path::label(h)))
+        .map_err(|e| format!("Failed to write CSV headers: {}", e))?;
+    Ok(wtr)
+}
+
+/// Two header rows: nested columns grouped under their top-level key, the others
+/// merged over both rows. Data starts at row 2.
+fn write_excel_headers(worksheet: &mut Worksheet, headers: &[String]) -> Result<(), String> {
+    // Formats
+    let header_format = Format::new()
+        .set_bold()
+        .set_border(FormatBorder::Thin)
+        .set_align(FormatAlign::Center)
+        .set_align(FormatAlign::VerticalCenter)
+        .set_background_color(Color::Silver);
+
+    let subheader_format = Format::new()
+        .set_bold()
+        .set_border(FormatBorder::Thin)
+        .set_align(FormatAlign::Left)
+        .set_background_color(Color::Gray);
+
+    // Group headers logic
+    let mut current_top_key: Option<String> = None;
+    let mut start_col: u16 = 0;
+
+    for (i, header) in headers.iter().enumerate() {
+        let parts = field_path::segments(header);
+        let top_key = if parts.len() > 1 { parts[0].clone() } else { "".to_string() };
+
+        let is_last = i == headers.len() - 1;
+        let col_idx = i as u16;
+
+        if let Some(ref current) = current_top_key {
+            if top_key != *current {
+                // End of previous group
+                let end_col = col_idx - 1;
+
+                // Write Header for previous group
+                if current.is_empty() {
+                    // No grouping, just write full headers in row 1??
+                    // Or merge vertically? Let's merge vertically R1:R2 for root keys
+                     for c in start_col..=end_col {
+                        worksheet.merge_range(0, c, 1, c, field_path::label(&headers[c as usize]), &header_format)
+                             .map_err(|e| e.to_string())?;
+                     }
+                } else {
+                    // Group header
+                    write_group_header(worksheet, start_col, end_col, current, &header_format)?;
+                    // Sub headers
+                    for c in start_col..=end_col {
+                        let sub = sub_header(&headers[c as usize]);
+                        worksheet.write_string_with_format(1, c, sub, &subheader_format).map_err(|e| e.to_string())?;
+                    }
+                }
+
+                start_col = col_idx;
+                current_top_key = Some(top_key.clone());
+            }
         } else {
-             if let Some(obj) = current.as_object() {
-                if let Some(val) = obj.get(part) {
-                    current = val;
-                } else { return "".to_string(); }
-            } else { return "".to_string(); }
+            current_top_key = Some(top_key.clone());
+        }
+
+        if is_last {
+            // Write last group
+            if let Some(ref current) = current_top_key {
+                let end_col = col_idx;
+                if current.is_empty() {
+                    for c in start_col..=end_col {
+                        worksheet.merge_range(0, c, 1, c, field_path::label(&headers[c as usize]), &header_format)
+                             .map_err(|e| e.to_string())?;
+                    }
+                } else {
+                     write_group_header(worksheet, start_col, end_col, current, &header_format)?;
+                     for c in start_col..=end_col {
+                        let sub = sub_header(&headers[c as usize]);
+                        worksheet.write_string_with_format(1, c, sub, &subheader_format).map_err(|e| e.to_string())?;
+                    }
+                }
+            }
         }
     }
 
-    match current {
-        serde_json::Value::String(s) => s.clone(),
-        serde_json::Value::Null => "".to_string(),
-        v => v.to_string()
+    Ok(())
+}
+
+/// Group header over columns `start..=end` (Excel refuses to merge a single cell)
+fn write_group_header(worksheet: &mut Worksheet, start: u16, end: u16, text: &str, format: &Format) -> Result<(), String> {
+    if start == end {
+        worksheet.write_string_with_format(0, start, text, format).map_err(|e| e.to_string())?;
+    } else {
+        worksheet.merge_range(0, start, 0, end, text, format).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Excel sub-header for a grouped column: the pointer below its top-level key
+fn sub_header(path: &str) -> String {
+    field_path::segments(path)[1..].join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_util::temp_file;
+
+    #[test]
+    fn headers_are_pointers() {
+        let record = serde_json::json!({
+            "user": { "id": 1, "address": { "city": "Oslo" } },
+            "user_id": 2,
+            "user/id": 3,
+            "tags": ["x", { "k": null }],
+            "empty": {},
+        });
+        let mut headers = HashSet::new();
+        collect_headers(&record, "", &mut headers);
+        let mut headers: Vec<String> = headers.into_iter().collect();
+        headers.sort();
+        assert_eq!(
+            headers,
+            vec!["/tags/0", "/tags/1/k", "/user/address/city", "/user/id", "/user_id", "/user~1id"]
+        );
+
+        let values: Vec<String> = headers.iter().map(|h| get_flat_value(&record, h)).collect();
+        assert_eq!(values, vec!["x", "", "Oslo", "1", "2", "3"]);
+        assert_eq!(get_flat_value(&record, "/missing"), "");
+        assert_eq!(get_flat_value(&record, "/user"), r#"{"address":{"city":"Oslo"},"id":1}"#);
+    }
+
+    #[test]
+    fn scalar_records_have_no_headers() {
+        let mut headers = HashSet::new();
+        collect_headers(&serde_json::json!(42), "", &mut headers);
+        collect_headers(&serde_json::json!([]), "", &mut headers);
+        assert!(headers.is_empty());
+    }
+
+    #[test]
+    fn sub_headers() {
+        assert_eq!(sub_header("/user/address/city"), "address/city");
+        assert_eq!(sub_header("/user/id"), "id");
+        assert_eq!(sub_header("/user/a~1b"), "a/b");
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn csv_headers_are_labels() {
+        let path = temp_file("records.jsonl", "{\"user\":{\"id\":1},\"user_id\":\"a\"}\n{\"user_id\":\"b,c\"}\nnot json\n");
+        let output_path = path.with_extension("csv");
+
+        let filter = ExportFilter {
+            line_ids: None,
+            search_query: None,
+            view_id: None,
+        };
+        let stats = export_csv(
+            &path.to_string_lossy(),
+            &filter,
+            &output_path.to_string_lossy(),
+            &SortViews::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.lines_exported, 2);
+        assert_eq!(std::fs::read_to_string(&output_path).unwrap(), "user/id,user_id\n1,a\n,\"b,c\"\n");
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn line_ids_are_exported_in_the_order_given() {
+        let path = temp_file("records.jsonl", "{\"k\":3,\"a\":\"x\"}\nnot json\n{\"k\":1}\n{\"k\":2,\"b\":true}\n");
+        let output_path = path.with_extension("csv");
+        let (path_str, output) = (path.to_string_lossy().to_string(), output_path.to_string_lossy().to_string());
+        let views = SortViews::default();
+        let export = |line_ids: Option<Vec<usize>>, view_id: Option<String>| {
+            let filter = ExportFilter { line_ids, search_query: None, view_id };
+            let (path_str, output, views) = (&path_str, &output, &views);
+            async move {
+                let stats = export_csv(path_str, &filter, output, views).await.unwrap();
+                (stats.lines_exported, std::fs::read_to_string(output).unwrap())
+            }
+        };
+
+        // Without a view the offsets come from the file; the unparseable line is skipped
+        assert_eq!(export(Some(vec![3, 1, 0]), None).await, (2, "a,b,k\n,true,2\nx,,3\n".to_string()));
+
+        let by_k = vec![serde_json::from_value(serde_json::json!({ "column": "/k", "direction": "asc" })).unwrap()];
+        let view = views.get_or_create(path.clone(), FileFormat::JsonL, by_k, None).await.unwrap();
+        assert_eq!(
+            export(None, Some(view.id.clone())).await,
+            (3, "a,b,k\n,,1\n,true,2\nx,,3\n".to_string())
+        );
+        assert_eq!(export(Some(vec![0, 2]), Some(view.id.clone())).await, (2, "a,k\nx,3\n,1\n".to_string()));
+
+        let excel = path.with_extension("xlsx");
+        let filter = ExportFilter { line_ids: None, search_query: None, view_id: Some(view.id.clone()) };
+        let stats = export_excel(&path_str, &filter, &excel.to_string_lossy(), &views).await.unwrap();
+        assert_eq!(stats.lines_exported, 3);
+
+        let other = ExportFilter { line_ids: None, search_query: None, view_id: Some(view.id.clone()) };
+        let error = export_csv(&output, &other, &output, &views).await.unwrap_err();
+        assert_eq!(error, "The sorted view belongs to another file");
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        std::fs::remove_file(&excel).unwrap();
+    }
+}
+
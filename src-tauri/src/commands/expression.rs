@@ -0,0 +1,557 @@
+// Small expressions evaluated per record, e.g. `$.end - $.start`, `len($.items)`
+// or `lower(trim($.name))`. Fields are JSONPath queries; a query matching several
+// values yields them as an array.
+
+use jsonpath_rust::path::config::JsonPathConfig;
+use jsonpath_rust::JsonPathInst;
+use serde_json::Value;
+use std::str::FromStr;
+use crate::commands::sort::parse_timestamp;
+
+/// Parsed expression, compiled once and evaluated for every record
+#[derive(Debug, Clone)]
+pub enum Expression {
+    Literal(Value),
+    Path(Box<JsonPathInst>),
+    Negate(Box<Expression>),
+    Binary(BinaryOp, Box<Expression>, Box<Expression>),
+    Call(Function, Vec<Expression>),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Function {
+    Len,
+    Lower,
+    Upper,
+    Trim,
+    Abs,
+    Round,
+    Floor,
+    Ceil,
+    Min,
+    Max,
+    Number,
+    Str,
+    Date,
+    Concat,
+    Coalesce,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        Some(match name {
+            "len" | "length" => Function::Len,
+            "lower" => Function::Lower,
+            "upper" => Function::Upper,
+            "trim" => Function::Trim,
+            "abs" => Function::Abs,
+            "round" => Function::Round,
+            "floor" => Function::Floor,
+            "ceil" => Function::Ceil,
+            "min" => Function::Min,
+            "max" => Function::Max,
+            "number" => Function::Number,
+            "string" => Function::Str,
+            "date" => Function::Date,
+            "concat" => Function::Concat,
+            "coalesce" => Function::Coalesce,
+            _ => return None,
+        })
+    }
+
+    /// Allowed argument counts (min, max)
+    fn arity(self) -> (usize, usize) {
+        match self {
+            Function::Min | Function::Max | Function::Concat | Function::Coalesce => (1, usize::MAX),
+            _ => (1, 1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Path(String),
+    Ident(String),
+    Op(char),
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl FromStr for Expression {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.parse_sum()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some(token) => Err(format!("Unexpected {:?} in expression", token)),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            }
+            '+' | '-' | '*' | '/' | '%' => {
+                tokens.push(Token::Op(c));
+                i += 1;
+            }
+            '\'' | '"' => {
+                let (text, next) = read_quoted(&chars, i)?;
+                tokens.push(Token::Str(text));
+                i = next;
+            }
+            '$' => {
+                // A JSONPath runs until an operator, comma or closing paren outside brackets/quotes
+                let start = i;
+                let mut depth = 0usize;
+                let mut quote: Option<char> = None;
+                while i < chars.len() {
+                    let c = chars[i];
+                    match quote {
+                        Some(q) if c == q => quote = None,
+                        Some(_) => {}
+                        None => match c {
+                            '\'' | '"' => quote = Some(c),
+                            '[' | '(' => depth += 1,
+                            ']' | ')' if depth > 0 => depth -= 1,
+                            '*' if chars[i - 1] == '.' => {}
+                            ')' | ',' | '+' | '-' | '*' | '/' | '%' if depth == 0 => break,
+                            c if c.is_whitespace() && depth == 0 => break,
+                            _ => {}
+                        },
+                    }
+                    i += 1;
+                }
+                tokens.push(Token::Path(chars[start..i].iter().collect()));
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.' || chars[i] == 'e' || chars[i] == 'E'
+                    || ((chars[i] == '+' || chars[i] == '-') && matches!(chars[i - 1], 'e' | 'E')))
+                {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let n = text.parse::<f64>().map_err(|_| format!("Invalid number {} in expression", text))?;
+                tokens.push(Token::Number(n));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            _ => return Err(format!("Unexpected character '{}' in expression", c)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Read a quoted string starting at `start`, returning it and the index past the closing quote
+fn read_quoted(chars: &[char], start: usize) -> Result<(String, usize), String> {
+    let quote = chars[start];
+    let mut text = String::new();
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                text.push(chars[i + 1]);
+                i += 2;
+            }
+            c if c == quote => return Ok((text, i + 1)),
+            c => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+    Err("Unterminated string in expression".to_string())
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_op(&self, ops: &[char]) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(c)) if ops.contains(c) => Some(*c),
+            _ => None,
+        }
+    }
+
+    fn parse_sum(&mut self) -> Result<Expression, String> {
+        let mut left = self.parse_product()?;
+        while let Some(op) = self.peek_op(&['+', '-']) {
+            self.pos += 1;
+            let right = self.parse_product()?;
+            let op = if op == '+' { BinaryOp::Add } else { BinaryOp::Sub };
+            left = Expression::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_product(&mut self) -> Result<Expression, String> {
+        let mut left = self.parse_unary()?;
+        while let Some(op) = self.peek_op(&['*', '/', '%']) {
+            self.pos += 1;
+            let right = self.parse_unary()?;
+            let op = match op {
+                '*' => BinaryOp::Mul,
+                '/' => BinaryOp::Div,
+                _ => BinaryOp::Rem,
+            };
+            left = Expression::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, String> {
+        if self.peek_op(&['-']).is_some() {
+            self.pos += 1;
+            return Ok(Expression::Negate(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, String> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expression::Literal(number_value(n))),
+            Some(Token::Str(s)) => Ok(Expression::Literal(Value::String(s))),
+            Some(Token::Path(p)) => {
+                let path = JsonPathInst::from_str(&p).map_err(|e| format!("Invalid JSONPath {}: {}", p, e))?;
+                Ok(Expression::Path(Box::new(path)))
+            }
+            Some(Token::LParen) => {
+                let expr = self.parse_sum()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => Err("Missing ) in expression".to_string()),
+                }
+            }
+            Some(Token::Ident(name)) => match name.as_str() {
+                "true" => Ok(Expression::Literal(Value::Bool(true))),
+                "false" => Ok(Expression::Literal(Value::Bool(false))),
+                "null" => Ok(Expression::Literal(Value::Null)),
+                _ => {
+                    let function = Function::from_name(&name).ok_or_else(|| format!("Unknown function {}", name))?;
+                    self.parse_call(function, &name)
+                }
+            },
+            Some(token) => Err(format!("Unexpected {:?} in expression", token)),
+            None => Err("Unexpected end of expression".to_string()),
+        }
+    }
+
+    fn parse_call(&mut self, function: Function, name: &str) -> Result<Expression, String> {
+        if self.next() != Some(Token::LParen) {
+            return Err(format!("Expected ( after {}", name));
+        }
+        let mut args = Vec::new();
+        if self.tokens.get(self.pos) == Some(&Token::RParen) {
+            self.pos += 1;
+        } else {
+            loop {
+                args.push(self.parse_sum()?);
+                match self.next() {
+                    Some(Token::Comma) => continue,
+                    Some(Token::RParen) => break,
+                    _ => return Err(format!("Missing ) after arguments of {}", name)),
+                }
+            }
+        }
+
+        let (min, max) = function.arity();
+        if args.len() < min || args.len() > max {
+            return Err(format!("Wrong number of arguments for {}", name));
+        }
+        Ok(Expression::Call(function, args))
+    }
+}
+
+impl Expression {
+    /// Evaluate against one record. Missing fields and invalid operations give null.
+    pub fn evaluate(&self, record: &Value) -> Value {
+        match self {
+            Expression::Literal(v) => v.clone(),
+            Expression::Path(path) => {
+                let mut found: Vec<Value> = path
+                    .find_slice(record, JsonPathConfig::default())
+                    .into_iter()
+                    .map(|v| (*v).clone())
+                    .collect();
+                match found.len() {
+                    0 => Value::Null,
+                    1 => found.pop().unwrap_or(Value::Null),
+                    _ => Value::Array(found),
+                }
+            }
+            Expression::Negate(inner) => match as_number(&inner.evaluate(record)) {
+                Some(n) => number_value(-n),
+                None => Value::Null,
+            },
+            Expression::Binary(op, left, right) => {
+                let (Some(a), Some(b)) = (as_number(&left.evaluate(record)), as_number(&right.evaluate(record))) else {
+                    return Value::Null;
+                };
+                number_value(match op {
+                    BinaryOp::Add => a + b,
+                    BinaryOp::Sub => a - b,
+                    BinaryOp::Mul => a * b,
+                    BinaryOp::Div => a / b,
+                    BinaryOp::Rem => a % b,
+                })
+            }
+            Expression::Call(function, args) => {
+                let values: Vec<Value> = args.iter().map(|a| a.evaluate(record)).collect();
+                call(*function, values)
+            }
+        }
+    }
+}
+
+fn call(function: Function, args: Vec<Value>) -> Value {
+    // Arity is checked when parsing, so there is always a first argument
+    let first = &args[0];
+    match function {
+        Function::Len => match first {
+            Value::String(s) => number_value(s.chars().count() as f64),
+            Value::Array(a) => number_value(a.len() as f64),
+            Value::Object(o) => number_value(o.len() as f64),
+            _ => Value::Null,
+        },
+        Function::Lower => map_string(first, |s| s.to_lowercase()),
+        Function::Upper => map_string(first, |s| s.to_uppercase()),
+        Function::Trim => map_string(first, |s| s.trim().to_string()),
+        Function::Abs => map_number(first, f64::abs),
+        Function::Round => map_number(first, f64::round),
+        Function::Floor => map_number(first, f64::floor),
+        Function::Ceil => map_number(first, f64::ceil),
+        Function::Min => numbers(&args).reduce(f64::min).map(number_value).unwrap_or(Value::Null),
+        Function::Max => numbers(&args).reduce(f64::max).map(number_value).unwrap_or(Value::Null),
+        Function::Number => as_number(first).map(number_value).unwrap_or(Value::Null),
+        Function::Str => match first {
+            Value::Null => Value::Null,
+            Value::String(s) => Value::String(s.clone()),
+            v => Value::String(v.to_string()),
+        },
+        // Seconds since the epoch, so differences of dates are durations in seconds
+        Function::Date => parse_timestamp(first)
+            .map(|micros| number_value(micros as f64 / 1e6))
+            .unwrap_or(Value::Null),
+        Function::Concat => Value::String(
+            args.iter()
+                .map(|v| match v {
+                    Value::Null => String::new(),
+                    Value::String(s) => s.clone(),
+                    v => v.to_string(),
+                })
+                .collect(),
+        ),
+        Function::Coalesce => args.into_iter().find(|v| !v.is_null()).unwrap_or(Value::Null),
+    }
+}
+
+/// Numeric value of a JSON value: numbers, numeric strings and booleans
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse::<f64>().ok().filter(|n| n.is_finite()),
+        Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+        _ => None,
+    }
+}
+
+/// Numbers among the arguments, looking inside arrays (e.g. `max($.items[*])`)
+fn numbers(args: &[Value]) -> impl Iterator<Item = f64> + '_ {
+    args.iter()
+        .flat_map(|v| match v {
+            Value::Array(items) => items.iter().collect::<Vec<_>>(),
+            v => vec![v],
+        })
+        .filter_map(as_number)
+}
+
+/// JSON number, or null for NaN/infinity (e.g. division by zero)
+fn number_value(n: f64) -> Value {
+    serde_json::Number::from_f64(n).map(Value::Number).unwrap_or(Value::Null)
+}
+
+fn map_string(value: &Value, f: impl Fn(&str) -> String) -> Value {
+    match value {
+        Value::String(s) => Value::String(f(s)),
+        Value::Null | Value::Array(_) | Value::Object(_) => Value::Null,
+        v => Value::String(f(&v.to_string())),
+    }
+}
+
+fn map_number(value: &Value, f: impl Fn(f64) -> f64) -> Value {
+    as_number(value).map(|n| number_value(f(n))).unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn eval(source: &str, record: &Value) -> Value {
+        Expression::from_str(source).unwrap().evaluate(record)
+    }
+
+    fn parse_error(source: &str) -> String {
+        Expression::from_str(source).unwrap_err()
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(
+            tokenize("len($.items) * 2.5e-1 + 'a\\'b'").unwrap(),
+            vec![
+                Token::Ident("len".to_string()),
+                Token::LParen,
+                Token::Path("$.items".to_string()),
+                Token::RParen,
+                Token::Op('*'),
+                Token::Number(0.25),
+                Token::Op('+'),
+                Token::Str("a'b".to_string()),
+            ]
+        );
+        assert!(tokenize("1 # 2").is_err());
+        assert!(tokenize("1..2").is_err());
+    }
+
+    #[test]
+    fn paths_end_at_operators_outside_brackets() {
+        let paths = |source: &str| -> Vec<String> {
+            tokenize(source)
+                .unwrap()
+                .into_iter()
+                .filter_map(|t| match t {
+                    Token::Path(p) => Some(p),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(paths("$.end-$.start"), vec!["$.end", "$.start"]);
+        assert_eq!(paths("$['a-b'].c + 1"), vec!["$['a-b'].c"]);
+        assert_eq!(paths("$.items[?(@.price > 10)].price"), vec!["$.items[?(@.price > 10)].price"]);
+        assert_eq!(paths("max($.a.*, $.b)"), vec!["$.a.*", "$.b"]);
+        assert_eq!(paths("$.a * 2"), vec!["$.a"]);
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        let record = json!({});
+        assert_eq!(eval("1 + 2 * 3", &record), json!(7.0));
+        assert_eq!(eval("(1 + 2) * 3", &record), json!(9.0));
+        assert_eq!(eval("10 - 2 - 3", &record), json!(5.0));
+        assert_eq!(eval("12 / 3 / 2", &record), json!(2.0));
+        assert_eq!(eval("-2 * 3", &record), json!(-6.0));
+        assert_eq!(eval("--2", &record), json!(2.0));
+        assert_eq!(eval("7 % 4 + 1", &record), json!(4.0));
+    }
+
+    #[test]
+    fn fields_and_functions() {
+        let record = json!({
+            "start": 10,
+            "end": "25",
+            "name": "  Ada Lovelace ",
+            "items": [3, 9, "4", null],
+            "tags": { "a": 1, "b": 2 },
+            "ok": true,
+        });
+        assert_eq!(eval("$.end - $.start", &record), json!(15.0));
+        assert_eq!(eval("len($.items)", &record), json!(4.0));
+        assert_eq!(eval("length($.name)", &record), json!(15.0));
+        assert_eq!(eval("len($.tags)", &record), json!(2.0));
+        assert_eq!(eval("lower(trim($.name))", &record), json!("ada lovelace"));
+        assert_eq!(eval("upper($.start)", &record), json!("10"));
+        assert_eq!(eval("max($.items[*])", &record), json!(9.0));
+        assert_eq!(eval("min($.items[*], 1)", &record), json!(1.0));
+        assert_eq!(eval("round(2.5) + floor(-1.5) + ceil(0.2) + abs(-4)", &record), json!(6.0));
+        assert_eq!(eval("number($.end) + $.ok", &record), json!(26.0));
+        assert_eq!(eval("string($.start)", &record), json!("10"));
+        assert_eq!(eval("concat($.start, '-', $.missing, $.ok)", &record), json!("10-true"));
+        assert_eq!(eval("coalesce($.missing, null, 'x', 'y')", &record), json!("x"));
+        assert_eq!(
+            eval("date('2024-01-01T00:01:00Z') - date('2024-01-01T00:00:00Z')", &record),
+            json!(60.0)
+        );
+    }
+
+    #[test]
+    fn invalid_operations_give_null() {
+        let record = json!({ "name": "x", "items": [1, 2] });
+        assert_eq!(eval("$.missing + 1", &record), Value::Null);
+        assert_eq!(eval("$.name * 2", &record), Value::Null);
+        assert_eq!(eval("1 / 0", &record), Value::Null);
+        assert_eq!(eval("-$.name", &record), Value::Null);
+        assert_eq!(eval("len(42)", &record), Value::Null);
+        assert_eq!(eval("lower($.items)", &record), Value::Null);
+        assert_eq!(eval("date('not a date')", &record), Value::Null);
+        assert_eq!(eval("max($.name)", &record), Value::Null);
+        // A path matching several values gives them as an array
+        assert_eq!(eval("$.items[*]", &record), json!([1, 2]));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(parse_error(""), "Unexpected end of expression");
+        assert_eq!(parse_error("1 +"), "Unexpected end of expression");
+        assert_eq!(parse_error("(1 + 2"), "Missing ) in expression");
+        assert_eq!(parse_error("1 2"), "Unexpected Number(2.0) in expression");
+        assert_eq!(parse_error("foo(1)"), "Unknown function foo");
+        assert_eq!(parse_error("lower 'a'"), "Expected ( after lower");
+        assert_eq!(parse_error("len()"), "Wrong number of arguments for len");
+        assert_eq!(parse_error("len(1, 2)"), "Wrong number of arguments for len");
+        assert_eq!(parse_error("max(1, 2"), "Missing ) after arguments of max");
+        assert_eq!(parse_error("'open"), "Unterminated string in expression");
+        assert!(parse_error("$.a[").starts_with("Invalid JSONPath $.a["));
+        assert!(Expression::from_str("max()").is_err());
+        assert!(Expression::from_str("coalesce($.a, $.b, $.c)").is_ok());
+    }
+}
@@ -0,0 +1,71 @@
+// Field paths are JSON Pointers (RFC 6901): `/user/id`, `/user_id`, `/tags/0`.
+// Unlike underscore-joined names they stay unambiguous and can index arrays.
+
+/// Resolve a JSON Pointer against a record. An empty path is the record itself.
+pub fn resolve<'v>(json: &'v serde_json::Value, path: &str) -> Option<&'v serde_json::Value> {
+    json.pointer(path)
+}
+
+/// Append one key (or array index) to a pointer, escaping `~` and `/`
+pub fn child(prefix: &str, key: &str) -> String {
+    format!("{}/{}", prefix, key.replace('~', "~0").replace('/', "~1"))
+}
+
+/// Split a pointer into its unescaped segments
+pub fn segments(path: &str) -> Vec<String> {
+    if path.is_empty() {
+        return Vec::new();
+    }
+    path.trim_start_matches('/')
+        .split('/')
+        .map(|s| s.replace("~1", "/").replace("~0", "~"))
+        .collect()
+}
+
+/// Human readable label for a pointer, used for export headers (`/user/id` -> `user/id`)
+pub fn label(path: &str) -> &str {
+    path.strip_prefix('/').unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_with_separators_stay_apart() {
+        let record = serde_json::json!({
+            "user": { "id": 1 },
+            "user_id": 2,
+            "user/id": 3,
+            "a~b": 4,
+            "tags": ["x", "y"],
+        });
+        assert_eq!(child("/user", "id"), "/user/id");
+        assert_eq!(child("", "user/id"), "/user~1id");
+        assert_eq!(child("", "a~b"), "/a~0b");
+
+        assert_eq!(resolve(&record, "/user/id"), Some(&serde_json::json!(1)));
+        assert_eq!(resolve(&record, "/user_id"), Some(&serde_json::json!(2)));
+        assert_eq!(resolve(&record, &child("", "user/id")), Some(&serde_json::json!(3)));
+        assert_eq!(resolve(&record, &child("", "a~b")), Some(&serde_json::json!(4)));
+        assert_eq!(resolve(&record, "/tags/1"), Some(&serde_json::json!("y")));
+        assert_eq!(resolve(&record, "/tags/2"), None);
+        assert_eq!(resolve(&record, ""), Some(&record));
+    }
+
+    #[test]
+    fn segments_undo_child() {
+        let path = child(&child(&child("", "a/b"), "c~1"), "0");
+        assert_eq!(path, "/a~1b/c~01/0");
+        assert_eq!(segments(&path), vec!["a/b", "c~1", "0"]);
+        assert!(segments("").is_empty());
+        assert_eq!(segments("/"), vec![""]);
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(label("/user/id"), "user/id");
+        assert_eq!(label("/"), "");
+        assert_eq!(label(""), "");
+    }
+}
@@ -1,7 +1,9 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use tauri::ipc::Channel;
-use tokio::io::AsyncBufReadExt;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+use async_compression::tokio::bufread::GzipDecoder;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct JsonLine {
@@ -19,12 +21,76 @@ pub struct FileMetadata {
     pub format: FileFormat,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 pub enum FileFormat {
     JsonL,
     JsonArray,
 }
 
+/// Open a file for buffered reading, transparently decompressing `.gz` files
+pub async fn open_record_reader(path: &Path) -> Result<Pin<Box<dyn AsyncBufRead + Send>>, String> {
+    let file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| format!("Failed to open file: {}", e))?;
+    let reader = tokio::io::BufReader::new(file);
+
+    if is_gzip(path) {
+        let mut decoder = GzipDecoder::new(reader);
+        // Rotated logs are often several gzip members concatenated
+        decoder.multiple_members(true);
+        Ok(Box::pin(tokio::io::BufReader::new(decoder)))
+    } else {
+        Ok(Box::pin(reader))
+    }
+}
+
+/// Guess the format of a file the same way `parse_file_streaming` does: `.jsonl`/`.ndjson`
+/// are always JSONL, anything else starting with `[` is a JSON array
+pub async fn detect_file_format(path: &Path) -> Result<FileFormat, String> {
+    let extension = record_extension(path);
+    if extension == "jsonl" || extension == "ndjson" {
+        return Ok(FileFormat::JsonL);
+    }
+
+    let mut reader = open_record_reader(path).await?;
+    loop {
+        let buf = reader
+            .fill_buf()
+            .await
+            .map_err(|e| format!("Failed to read file: {}", e))?;
+        if buf.is_empty() {
+            return Ok(FileFormat::JsonL);
+        }
+        if let Some(&first) = buf.iter().find(|b| !b.is_ascii_whitespace()) {
+            return Ok(if first == b'[' { FileFormat::JsonArray } else { FileFormat::JsonL });
+        }
+        let consumed = buf.len();
+        reader.as_mut().consume(consumed);
+    }
+}
+
+/// Whether the file name looks like something the viewer can search (optionally gzipped)
+pub fn is_record_file(path: &Path) -> bool {
+    matches!(record_extension(path).as_str(), "json" | "jsonl" | "ndjson")
+}
+
+pub(crate) fn is_gzip(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.eq_ignore_ascii_case("gz"))
+        .unwrap_or(false)
+}
+
+/// Lowercased extension of the records inside the file, looking through a `.gz` suffix
+fn record_extension(path: &Path) -> String {
+    let inner = if is_gzip(path) { path.file_stem().map(Path::new) } else { Some(path) };
+    inner
+        .and_then(|p| p.extension())
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default()
+}
+
 
 
 #[tauri::command]
@@ -210,4 +276,86 @@ fn process_single_line(
     } else {
         Ok(false)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use crate::commands::test_util::{gzip, temp_path};
+
+    async fn read_all(path: &Path) -> String {
+        let mut content = String::new();
+        open_record_reader(path).await.unwrap().read_to_string(&mut content).await.unwrap();
+        content
+    }
+
+    #[tokio::test]
+    async fn gzip_files_are_decompressed() {
+        let records = "{\"a\":1}\n{\"a\":2}\n";
+        for name in ["records.jsonl.gz", "RECORDS.JSONL.GZ"] {
+            let path = temp_path(name);
+            std::fs::write(&path, gzip(records).await).unwrap();
+            assert_eq!(read_all(&path).await, records);
+            assert!(matches!(detect_file_format(&path).await, Ok(FileFormat::JsonL)));
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn concatenated_gzip_members_are_read_through() {
+        let path = temp_path("rotated.jsonl.gz");
+        let mut content = gzip("{\"a\":1}\n").await;
+        content.extend(gzip("{\"a\":2}\n").await);
+        std::fs::write(&path, content).unwrap();
+        assert_eq!(read_all(&path).await, "{\"a\":1}\n{\"a\":2}\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn other_files_are_read_as_is() {
+        // Only the extension decides, so compressed bytes under another name stay compressed
+        let path = temp_path("records.jsonl");
+        let compressed = gzip("{}\n").await;
+        std::fs::write(&path, &compressed).unwrap();
+        let mut raw = Vec::new();
+        open_record_reader(&path).await.unwrap().read_to_end(&mut raw).await.unwrap();
+        assert_eq!(raw, compressed);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn corrupt_gzip_is_a_read_error() {
+        let path = temp_path("broken.json.gz");
+        std::fs::write(&path, b"not gzip at all").unwrap();
+        let mut content = String::new();
+        let mut reader = open_record_reader(&path).await.unwrap();
+        assert!(reader.read_to_string(&mut content).await.is_err());
+        assert!(detect_file_format(&path).await.is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn format_of_gzipped_arrays() {
+        let path = temp_path("array.json.gz");
+        std::fs::write(&path, gzip("  \n [{\"a\":1}]").await).unwrap();
+        assert!(matches!(detect_file_format(&path).await, Ok(FileFormat::JsonArray)));
+        std::fs::remove_file(&path).unwrap();
+
+        // The extension wins for JSONL names, whatever the content
+        let path = temp_path("array.ndjson.gz");
+        std::fs::write(&path, gzip("[1]").await).unwrap();
+        assert!(matches!(detect_file_format(&path).await, Ok(FileFormat::JsonL)));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn record_file_names() {
+        for name in ["a.json", "a.JSONL", "a.ndjson", "a.json.gz", "a.jsonl.GZ", "logs.2024.ndjson.gz"] {
+            assert!(is_record_file(Path::new(name)), "{}", name);
+        }
+        for name in ["a.gz", "a.txt", "a.txt.gz", "json", "a.json.zip"] {
+            assert!(!is_record_file(Path::new(name)), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,370 @@
+// Histograms of a field: numeric values in equal-width buckets, or timestamps in
+// time buckets (an event-rate timeline). Empty buckets are kept so the result plots as is.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use crate::commands::field_path;
+use crate::commands::file_parser::FileFormat;
+use crate::commands::search::{scan_matching_records, SearchQuery};
+use crate::commands::sort::parse_timestamp;
+
+const DEFAULT_TARGET_BUCKETS: usize = 50;
+/// Buckets a histogram may have, empty ones included
+const MAX_BUCKETS: usize = 10_000;
+
+const SECOND_MS: i64 = 1_000;
+const MINUTE_MS: i64 = 60 * SECOND_MS;
+const HOUR_MS: i64 = 60 * MINUTE_MS;
+const DAY_MS: i64 = 24 * HOUR_MS;
+
+/// Time bucket sizes picked from when the interval is automatic
+const TIME_STEPS_MS: &[i64] = &[
+    1, 5, 10, 50, 100, 500,
+    SECOND_MS, 2 * SECOND_MS, 5 * SECOND_MS, 10 * SECOND_MS, 15 * SECOND_MS, 30 * SECOND_MS,
+    MINUTE_MS, 2 * MINUTE_MS, 5 * MINUTE_MS, 10 * MINUTE_MS, 15 * MINUTE_MS, 30 * MINUTE_MS,
+    HOUR_MS, 2 * HOUR_MS, 3 * HOUR_MS, 6 * HOUR_MS, 12 * HOUR_MS,
+    DAY_MS, 2 * DAY_MS, 7 * DAY_MS, 14 * DAY_MS, 30 * DAY_MS, 90 * DAY_MS, 365 * DAY_MS,
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum HistogramKind {
+    /// Numbers, or numeric strings
+    Numeric,
+    /// Date strings or epoch numbers, read like date sorting does
+    Time,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct HistogramOptions {
+    /// Guessed from the first value when unset: date strings give a timeline
+    #[serde(default)]
+    pub kind: Option<HistogramKind>,
+    /// Fixed width of numeric buckets
+    #[serde(default)]
+    pub width: Option<f64>,
+    /// Fixed size of time buckets: a number and a unit (`ms`, `s`, `m`, `h`, `d`, `w`), e.g. `5m`
+    #[serde(default)]
+    pub interval: Option<String>,
+    /// About how many buckets an automatic width or interval aims at
+    #[serde(default)]
+    pub target_buckets: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HistogramBucket {
+    /// Inclusive start; epoch milliseconds for time buckets
+    pub start: f64,
+    /// Exclusive end
+    pub end: f64,
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Histogram {
+    pub field: String,
+    pub kind: HistogramKind,
+    /// Bucket width, in milliseconds for time buckets
+    pub width: f64,
+    pub buckets: Vec<HistogramBucket>,
+    /// Records looked at (the matching ones when a query was given)
+    pub records: usize,
+    /// Records without a usable value in the field
+    pub missing: usize,
+}
+
+/// Command to bucket a field over the whole file, or over the records matching
+/// `query`: a numeric histogram or a timeline of events per time bucket
+#[tauri::command]
+pub async fn compute_histogram(
+    path: String,
+    file_format: FileFormat,
+    field: String,
+    options: Option<HistogramOptions>,
+    query: Option<SearchQuery>,
+) -> Result<Histogram, String> {
+    let file_path = PathBuf::from(&path);
+    let options = options.unwrap_or_default();
+    let target = options.target_buckets.unwrap_or(DEFAULT_TARGET_BUCKETS).clamp(1, MAX_BUCKETS);
+    let fixed_interval_ms = options.interval.as_deref().map(parse_interval).transpose()?;
+    if let Some(width) = options.width {
+        if !(width.is_finite() && width > 0.0) {
+            return Err(format!("Invalid bucket width: {}", width));
+        }
+    }
+
+    // First pass: the range of the values, so the buckets can be laid out
+    // without holding the values
+    let mut kind = options.kind;
+    let mut records = 0;
+    let mut present = 0;
+    let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+    scan_matching_records(&file_path, &file_format, query.as_ref(), |_, json| {
+        records += 1;
+        let Some(value) = field_path::resolve(json, &field).filter(|v| !v.is_null()) else {
+            return Ok(());
+        };
+        let kind = *kind.get_or_insert_with(|| guess_kind(value));
+        if let Some(number) = bucket_value(kind, value) {
+            present += 1;
+            min = min.min(number);
+            max = max.max(number);
+        }
+        Ok(())
+    })
+    .await?;
+
+    let kind = kind.unwrap_or(HistogramKind::Numeric);
+    let missing = records - present;
+    if present == 0 {
+        return Ok(Histogram {
+            field,
+            kind,
+            width: 0.0,
+            buckets: Vec::new(),
+            records,
+            missing,
+        });
+    }
+
+    let width = match kind {
+        HistogramKind::Numeric => options.width.unwrap_or_else(|| nice_width((max - min) / target as f64)),
+        HistogramKind::Time => {
+            let step = fixed_interval_ms.unwrap_or_else(|| time_step((max - min) / target as f64));
+            step as f64
+        }
+    };
+    let mut buckets = Buckets::new(min, max, width)?;
+
+    // Second pass: count
+    scan_matching_records(&file_path, &file_format, query.as_ref(), |_, json| {
+        if let Some(number) = field_path::resolve(json, &field).and_then(|value| bucket_value(kind, value)) {
+            buckets.add(number);
+        }
+        Ok(())
+    })
+    .await?;
+
+    Ok(Histogram {
+        field,
+        kind,
+        width,
+        buckets: buckets.finish(),
+        records,
+        missing,
+    })
+}
+
+/// Equal-width buckets aligned to multiples of the width (time buckets: from the
+/// epoch, in UTC), covering `min..=max`
+struct Buckets {
+    first: f64,
+    width: f64,
+    counts: Vec<usize>,
+}
+
+impl Buckets {
+    fn new(min: f64, max: f64, width: f64) -> Result<Buckets, String> {
+        let first = (min / width).floor();
+        let span = (max / width).floor() - first;
+        if span >= MAX_BUCKETS as f64 {
+            return Err(format!(
+                "A bucket size of {} gives {} buckets, more than the {} allowed; use a larger one",
+                width,
+                span + 1.0,
+                MAX_BUCKETS
+            ));
+        }
+        Ok(Buckets {
+            first,
+            width,
+            counts: vec![0; span as usize + 1],
+        })
+    }
+
+    /// Values outside the range (the file changed between passes) go to the end buckets
+    fn add(&mut self, value: f64) {
+        let index = ((value / self.width).floor() - self.first).max(0.0) as usize;
+        let last = self.counts.len() - 1;
+        self.counts[index.min(last)] += 1;
+    }
+
+    fn finish(self) -> Vec<HistogramBucket> {
+        self.counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| HistogramBucket {
+                start: (self.first + i as f64) * self.width,
+                end: (self.first + i as f64 + 1.0) * self.width,
+                count,
+            })
+            .collect()
+    }
+}
+
+/// The field's value as a number to bucket: epoch milliseconds for time buckets
+fn bucket_value(kind: HistogramKind, value: &serde_json::Value) -> Option<f64> {
+    match kind {
+        HistogramKind::Numeric => numeric_value(value),
+        HistogramKind::Time => parse_timestamp(value).map(|micros| micros as f64 / 1000.0),
+    }
+}
+
+fn guess_kind(value: &serde_json::Value) -> HistogramKind {
+    match value {
+        serde_json::Value::String(s) if s.trim().parse::<f64>().is_err() && parse_timestamp(value).is_some() => {
+            HistogramKind::Time
+        }
+        _ => HistogramKind::Numeric,
+    }
+}
+
+/// A JSON number, or a string holding one
+pub(crate) fn numeric_value(value: &serde_json::Value) -> Option<f64> {
+    match value {
+        serde_json::Value::Number(n) => n.as_f64(),
+        serde_json::Value::String(s) => s.trim().parse::<f64>().ok(),
+        _ => None,
+    }
+    .filter(|n| n.is_finite())
+}
+
+/// Smallest 1, 2 or 5 times a power of ten that is at least `raw`
+fn nice_width(raw: f64) -> f64 {
+    if !(raw.is_finite() && raw > 0.0) {
+        return 1.0;
+    }
+    let magnitude = 10f64.powf(raw.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|&w| w >= raw)
+        .unwrap_or(10.0 * magnitude)
+}
+
+/// Smallest usual time step that is at least `raw_ms`
+fn time_step(raw_ms: f64) -> i64 {
+    TIME_STEPS_MS
+        .iter()
+        .copied()
+        .find(|&step| step as f64 >= raw_ms)
+        .unwrap_or(TIME_STEPS_MS[TIME_STEPS_MS.len() - 1])
+}
+
+/// `500ms`, `30s`, `5m`, `1h`, `1d`, `1w` as milliseconds
+fn parse_interval(interval: &str) -> Result<i64, String> {
+    let invalid = || format!("Invalid interval {:?}: use a number and a unit (ms, s, m, h, d, w), e.g. 5m", interval);
+    let trimmed = interval.trim();
+    let split = trimmed.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let (amount, unit) = trimmed.split_at(split);
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    let unit_ms = match unit.trim() {
+        "ms" => 1,
+        "s" => SECOND_MS,
+        "m" => MINUTE_MS,
+        "h" => HOUR_MS,
+        "d" => DAY_MS,
+        "w" => 7 * DAY_MS,
+        _ => return Err(invalid()),
+    };
+    if amount == 0 {
+        return Err(invalid());
+    }
+    amount
+        .checked_mul(unit_ms)
+        .ok_or_else(|| format!("Interval {:?} is too large", interval))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(buckets: &Buckets) -> Vec<usize> {
+        buckets.counts.clone()
+    }
+
+    #[test]
+    fn bucket_starts_are_inclusive() {
+        let mut buckets = Buckets::new(0.0, 30.0, 10.0).unwrap();
+        for value in [0.0, 9.999, 10.0, 19.0, 20.0, 30.0] {
+            buckets.add(value);
+        }
+        // The maximum on a boundary gets a bucket of its own
+        assert_eq!(counts(&buckets), vec![2, 2, 1, 1]);
+        let buckets = buckets.finish();
+        assert_eq!((buckets[0].start, buckets[0].end), (0.0, 10.0));
+        assert_eq!((buckets[3].start, buckets[3].end), (30.0, 40.0));
+    }
+
+    #[test]
+    fn buckets_are_aligned_to_the_width() {
+        let mut buckets = Buckets::new(-7.0, 13.0, 5.0).unwrap();
+        for value in [-7.0, -5.0, -0.5, 0.0, 13.0] {
+            buckets.add(value);
+        }
+        assert_eq!(counts(&buckets), vec![1, 2, 1, 0, 1]);
+        let buckets = buckets.finish();
+        assert_eq!(buckets[0].start, -10.0);
+        assert_eq!(buckets[4].end, 15.0);
+        assert!(buckets.windows(2).all(|pair| pair[0].end == pair[1].start));
+    }
+
+    #[test]
+    fn single_value_gives_one_bucket() {
+        let mut buckets = Buckets::new(42.0, 42.0, 1.0).unwrap();
+        buckets.add(42.0);
+        assert_eq!(counts(&buckets), vec![1]);
+    }
+
+    #[test]
+    fn values_outside_the_range_go_to_the_end_buckets() {
+        let mut buckets = Buckets::new(10.0, 20.0, 10.0).unwrap();
+        buckets.add(-100.0);
+        buckets.add(1e12);
+        assert_eq!(counts(&buckets), vec![1, 1]);
+    }
+
+    #[test]
+    fn too_many_buckets_is_an_error() {
+        assert!(Buckets::new(0.0, (MAX_BUCKETS - 1) as f64, 1.0).is_ok());
+        assert!(Buckets::new(0.0, MAX_BUCKETS as f64, 1.0).is_err());
+        assert!(Buckets::new(-f64::MAX, f64::MAX, 1.0).is_err());
+    }
+
+    #[test]
+    fn automatic_widths() {
+        assert_eq!(nice_width(0.3), 0.5);
+        assert_eq!(nice_width(1.0), 1.0);
+        assert_eq!(nice_width(7.0), 10.0);
+        assert_eq!(nice_width(120.0), 200.0);
+        assert_eq!(nice_width(0.0), 1.0);
+        assert_eq!(time_step(0.5), 1);
+        assert_eq!(time_step(40.0 * SECOND_MS as f64), MINUTE_MS);
+        assert_eq!(time_step(1e15), 365 * DAY_MS);
+    }
+
+    #[test]
+    fn interval_units() {
+        assert_eq!(parse_interval("500ms"), Ok(500));
+        assert_eq!(parse_interval("30s"), Ok(30 * SECOND_MS));
+        assert_eq!(parse_interval(" 5m "), Ok(5 * MINUTE_MS));
+        assert_eq!(parse_interval("1 h"), Ok(HOUR_MS));
+        assert_eq!(parse_interval("2d"), Ok(2 * DAY_MS));
+        assert_eq!(parse_interval("1w"), Ok(7 * DAY_MS));
+    }
+
+    #[test]
+    fn invalid_intervals() {
+        for interval in ["", "5", "m", "0m", "-5m", "1.5h", "5y", "5 minutes"] {
+            assert!(parse_interval(interval).is_err(), "{:?}", interval);
+        }
+    }
+
+    #[test]
+    fn interval_overflow_is_an_error() {
+        let largest_weeks = i64::MAX / (7 * DAY_MS);
+        assert_eq!(parse_interval(&format!("{}w", largest_weeks)), Ok(largest_weeks * 7 * DAY_MS));
+        let error = parse_interval(&format!("{}w", largest_weeks + 1)).unwrap_err();
+        assert!(error.contains("too large"), "{}", error);
+        assert!(parse_interval("99999999999999999999ms").is_err());
+    }
+}
@@ -0,0 +1,372 @@
+// Request options shared by the URL commands (method, headers, body, auth, TLS),
+// and credentials saved per host in a local JSON file next to the app config.
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Certificate, Client, Method, RequestBuilder, Response, Url};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use crate::commands::network_policy::{NetworkPolicy, PolicyViolation};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct HttpOptions {
+    /// GET when unset
+    #[serde(default)]
+    pub method: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Overrides credentials saved for the host
+    #[serde(default)]
+    pub auth: Option<HttpAuth>,
+    /// Seconds to wait for the server: connecting, response headers and between chunks
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// PEM file with an extra CA certificate to trust
+    #[serde(default)]
+    pub ca_cert_path: Option<String>,
+    /// Accept self-signed or otherwise invalid certificates (local stand-ins only)
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum HttpAuth {
+    Bearer { token: String },
+    Basic { username: String, password: Option<String> },
+}
+
+/// Credentials remembered for one host
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct HostCredentials {
+    #[serde(default)]
+    pub auth: Option<HttpAuth>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+/// Saved credentials by host, persisted to a JSON file (managed Tauri state).
+/// The secrets are stored unencrypted: the file is only protected by being
+/// readable by the current user alone.
+pub struct CredentialStore {
+    path: PathBuf,
+    hosts: Mutex<HashMap<String, HostCredentials>>,
+}
+
+impl CredentialStore {
+    /// Load the store from `path`; a missing or unreadable file starts empty
+    pub fn load(path: PathBuf) -> CredentialStore {
+        let hosts = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        CredentialStore {
+            path,
+            hosts: Mutex::new(hosts),
+        }
+    }
+
+    pub fn get(&self, host: &str) -> Option<HostCredentials> {
+        self.hosts.lock().unwrap().get(&host.to_lowercase()).cloned()
+    }
+
+    fn update(&self, f: impl FnOnce(&mut HashMap<String, HostCredentials>)) -> Result<(), String> {
+        let mut hosts = self.hosts.lock().unwrap();
+        f(&mut hosts);
+        let content = serde_json::to_string_pretty(&*hosts)
+            .map_err(|e| format!("Failed to serialize credentials: {}", e))?;
+        write_private(&self.path, content.as_bytes())
+    }
+}
+
+/// Write a file readable by the current user only. The mode is set on every write,
+/// not just on creation, so a file created by other means is locked down too.
+fn write_private(path: &PathBuf, content: &[u8]) -> Result<(), String> {
+    use std::io::Write;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options
+        .open(path)
+        .map_err(|e| format!("Failed to write credentials: {}", e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("Failed to protect credentials file: {}", e))?;
+    }
+    file.write_all(content)
+        .map_err(|e| format!("Failed to write credentials: {}", e))
+}
+
+/// Client for one download, with the network policy its requests have to pass
+pub struct HttpClient {
+    client: Client,
+    policy: Arc<NetworkPolicy>,
+    /// Origins sent saved or custom headers, which redirects can't carry elsewhere
+    header_origins: Arc<Mutex<HashSet<String>>>,
+}
+
+/// Build the client for these options (TLS settings live on the client). Redirects
+/// and name resolution go through `policy`.
+pub fn build_client(options: &HttpOptions, policy: Arc<NetworkPolicy>) -> Result<HttpClient, String> {
+    let header_origins = Arc::new(Mutex::new(HashSet::new()));
+    let mut builder = Client::builder()
+        .redirect(policy.redirect_policy(header_origins.clone()))
+        .dns_resolver(policy.resolver());
+    // A proxy resolves the target host itself, out of the policy's reach
+    if policy.block_private_ips {
+        builder = builder.no_proxy();
+    }
+
+    if let Some(secs) = options.timeout_secs {
+        builder = builder.connect_timeout(Duration::from_secs(secs));
+    }
+    if let Some(ref ca_path) = options.ca_cert_path {
+        let pem = std::fs::read(ca_path).map_err(|e| format!("Failed to read CA certificate: {}", e))?;
+        let cert = Certificate::from_pem(&pem).map_err(|e| format!("Invalid CA certificate: {}", e))?;
+        builder = builder.add_root_certificate(cert);
+    }
+    if options.accept_invalid_certs {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    let client = builder.build().map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    Ok(HttpClient { client, policy, header_origins })
+}
+
+/// Build a request for `url`: saved credentials for its host first, then the
+/// headers and auth given in `options`. Fails when the policy refuses the URL.
+pub fn build_request(
+    client: &HttpClient,
+    url: Url,
+    options: &HttpOptions,
+    saved: Option<HostCredentials>,
+) -> Result<RequestBuilder, String> {
+    client.policy.check_url(&url).map_err(|violation| violation.to_string())?;
+
+    let method = match options.method {
+        Some(ref m) => Method::from_bytes(m.trim().to_uppercase().as_bytes())
+            .map_err(|_| format!("Invalid HTTP method: {}", m))?,
+        None => Method::GET,
+    };
+
+    let saved = saved.unwrap_or_default();
+    let mut headers = HeaderMap::new();
+    for (name, value) in saved.headers.iter().chain(options.headers.iter()) {
+        let name = HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|_| format!("Invalid header name: {}", name))?;
+        let value = HeaderValue::from_str(value.trim())
+            .map_err(|_| format!("Invalid value for header {}", name))?;
+        headers.insert(name, value);
+    }
+    if !headers.is_empty() {
+        client.header_origins.lock().unwrap().insert(url.origin().ascii_serialization());
+    }
+
+    let mut request = client.client.request(method, url).headers(headers);
+    match options.auth.as_ref().or(saved.auth.as_ref()) {
+        Some(HttpAuth::Bearer { token }) => request = request.bearer_auth(token),
+        Some(HttpAuth::Basic { username, password }) => request = request.basic_auth(username, password.as_ref()),
+        None => {}
+    }
+    if let Some(ref body) = options.body {
+        request = request.body(body.clone());
+    }
+
+    Ok(request)
+}
+
+/// Send a request, failing on non-success status and on the options' timeout
+pub async fn send(request: RequestBuilder, options: &HttpOptions) -> Result<Response, String> {
+    let response = execute(request, options).await?;
+    check_status(&response)?;
+    Ok(response)
+}
+
+/// Send a request with the options' timeout, whatever status comes back
+/// (conditional requests handle 304 themselves)
+pub async fn execute(request: RequestBuilder, options: &HttpOptions) -> Result<Response, String> {
+    match options.timeout_secs {
+        Some(secs) => tokio::time::timeout(Duration::from_secs(secs), request.send())
+            .await
+            .map_err(|_| format!("Server did not respond within {} seconds", secs))?,
+        None => request.send().await,
+    }
+    .map_err(|e| request_error(&e))
+}
+
+/// Policy violations are reported on their own, other failures with reqwest's message
+fn request_error(error: &reqwest::Error) -> String {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(error);
+    while let Some(e) = source {
+        if let Some(violation) = e.downcast_ref::<PolicyViolation>() {
+            return violation.to_string();
+        }
+        source = e.source();
+    }
+    format!("Failed to download file: {}", error)
+}
+
+pub fn check_status(response: &Response) -> Result<(), String> {
+    if !response.status().is_success() {
+        return Err(format!("Server returned error: {}", response.status()));
+    }
+    Ok(())
+}
+
+/// Next body chunk, applying the options' timeout between chunks
+pub async fn next_chunk(response: &mut Response, options: &HttpOptions) -> Result<Option<bytes::Bytes>, String> {
+    let chunk = match options.timeout_secs {
+        Some(secs) => tokio::time::timeout(Duration::from_secs(secs), response.chunk())
+            .await
+            .map_err(|_| format!("Server stopped sending data for {} seconds", secs))?,
+        None => response.chunk().await,
+    };
+    chunk.map_err(|e| format!("Failed to read response body: {}", e))
+}
+
+/// Command to remember credentials (auth and/or headers) for a host, saved unencrypted
+#[tauri::command]
+pub async fn save_host_credentials(
+    host: String,
+    credentials: HostCredentials,
+    store: State<'_, CredentialStore>,
+) -> Result<(), String> {
+    store.update(|hosts| {
+        hosts.insert(host.trim().to_lowercase(), credentials);
+    })
+}
+
+/// Command to list the hosts with saved credentials (the secrets stay in the backend)
+#[tauri::command]
+pub async fn list_credential_hosts(store: State<'_, CredentialStore>) -> Result<Vec<String>, String> {
+    let mut hosts: Vec<String> = store.hosts.lock().unwrap().keys().cloned().collect();
+    hosts.sort();
+    Ok(hosts)
+}
+
+/// Command to forget the credentials saved for a host
+#[tauri::command]
+pub async fn remove_host_credentials(host: String, store: State<'_, CredentialStore>) -> Result<(), String> {
+    store.update(|hosts| {
+        hosts.remove(&host.trim().to_lowercase());
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_util::temp_file;
+
+    #[cfg(unix)]
+    #[test]
+    fn credentials_file_is_private_after_every_write() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = temp_file("credentials.json", "{}");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let store = CredentialStore::load(path.clone());
+        store.update(|hosts| {
+            hosts.insert("example.com".to_string(), HostCredentials::default());
+        })
+        .unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(CredentialStore::load(path.clone()).get("EXAMPLE.com").is_some());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Answer every connection with `respond(request head)`, keeping the heads in `requests`
+    async fn serve(respond: impl Fn(&str) -> String + Send + 'static, requests: Arc<Mutex<Vec<String>>>) -> Url {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0u8; 4096];
+                let read = socket.read(&mut request).await.unwrap_or(0);
+                let head = String::from_utf8_lossy(&request[..read]).to_lowercase();
+                let _ = socket.write_all(respond(&head).as_bytes()).await;
+                requests.lock().unwrap().push(head);
+            }
+        });
+        Url::parse(&format!("http://{}/", addr)).unwrap()
+    }
+
+    fn ok(_: &str) -> String {
+        "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok".to_string()
+    }
+
+    fn redirect(location: &str) -> String {
+        format!("HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\n\r\n", location)
+    }
+
+    async fn get(url: &Url, options: &HttpOptions, saved: Option<HostCredentials>) -> Result<Response, String> {
+        let client = build_client(options, Arc::new(NetworkPolicy::default())).unwrap();
+        send(build_request(&client, url.clone(), options, saved).unwrap(), options).await
+    }
+
+    #[tokio::test]
+    async fn custom_headers_are_not_redirected_to_another_origin() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let other = serve(ok, received.clone()).await;
+        let target = other.clone();
+        let redirecting = serve(move |_| redirect(target.as_str()), Arc::new(Mutex::new(Vec::new()))).await;
+
+        let secret = HttpOptions {
+            headers: HashMap::from([("X-API-Key".to_string(), "secret".to_string())]),
+            ..HttpOptions::default()
+        };
+        let error = get(&redirecting, &secret, None).await.unwrap_err();
+        assert_eq!(
+            error,
+            format!(
+                "Blocked by the network policy: the redirect to {} would send the request's headers to another site",
+                other.origin().ascii_serialization()
+            )
+        );
+        let saved = HostCredentials { auth: None, headers: secret.headers.clone() };
+        assert!(get(&redirecting, &HttpOptions::default(), Some(saved)).await.is_err());
+        assert!(received.lock().unwrap().is_empty());
+
+        // Without custom headers the redirect is followed
+        get(&redirecting, &HttpOptions::default(), None).await.unwrap();
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn custom_headers_follow_redirects_within_the_origin() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let url = serve(
+            |head| if head.starts_with("get /done") { ok(head) } else { redirect("/done") },
+            received.clone(),
+        )
+        .await;
+        let secret = HttpOptions {
+            headers: HashMap::from([("X-API-Key".to_string(), "secret".to_string())]),
+            ..HttpOptions::default()
+        };
+        get(&url, &secret, None).await.unwrap();
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        assert!(received[1].starts_with("get /done") && received[1].contains("x-api-key: secret"));
+    }
+}
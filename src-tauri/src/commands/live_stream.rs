@@ -0,0 +1,310 @@
+// Live HTTP sources: NDJSON streams and Server-Sent Events are fed to the viewer
+// record by record as they arrive, instead of waiting for the body to end.
+
+use std::path::PathBuf;
+use reqwest::header::{HeaderValue, ACCEPT, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use tauri::ipc::Channel;
+use tauri::State;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+use crate::commands::file_parser::JsonLine;
+use crate::commands::http::{self, CredentialStore, HttpOptions};
+use crate::commands::network::{self, Downloads};
+use crate::commands::network_policy::PolicyStore;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum StreamFormat {
+    /// One JSON record per line
+    Ndjson,
+    /// `text/event-stream`; the `data` of each event is the record
+    Sse,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LiveStreamSummary {
+    pub records: usize,
+    pub format: StreamFormat,
+    /// JSONL file the records were recorded to, when recording was asked for
+    pub recording_path: Option<String>,
+    /// Stopped with `cancel_download` rather than ended by the server
+    pub cancelled: bool,
+}
+
+/// Connect to a live NDJSON or SSE source and stream its records on `channel`
+/// until the server closes the stream, `max_records` is reached, or
+/// `cancel_download(stream_id)` is called. With `record` the records are also
+/// written to a local JSONL file whose line ids and byte offsets match the ones sent.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn stream_url_live(
+    url: String,
+    options: Option<HttpOptions>,
+    format: Option<StreamFormat>,
+    record: Option<bool>,
+    max_records: Option<usize>,
+    stream_id: Option<String>,
+    channel: Channel<Vec<JsonLine>>,
+    downloads: State<'_, Downloads>,
+    credentials: State<'_, CredentialStore>,
+    policy: State<'_, PolicyStore>,
+) -> Result<LiveStreamSummary, String> {
+    let parsed_url = network::parse_http_url(&url)?;
+    let options = options.unwrap_or_default();
+    let client = http::build_client(&options, policy.get())?;
+    let saved = parsed_url.host_str().and_then(|host| credentials.get(host));
+    let mut request = http::build_request(&client, parsed_url.clone(), &options, saved)?;
+    if format == Some(StreamFormat::Sse) && !options.headers.keys().any(|k| k.eq_ignore_ascii_case("accept")) {
+        request = request.header(ACCEPT, HeaderValue::from_static("text/event-stream"));
+    }
+
+    let stream_id = stream_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    let cancel = downloads.register(&stream_id);
+    let result = async {
+        let mut response = http::send(request, &options).await?;
+        let is_event_stream = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|ct| ct.to_ascii_lowercase().contains("text/event-stream"));
+        let format = format.unwrap_or(if is_event_stream { StreamFormat::Sse } else { StreamFormat::Ndjson });
+
+        let recording_path = if record.unwrap_or(false) {
+            let name = network::remote_file_name(response.headers(), &parsed_url);
+            let file_name = network::local_file_name(&name, Some("application/x-ndjson"), false);
+            let file_name = match file_name.strip_suffix(".json") {
+                Some(stem) => format!("{}.jsonl", stem),
+                None => file_name,
+            };
+            Some(network::create_download_dir().await?.join(file_name))
+        } else {
+            None
+        };
+
+        let mut sink = LiveSink::new(recording_path.clone(), max_records.unwrap_or(usize::MAX)).await?;
+        let mut parser = StreamParser::new(format);
+        let mut cancelled = false;
+
+        while !sink.is_full() {
+            let chunk = tokio::select! {
+                chunk = http::next_chunk(&mut response, &options) => chunk?,
+                _ = cancel.notified() => {
+                    cancelled = true;
+                    break;
+                }
+            };
+            match chunk {
+                Some(chunk) => {
+                    for content in parser.push(&chunk) {
+                        sink.push(content).await?;
+                    }
+                }
+                None => {
+                    if let Some(content) = parser.finish() {
+                        sink.push(content).await?;
+                    }
+                    break;
+                }
+            }
+            sink.flush(&channel).await?;
+        }
+        sink.flush(&channel).await?;
+
+        Ok(LiveStreamSummary {
+            records: sink.records,
+            format,
+            recording_path: recording_path.map(|p| p.to_string_lossy().to_string()),
+            cancelled,
+        })
+    }
+    .await;
+    downloads.unregister(&stream_id);
+
+    result
+}
+
+/// Splits a byte stream into record texts, one per NDJSON line or SSE event
+struct StreamParser {
+    format: StreamFormat,
+    pending: Vec<u8>,
+    /// `data:` lines of the SSE event being read
+    event_data: Vec<String>,
+}
+
+impl StreamParser {
+    fn new(format: StreamFormat) -> Self {
+        StreamParser {
+            format,
+            pending: Vec::new(),
+            event_data: Vec::new(),
+        }
+    }
+
+    /// Feed a chunk, returning the records it completed
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(chunk);
+        let mut records = Vec::new();
+
+        while let Some(pos) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if let Some(record) = self.line(line) {
+                records.push(record);
+            }
+        }
+
+        records
+    }
+
+    /// End of stream: a last NDJSON line without newline still counts,
+    /// an unterminated SSE event is dropped (as EventSource does)
+    fn finish(&mut self) -> Option<String> {
+        let rest = String::from_utf8_lossy(&std::mem::take(&mut self.pending)).trim().to_string();
+        match self.format {
+            StreamFormat::Ndjson if !rest.is_empty() => Some(rest),
+            _ => None,
+        }
+    }
+
+    fn line(&mut self, line: &str) -> Option<String> {
+        match self.format {
+            StreamFormat::Ndjson => (!line.trim().is_empty()).then(|| line.to_string()),
+            StreamFormat::Sse => {
+                if line.is_empty() {
+                    // Blank line dispatches the event
+                    if self.event_data.is_empty() {
+                        return None;
+                    }
+                    let data = self.event_data.join("\n");
+                    self.event_data.clear();
+                    return Some(data);
+                }
+                if line.starts_with(':') {
+                    return None; // comment / keep-alive
+                }
+                let (field, value) = line.split_once(':').unwrap_or((line, ""));
+                if field == "data" {
+                    self.event_data.push(value.strip_prefix(' ').unwrap_or(value).to_string());
+                }
+                None
+            }
+        }
+    }
+}
+
+/// Turns record texts into `JsonLine`s, batches them for the channel and
+/// optionally records them
+struct LiveSink {
+    recorder: Option<tokio::io::BufWriter<tokio::fs::File>>,
+    batch: Vec<JsonLine>,
+    records: usize,
+    byte_offset: u64,
+    max_records: usize,
+}
+
+impl LiveSink {
+    async fn new(recording_path: Option<PathBuf>, max_records: usize) -> Result<Self, String> {
+        let recorder = match recording_path {
+            Some(path) => {
+                let file = tokio::fs::File::create(&path)
+                    .await
+                    .map_err(|e| format!("Failed to create recording file: {}", e))?;
+                Some(tokio::io::BufWriter::new(file))
+            }
+            None => None,
+        };
+        Ok(LiveSink {
+            recorder,
+            batch: Vec::new(),
+            records: 0,
+            byte_offset: 0,
+            max_records,
+        })
+    }
+
+    fn is_full(&self) -> bool {
+        self.records >= self.max_records
+    }
+
+    /// Add one record; text that isn't JSON is skipped like invalid file lines
+    async fn push(&mut self, content: String) -> Result<(), String> {
+        if self.is_full() {
+            return Ok(());
+        }
+        let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&content) else {
+            return Ok(());
+        };
+        // Multi-line SSE data is stored compacted so the recording stays one record per line
+        let content = if content.contains('\n') { parsed.to_string() } else { content };
+
+        if let Some(ref mut recorder) = self.recorder {
+            let mut line = Vec::with_capacity(content.len() + 1);
+            line.extend_from_slice(content.as_bytes());
+            line.push(b'\n');
+            recorder
+                .write_all(&line)
+                .await
+                .map_err(|e| format!("Failed to write recording: {}", e))?;
+        }
+
+        let len = content.len() as u64 + 1;
+        self.batch.push(JsonLine {
+            id: self.records,
+            content,
+            parsed,
+            byte_offset: self.byte_offset,
+        });
+        self.byte_offset += len;
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Send what arrived so far and make the recording readable up to here
+    async fn flush(&mut self, channel: &Channel<Vec<JsonLine>>) -> Result<(), String> {
+        if let Some(ref mut recorder) = self.recorder {
+            recorder.flush().await.map_err(|e| format!("Failed to write recording: {}", e))?;
+        }
+        if !self.batch.is_empty() {
+            channel
+                .send(std::mem::take(&mut self.batch))
+                .map_err(|e| format!("Failed to send data: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(format: StreamFormat, chunks: &[&str]) -> Vec<String> {
+        let mut parser = StreamParser::new(format);
+        let mut records: Vec<String> = chunks.iter().flat_map(|chunk| parser.push(chunk.as_bytes())).collect();
+        records.extend(parser.finish());
+        records
+    }
+
+    #[test]
+    fn ndjson_lines_across_chunks() {
+        let records = parse(StreamFormat::Ndjson, &["{\"a\":1}\r\n{\"a\"", ":2}\n\n  \n{\"a\":3}"]);
+        assert_eq!(records, vec![r#"{"a":1}"#, r#"{"a":2}"#, r#"{"a":3}"#]);
+    }
+
+    #[test]
+    fn sse_events_join_multi_line_data() {
+        let stream = concat!(
+            ": keep-alive\n",
+            "event: update\nid: 1\ndata: {\"a\":\n",
+            "data:1}\n\n",
+            "data: first\r\ndata:  second\r\n\r\n",
+            "retry: 1000\n\n",
+            "data\n\n",
+            "data: never dispatched",
+        );
+        // Split inside a line and inside the blank line that ends an event
+        let (head, tail) = stream.split_at(30);
+        let records = parse(StreamFormat::Sse, &[head, tail]);
+        assert_eq!(records, vec!["{\"a\":\n1}", "first\n second", ""]);
+    }
+}
@@ -1,5 +1,19 @@
+pub mod aggregate;
+pub mod download_cache;
 pub mod export;
+pub mod expression;
+pub mod field_path;
 pub mod file_parser;
+pub mod histogram;
+pub mod http;
+pub mod live_stream;
+pub mod schema;
 pub mod search;
 pub mod network;
-pub mod sort;
\ No newline at end of file
+pub mod network_policy;
+pub mod sort;
+pub mod stats;
+pub mod validation;
+pub mod sort_view;
+#[cfg(test)]
+mod test_util;
@@ -1,41 +1,987 @@
-use std::io::Write;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use async_compression::tokio::write::GzipDecoder;
+use jsonpath_rust::path::config::JsonPathConfig;
+use jsonpath_rust::JsonPathInst;
+use reqwest::header::{HeaderMap, CONTENT_DISPOSITION, CONTENT_TYPE, LINK};
 use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use tauri::ipc::Channel;
+use tauri::State;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::Notify;
 use uuid::Uuid;
+use crate::commands::download_cache::{DownloadCache, DEFAULT_CACHE_MAX_AGE, DEFAULT_CACHE_MAX_MB};
+use crate::commands::http::{self, CredentialStore, HttpOptions};
+use crate::commands::network_policy::PolicyStore;
 
+/// Bytes received between two progress events
+const PROGRESS_INTERVAL_BYTES: u64 = 256 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DownloadedFile {
+    /// Local copy, named after the remote file
+    pub path: String,
+    pub file_name: String,
+    /// The URL the file was downloaded from
+    pub url: String,
+    pub content_type: Option<String>,
+    /// Whether the body was gzip-compressed and got decompressed on the way
+    pub decompressed: bool,
+    /// Served from the download cache because the server reported no change
+    pub from_cache: bool,
+    /// Continued an interrupted download of the same URL
+    pub resumed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DownloadProgress {
+    pub received: u64,
+    /// From Content-Length, when the server sends one
+    pub total: Option<u64>,
+}
+
+/// Downloads in flight that can be cancelled by id (managed Tauri state)
+#[derive(Default)]
+pub struct Downloads {
+    active: Mutex<HashMap<String, Arc<Notify>>>,
+}
+
+impl Downloads {
+    pub(crate) fn register(&self, id: &str) -> Arc<Notify> {
+        let cancel = Arc::new(Notify::new());
+        self.active.lock().unwrap().insert(id.to_string(), cancel.clone());
+        cancel
+    }
+
+    pub(crate) fn unregister(&self, id: &str) {
+        self.active.lock().unwrap().remove(id);
+    }
+}
+
+/// Download a URL to a temp file, streaming the body to disk in chunks.
+/// Progress is reported on `channel`; `max_size_mb` aborts oversized downloads
+/// and `cancel_download(download_id)` stops one in flight. Partial files are removed.
+/// The file keeps the remote name (Content-Disposition or URL path) in its own temp
+/// directory, with an extension guessed from Content-Type; gzip bodies are unpacked.
+/// `options` sets the method, headers, body, auth, timeout and TLS settings; saved
+/// credentials for the host are used unless `options` brings its own.
+/// Plain GET downloads go through the download cache unless `use_cache` is false:
+/// an unchanged file is not downloaded again and an interrupted one is resumed.
+/// The URL, and every redirect, has to pass the network policy.
 #[tauri::command]
-pub async fn download_url_to_temp(url: String) -> Result<String, String> {
-    // Validate URL
-    let parsed_url = Url::parse(&url).map_err(|e| format!("Invalid URL: {}", e))?;
+#[allow(clippy::too_many_arguments)]
+pub async fn download_url_to_temp(
+    url: String,
+    options: Option<HttpOptions>,
+    download_id: Option<String>,
+    max_size_mb: Option<u64>,
+    use_cache: Option<bool>,
+    channel: Channel<DownloadProgress>,
+    downloads: State<'_, Downloads>,
+    credentials: State<'_, CredentialStore>,
+    policy: State<'_, PolicyStore>,
+    cache: State<'_, DownloadCache>,
+) -> Result<DownloadedFile, String> {
+    let parsed_url = parse_http_url(&url)?;
+
+    let options = options.unwrap_or_default();
+    let client = http::build_client(&options, policy.get())?;
+    let saved = parsed_url.host_str().and_then(|host| credentials.get(host));
+    let request = http::build_request(&client, parsed_url.clone(), &options, saved)?;
+
+    let download_id = download_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    let cancel = downloads.register(&download_id);
+    let max_bytes = max_size_mb.map(|mb| mb * 1024 * 1024);
+
+    // Another download of the same URL holds its cache entry: download uncached
+    let slot = if use_cache.unwrap_or(true) && DownloadCache::is_cacheable(&options) {
+        cache.claim(&parsed_url)
+    } else {
+        None
+    };
+
+    let result = match slot {
+        Some(slot) => {
+            let result = tokio::select! {
+                result = slot.fetch(request, &options, max_bytes, &channel) => result,
+                _ = cancel.notified() => Err("Download cancelled".to_string()),
+            };
+            if result.is_err() {
+                // Keeps the partial file only when the server lets it be resumed
+                slot.discard_unresumable();
+            }
+            result
+        }
+        None => {
+            let temp_dir = create_download_dir().await?;
+            let result = tokio::select! {
+                result = stream_to_file(request, &options, &temp_dir, max_bytes, &channel) => result,
+                _ = cancel.notified() => Err("Download cancelled".to_string()),
+            };
+            if result.is_err() {
+                let _ = tokio::fs::remove_dir_all(&temp_dir).await;
+            }
+            result
+        }
+    };
+    downloads.unregister(&download_id);
 
-    // Validate scheme
+    if let Ok(ref file) = result {
+        // The viewer opens the returned file next: keep it out of the pruning
+        cache.set_viewed(Some(PathBuf::from(&file.path)));
+        cache.prune(DEFAULT_CACHE_MAX_MB * 1024 * 1024, DEFAULT_CACHE_MAX_AGE);
+    }
+
+    result.map(|mut file| {
+        file.url = url;
+        file
+    })
+}
+
+/// Parse a URL, accepting only HTTP and HTTPS
+pub(crate) fn parse_http_url(url: &str) -> Result<Url, String> {
+    let parsed_url = Url::parse(url).map_err(|e| format!("Invalid URL: {}", e))?;
     if parsed_url.scheme() != "http" && parsed_url.scheme() != "https" {
         return Err("Only HTTP and HTTPS URLs are supported".to_string());
     }
+    Ok(parsed_url)
+}
 
-    // Perform request
-    let response = reqwest::get(parsed_url)
+/// One temp directory per download, so the file can keep its own name
+pub(crate) async fn create_download_dir() -> Result<PathBuf, String> {
+    let temp_dir = std::env::temp_dir().join(format!("jsonl-viewer-{}", Uuid::new_v4()));
+    tokio::fs::create_dir_all(&temp_dir)
         .await
-        .map_err(|e| format!("Failed to download file: {}", e))?;
+        .map_err(|e| format!("Failed to create temp directory: {}", e))?;
+    Ok(temp_dir)
+}
 
-    if !response.status().is_success() {
-        return Err(format!("Server returned error: {}", response.status()));
+/// Command to cancel a download started with `download_id`
+#[tauri::command]
+pub async fn cancel_download(download_id: String, downloads: State<'_, Downloads>) -> Result<(), String> {
+    if let Some(cancel) = downloads.active.lock().unwrap().get(&download_id) {
+        // Stores a permit, so this also works before the download starts waiting
+        cancel.notify_one();
     }
+    Ok(())
+}
 
-    let content = response
-        .bytes()
-        .await
-        .map_err(|e| format!("Failed to read response body: {}", e))?;
+async fn stream_to_file(
+    request: reqwest::RequestBuilder,
+    options: &HttpOptions,
+    temp_dir: &Path,
+    max_bytes: Option<u64>,
+    channel: &Channel<DownloadProgress>,
+) -> Result<DownloadedFile, String> {
+    let response = http::send(request, options).await?;
+    let url = response.url().to_string();
+    let body = open_body(response, options).await?;
+
+    let file = DownloadedFile {
+        path: temp_dir.join(&body.file_name).to_string_lossy().to_string(),
+        file_name: body.file_name.clone(),
+        url,
+        content_type: body.content_type.clone(),
+        decompressed: body.gzip,
+        from_cache: false,
+        resumed: false,
+    };
+    write_body(body, options, &temp_dir.join(&file.file_name), 0, max_bytes, channel).await?;
+    Ok(file)
+}
+
+/// A response whose first chunk has been read, which tells the local file name
+/// and whether the body is gzip
+pub(crate) struct Body {
+    response: reqwest::Response,
+    first: bytes::Bytes,
+    /// Content-Length, taken before the first chunk was read off it
+    content_length: Option<u64>,
+    pub file_name: String,
+    pub content_type: Option<String>,
+    pub gzip: bool,
+}
+
+pub(crate) async fn open_body(mut response: reqwest::Response, options: &HttpOptions) -> Result<Body, String> {
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    let remote_name = remote_file_name(response.headers(), response.url());
+    let content_length = response.content_length();
+
+    // Gzip is recognised by its magic bytes as well as the content type
+    let first = http::next_chunk(&mut response, options).await?.unwrap_or_default();
+    let gzip = first.starts_with(&[0x1f, 0x8b]) || content_type.as_deref().is_some_and(is_gzip_type);
+
+    Ok(Body {
+        file_name: local_file_name(&remote_name, content_type.as_deref(), gzip),
+        response,
+        first,
+        content_length,
+        content_type,
+        gzip,
+    })
+}
+
+/// Stream a body to `path`. With `offset` the body continues a partial file of
+/// that many bytes (a Range resume) and is appended to it.
+pub(crate) async fn write_body(
+    mut body: Body,
+    options: &HttpOptions,
+    path: &Path,
+    offset: u64,
+    max_bytes: Option<u64>,
+    channel: &Channel<DownloadProgress>,
+) -> Result<(), String> {
+    let total = body.content_length.map(|len| len + offset);
+    if let (Some(total), Some(max)) = (total, max_bytes) {
+        if total > max {
+            return Err(too_large(max));
+        }
+    }
+
+    let file = if offset > 0 {
+        tokio::fs::OpenOptions::new().append(true).open(path).await
+    } else {
+        tokio::fs::File::create(path).await
+    }
+    .map_err(|e| format!("Failed to create temp file: {}", e))?;
+    // The limit is checked on what reaches the file, so a small gzip body can't unpack past it
+    let file = LimitedWriter {
+        inner: tokio::io::BufWriter::new(file),
+        written: offset,
+        max: max_bytes,
+    };
+    let mut writer: Pin<Box<dyn AsyncWrite + Send>> = if body.gzip {
+        Box::pin(GzipDecoder::new(file))
+    } else {
+        Box::pin(file)
+    };
+
+    let mut received: u64 = offset;
+    let mut last_reported: u64 = offset;
+    let _ = channel.send(DownloadProgress { received, total });
+
+    let mut next = Some(std::mem::take(&mut body.first));
+    while let Some(chunk) = next.take() {
+        received += chunk.len() as u64;
+        writer.write_all(&chunk).await.map_err(|e| write_error(e, max_bytes))?;
+
+        if received - last_reported >= PROGRESS_INTERVAL_BYTES {
+            last_reported = received;
+            let _ = channel.send(DownloadProgress { received, total });
+        }
+
+        next = http::next_chunk(&mut body.response, options).await?;
+    }
+
+    // Shutdown also finishes the gzip stream and flushes the buffered file
+    writer.shutdown().await.map_err(|e| write_error(e, max_bytes))?;
+    let _ = channel.send(DownloadProgress { received, total });
+
+    Ok(())
+}
+
+/// File name the server suggests (Content-Disposition), else the last URL path segment
+pub(crate) fn remote_file_name(headers: &HeaderMap, url: &Url) -> Option<String> {
+    let from_header = headers
+        .get(CONTENT_DISPOSITION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(content_disposition_name);
+
+    from_header.or_else(|| {
+        url.path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .filter(|s| !s.is_empty())
+            .map(percent_decode)
+    })
+}
+
+/// `filename*=UTF-8''...` (preferred) or `filename=...` from a Content-Disposition header
+fn content_disposition_name(header: &str) -> Option<String> {
+    let mut plain = None;
+    for param in header.split(';').map(str::trim) {
+        let Some((key, value)) = param.split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "filename*" => {
+                // charset'language'percent-encoded-name
+                if let Some(encoded) = value.splitn(3, '\'').nth(2) {
+                    return Some(percent_decode(encoded));
+                }
+            }
+            "filename" => plain = Some(value.trim_matches('"').to_string()),
+            _ => {}
+        }
+    }
+    plain
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() && bytes[i + 1].is_ascii_hexdigit() && bytes[i + 2].is_ascii_hexdigit() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("0");
+            out.push(u8::from_str_radix(hex, 16).unwrap_or(b'?'));
+            i += 3;
+            continue;
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn is_gzip_type(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    mime == "application/gzip" || mime == "application/x-gzip"
+}
+
+/// Extension implied by a Content-Type, for names that don't carry one
+fn extension_for_type(content_type: &str) -> Option<&'static str> {
+    let mime = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    match mime.as_str() {
+        "application/x-ndjson" | "application/ndjson" | "application/jsonl" | "application/x-jsonlines"
+        | "application/jsonlines" => Some("jsonl"),
+        "application/json" | "text/json" => Some("json"),
+        m if m.ends_with("+json") => Some("json"),
+        _ => None,
+    }
+}
+
+/// Safe local name: the remote name without directories or reserved characters,
+/// minus `.gz` when the body is unpacked, with a JSON extension when it lacks one
+pub(crate) fn local_file_name(remote: &Option<String>, content_type: Option<&str>, gzip: bool) -> String {
+    let mut name: String = remote
+        .as_deref()
+        .and_then(|n| n.rsplit(['/', '\\']).next())
+        .unwrap_or("")
+        .chars()
+        .map(|c| if c.is_control() || "<>:\"|?*".contains(c) { '_' } else { c })
+        .collect();
+    name = name.trim_matches(|c: char| c == '.' || c.is_whitespace()).to_string();
+
+    if gzip {
+        for suffix in [".gz", ".gzip"] {
+            if name.to_lowercase().ends_with(suffix) {
+                name.truncate(name.len() - suffix.len());
+            }
+        }
+    }
+    if name.is_empty() {
+        name = "download".to_string();
+    }
+
+    let extension = Path::new(&name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    let has_record_extension = matches!(extension.as_deref(), Some("json" | "jsonl" | "ndjson"));
+    if !has_record_extension {
+        match content_type.and_then(extension_for_type) {
+            Some(ext) => name = format!("{}.{}", name, ext),
+            None if extension.is_none() => name.push_str(".json"),
+            None => {}
+        }
+    }
+
+    name
+}
+
+fn too_large(max_bytes: u64) -> String {
+    format!("File is larger than the {} MB download limit", max_bytes / (1024 * 1024))
+}
+
+fn write_error(error: io::Error, max_bytes: Option<u64>) -> String {
+    match max_bytes {
+        Some(max) if error.kind() == io::ErrorKind::FileTooLarge => too_large(max),
+        _ => format!("Failed to write to temp file: {}", error),
+    }
+}
+
+/// Writer that fails with `FileTooLarge` instead of writing past `max` bytes
+struct LimitedWriter<W> {
+    inner: W,
+    written: u64,
+    max: Option<u64>,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for LimitedWriter<W> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        if self.max.is_some_and(|max| self.written + buf.len() as u64 > max) {
+            return Poll::Ready(Err(io::ErrorKind::FileTooLarge.into()));
+        }
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = poll {
+            self.written += n as u64;
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// How an API tells where the next page is
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum PaginationMode {
+    /// A single request
+    None,
+    /// A cursor or next-page URL in the body, found by `next_path`
+    NextCursor,
+    /// `Link: <...>; rel="next"` response headers
+    LinkHeader,
+    /// `offset`/`limit` query parameters, until a short page
+    OffsetLimit,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Pagination {
+    pub mode: PaginationMode,
+    /// JSONPath to the cursor or next URL, e.g. `$.next` or `$.meta.next_cursor`
+    #[serde(default)]
+    pub next_path: Option<String>,
+    /// Query parameter that carries a cursor value, `cursor` by default
+    #[serde(default)]
+    pub cursor_param: Option<String>,
+    /// Query parameter names for offset/limit paging
+    #[serde(default)]
+    pub offset_param: Option<String>,
+    #[serde(default)]
+    pub limit_param: Option<String>,
+    #[serde(default)]
+    pub page_size: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApiProgress {
+    pub pages: usize,
+    pub records: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApiDownload {
+    /// Local JSONL dataset with the records of every page
+    pub path: String,
+    pub file_name: String,
+    /// The URL of the first page
+    pub url: String,
+    pub pages: usize,
+    pub records: usize,
+    /// Stopped at `max_records` or `max_pages` rather than at the last page
+    pub truncated: bool,
+}
+
+/// Pages fetched at most unless `max_pages` says otherwise
+const DEFAULT_MAX_PAGES: usize = 10_000;
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Fetch every page of a paginated API and append the records to one JSONL file.
+/// Records are taken from each page by `records_path` (JSONPath; the body itself
+/// when it is an array). Stops when pages run out, or at `max_records` / `max_pages`.
+/// Each page gets the credentials saved for its own host; the auth and headers in
+/// `options` are only sent to pages on the first URL's origin.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn download_api_pages(
+    url: String,
+    options: Option<HttpOptions>,
+    pagination: Pagination,
+    records_path: Option<String>,
+    max_records: Option<usize>,
+    max_pages: Option<usize>,
+    download_id: Option<String>,
+    channel: Channel<ApiProgress>,
+    downloads: State<'_, Downloads>,
+    credentials: State<'_, CredentialStore>,
+    policy: State<'_, PolicyStore>,
+) -> Result<ApiDownload, String> {
+    let first_url = parse_http_url(&url)?;
+    let options = options.unwrap_or_default();
+    let client = http::build_client(&options, policy.get())?;
+
+    let records_path = records_path
+        .filter(|p| !p.trim().is_empty())
+        .map(|p| JsonPathInst::from_str(&p).map_err(|e| format!("Invalid records path {}: {}", p, e)))
+        .transpose()?;
+    let next_path = pagination
+        .next_path
+        .as_deref()
+        .filter(|p| !p.trim().is_empty())
+        .map(|p| JsonPathInst::from_str(p).map_err(|e| format!("Invalid next path {}: {}", p, e)))
+        .transpose()?;
+    if pagination.mode == PaginationMode::NextCursor && next_path.is_none() {
+        return Err("Cursor pagination needs the path of the next cursor".to_string());
+    }
+
+    let download_id = download_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    let cancel = downloads.register(&download_id);
+    let temp_dir = create_download_dir().await?;
+
+    let name = remote_file_name(&HeaderMap::new(), &first_url).unwrap_or_default();
+    let file_name = local_file_name(&Some(name), Some("application/x-ndjson"), false);
+    let file_name = match file_name.strip_suffix(".json") {
+        Some(stem) => format!("{}.jsonl", stem),
+        None => file_name,
+    };
+    let temp_path = temp_dir.join(&file_name);
+
+    let pager = Pager {
+        client: &client,
+        options: &options,
+        credentials: &credentials,
+        pagination: &pagination,
+        records_path: records_path.as_ref(),
+        next_path: next_path.as_ref(),
+        max_records: max_records.unwrap_or(usize::MAX),
+        max_pages: max_pages.unwrap_or(DEFAULT_MAX_PAGES),
+    };
+    let result = tokio::select! {
+        result = pager.run(first_url, &temp_path, &channel) => result,
+        _ = cancel.notified() => Err("Download cancelled".to_string()),
+    };
+    downloads.unregister(&download_id);
+
+    match result {
+        Ok((pages, records, truncated)) => Ok(ApiDownload {
+            path: temp_path.to_string_lossy().to_string(),
+            file_name,
+            url,
+            pages,
+            records,
+            truncated,
+        }),
+        Err(e) => {
+            let _ = tokio::fs::remove_dir_all(&temp_dir).await;
+            Err(e)
+        }
+    }
+}
+
+struct Pager<'a> {
+    client: &'a http::HttpClient,
+    options: &'a HttpOptions,
+    credentials: &'a CredentialStore,
+    pagination: &'a Pagination,
+    records_path: Option<&'a JsonPathInst>,
+    next_path: Option<&'a JsonPathInst>,
+    max_records: usize,
+    max_pages: usize,
+}
+
+impl Pager<'_> {
+    /// Fetch pages into `temp_path`, returning (pages, records, truncated)
+    async fn run(
+        &self,
+        first_url: Url,
+        temp_path: &Path,
+        channel: &Channel<ApiProgress>,
+    ) -> Result<(usize, usize, bool), String> {
+        let file = tokio::fs::File::create(temp_path)
+            .await
+            .map_err(|e| format!("Failed to create temp file: {}", e))?;
+        let mut writer = tokio::io::BufWriter::new(file);
+
+        let page_size = self.pagination.page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+        let mut offset = 0;
+        let mut pages = 0;
+        let mut records = 0;
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut page_url = Some(self.page_url(&first_url, offset, page_size));
+        // Next-page URLs come from the server and may point anywhere: the explicit
+        // auth and headers only go to the first URL's origin
+        let other_origin_options = HttpOptions {
+            auth: None,
+            headers: Default::default(),
+            ..self.options.clone()
+        };
+
+        while let Some(url) = page_url.take() {
+            if pages >= self.max_pages {
+                writer.flush().await.map_err(|e| format!("Failed to write to temp file: {}", e))?;
+                return Ok((pages, records, true));
+            }
+            // A server handing out the same page again would loop forever
+            if !seen.insert(url.to_string()) {
+                break;
+            }
+
+            let options = if url.origin() == first_url.origin() { self.options } else { &other_origin_options };
+            let saved = url.host_str().and_then(|host| self.credentials.get(host));
+            let request = http::build_request(self.client, url.clone(), options, saved)?;
+            let mut response = http::send(request, self.options).await?;
+            let link_next = response
+                .headers()
+                .get_all(LINK)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .find_map(link_next_url);
+
+            let mut body = Vec::new();
+            while let Some(chunk) = http::next_chunk(&mut response, self.options).await? {
+                body.extend_from_slice(&chunk);
+            }
+            let json: serde_json::Value = serde_json::from_slice(&body)
+                .map_err(|e| format!("Page {} is not valid JSON: {}", pages + 1, e))?;
+            pages += 1;
+
+            let page_records = self.extract_records(&json);
+            let page_len = page_records.len();
+            for record in page_records {
+                if records >= self.max_records {
+                    writer.flush().await.map_err(|e| format!("Failed to write to temp file: {}", e))?;
+                    let _ = channel.send(ApiProgress { pages, records });
+                    return Ok((pages, records, true));
+                }
+                let mut line = serde_json::to_vec(&record).map_err(|e| format!("Failed to serialize record: {}", e))?;
+                line.push(b'\n');
+                writer
+                    .write_all(&line)
+                    .await
+                    .map_err(|e| format!("Failed to write to temp file: {}", e))?;
+                records += 1;
+            }
+            let _ = channel.send(ApiProgress { pages, records });
+
+            page_url = match self.pagination.mode {
+                PaginationMode::None => None,
+                PaginationMode::LinkHeader => link_next.and_then(|next| url.join(&next).ok()),
+                PaginationMode::NextCursor => self.next_from_body(&json, &url),
+                PaginationMode::OffsetLimit => {
+                    offset += page_len;
+                    (page_len >= page_size && page_len > 0).then(|| self.page_url(&first_url, offset, page_size))
+                }
+            };
+            if records >= self.max_records && page_url.is_some() {
+                writer.flush().await.map_err(|e| format!("Failed to write to temp file: {}", e))?;
+                return Ok((pages, records, true));
+            }
+        }
+
+        writer.flush().await.map_err(|e| format!("Failed to write to temp file: {}", e))?;
+        Ok((pages, records, false))
+    }
+
+    /// The first URL with offset/limit parameters set (other modes use it as is)
+    fn page_url(&self, first_url: &Url, offset: usize, page_size: usize) -> Url {
+        if self.pagination.mode != PaginationMode::OffsetLimit {
+            return first_url.clone();
+        }
+        let offset_param = self.pagination.offset_param.as_deref().unwrap_or("offset");
+        let limit_param = self.pagination.limit_param.as_deref().unwrap_or("limit");
+        let mut url = first_url.clone();
+        set_query_param(&mut url, offset_param, &offset.to_string());
+        set_query_param(&mut url, limit_param, &page_size.to_string());
+        url
+    }
+
+    /// Records of one page: the matches of `records_path` (arrays are flattened),
+    /// or the page itself
+    fn extract_records(&self, json: &serde_json::Value) -> Vec<serde_json::Value> {
+        let found: Vec<serde_json::Value> = match self.records_path {
+            Some(path) => path
+                .find_slice(json, JsonPathConfig::default())
+                .into_iter()
+                .map(|v| (*v).clone())
+                .collect(),
+            None => vec![json.clone()],
+        };
+
+        found
+            .into_iter()
+            .flat_map(|v| match v {
+                serde_json::Value::Array(items) => items,
+                serde_json::Value::Null => Vec::new(),
+                v => vec![v],
+            })
+            .collect()
+    }
+
+    /// Next page from a body cursor: a URL (absolute or relative) is followed,
+    /// any other value is sent as the cursor parameter
+    fn next_from_body(&self, json: &serde_json::Value, url: &Url) -> Option<Url> {
+        let value = self
+            .next_path?
+            .find_slice(json, JsonPathConfig::default())
+            .into_iter()
+            .map(|v| (*v).clone())
+            .next()?;
+        let cursor = match value {
+            serde_json::Value::String(s) if !s.is_empty() => s,
+            serde_json::Value::Number(n) => n.to_string(),
+            _ => return None,
+        };
+
+        if cursor.starts_with("http://") || cursor.starts_with("https://") || cursor.starts_with('/') || cursor.starts_with('?') {
+            return url.join(&cursor).ok();
+        }
+        let mut next = url.clone();
+        set_query_param(&mut next, self.pagination.cursor_param.as_deref().unwrap_or("cursor"), &cursor);
+        Some(next)
+    }
+}
+
+/// Replace (or add) one query parameter, keeping the others
+fn set_query_param(url: &mut Url, name: &str, value: &str) {
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(k, _)| k != name)
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    url.query_pairs_mut().clear().extend_pairs(pairs).append_pair(name, value);
+}
+
+/// The `rel="next"` target of a Link header, e.g. `<https://api/x?page=2>; rel="next"`
+fn link_next_url(header: &str) -> Option<String> {
+    header.split(',').find_map(|link| {
+        let mut parts = link.split(';');
+        let target = parts.next()?.trim();
+        let is_next = parts.any(|p| {
+            let p = p.trim().to_ascii_lowercase();
+            p.strip_prefix("rel=")
+                .map(|rel| rel.trim_matches('"').split_whitespace().any(|r| r == "next"))
+                .unwrap_or(false)
+        });
+        (is_next && target.starts_with('<') && target.ends_with('>')).then(|| target[1..target.len() - 1].to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::network_policy::NetworkPolicy;
+    use crate::commands::test_util::{channel_sink, gzip};
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("a%20b%2Fc"), "a b/c");
+        assert_eq!(percent_decode("na%C3%AFve"), "naïve");
+        // Malformed escapes are kept as they are
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+        assert_eq!(percent_decode("%ff"), "\u{FFFD}");
+    }
+
+    #[test]
+    fn content_disposition_names() {
+        assert_eq!(content_disposition_name(r#"attachment; filename="data.jsonl""#).as_deref(), Some("data.jsonl"));
+        assert_eq!(content_disposition_name("attachment; FILENAME=data.json").as_deref(), Some("data.json"));
+        // The encoded name wins, wherever it appears
+        assert_eq!(
+            content_disposition_name(r#"attachment; filename="fallback.jsonl"; filename*=UTF-8''na%C3%AFve%20events.jsonl"#).as_deref(),
+            Some("naïve events.jsonl")
+        );
+        assert_eq!(content_disposition_name("inline"), None);
+        assert_eq!(content_disposition_name("attachment; size=42"), None);
+    }
+
+    #[test]
+    fn local_names_stay_in_the_download_directory() {
+        let name = |remote: &str| local_file_name(&Some(remote.to_string()), None, false);
+        assert_eq!(name("../../etc/passwd"), "passwd.json");
+        assert_eq!(name(r"..\..\Windows\win.ini"), "win.ini");
+        assert_eq!(name(".."), "download.json");
+        assert_eq!(name("/"), "download.json");
+        assert_eq!(name("  .hidden.jsonl "), "hidden.jsonl");
+        assert_eq!(name("a<b>:c|d?.jsonl"), "a_b__c_d_.jsonl");
+
+        // An encoded traversal in the URL path is decoded, then stripped
+        let url = Url::parse("https://example.com/files/..%2F..%2Fetc%2Fpasswd").unwrap();
+        let remote = remote_file_name(&HeaderMap::new(), &url);
+        assert_eq!(remote.as_deref(), Some("../../etc/passwd"));
+        assert_eq!(local_file_name(&remote, None, false), "passwd.json");
+    }
+
+    #[test]
+    fn local_names_get_a_record_extension() {
+        assert_eq!(local_file_name(&Some("events".to_string()), Some("application/x-ndjson"), false), "events.jsonl");
+        assert_eq!(local_file_name(&Some("events.json.gz".to_string()), None, true), "events.json");
+        assert_eq!(local_file_name(&Some("events.GZ".to_string()), Some("application/json"), true), "events.json");
+        assert_eq!(local_file_name(&Some("report.csv".to_string()), Some("application/json; charset=utf-8"), false), "report.csv.json");
+        assert_eq!(local_file_name(&None, Some("application/geo+json"), false), "download.json");
+    }
 
-    // Create temp file
-    let temp_dir = std::env::temp_dir();
-    let file_name = format!("jsonl-viewer-{}.json", Uuid::new_v4());
-    let temp_path = temp_dir.join(file_name);
+    #[test]
+    fn link_header_next_targets() {
+        assert_eq!(
+            link_next_url(r#"<https://api.example.com/items?page=2>; rel="next", <https://api.example.com/items?page=9>; rel="last""#).as_deref(),
+            Some("https://api.example.com/items?page=2")
+        );
+        assert_eq!(link_next_url(r#"</items?page=1>; rel="prev", </items?page=3>; REL="Next""#).as_deref(), Some("/items?page=3"));
+        assert_eq!(link_next_url(r#"</items?page=3>; rel="prefetch next""#).as_deref(), Some("/items?page=3"));
+        assert_eq!(link_next_url("</items?page=3>; rel=next").as_deref(), Some("/items?page=3"));
+
+        // Malformed or without a next link
+        assert_eq!(link_next_url(r#"</items?page=1>; rel="prev""#), None);
+        assert_eq!(link_next_url(r#"https://api.example.com/items?page=2; rel="next""#), None);
+        assert_eq!(link_next_url(r#"<https://api.example.com/items?page=2; rel="next""#), None);
+        assert_eq!(link_next_url(r#"<>; rel="nextpage""#), None);
+        assert_eq!(link_next_url(""), None);
+        assert_eq!(link_next_url(";;,,"), None);
+    }
+
+    #[test]
+    fn query_params_are_replaced() {
+        let mut url = Url::parse("https://api.example.com/items?limit=10&q=a%20b&offset=0").unwrap();
+        set_query_param(&mut url, "offset", "20");
+        assert_eq!(url.as_str(), "https://api.example.com/items?limit=10&q=a+b&offset=20");
+
+        set_query_param(&mut url, "cursor", "x&y=z");
+        assert_eq!(url.query_pairs().find(|(k, _)| k == "cursor").unwrap().1, "x&y=z");
+
+        let mut url = Url::parse("https://api.example.com/items?page=1&page=2").unwrap();
+        set_query_param(&mut url, "page", "3");
+        assert_eq!(url.query(), Some("page=3"));
+    }
+
+
+    /// Serve one canned HTTP response per connection on a local port, returning the URL
+    async fn serve(body: Vec<u8>, headers: &'static str) -> Url {
+        use tokio::io::AsyncReadExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let body = body.clone();
+                tokio::spawn(async move {
+                    let mut request = [0u8; 4096];
+                    let _ = socket.read(&mut request).await;
+                    let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n{}\r\n", body.len(), headers);
+                    let _ = socket.write_all(head.as_bytes()).await;
+                    let _ = socket.write_all(&body).await;
+                });
+            }
+        });
+        Url::parse(&format!("http://{}/files/records.jsonl", addr)).unwrap()
+    }
 
-    let mut file = std::fs::File::create(&temp_path)
-        .map_err(|e| format!("Failed to create temp file: {}", e))?;
+    /// Download `url` into a fresh directory, returning the file, its content and the
+    /// progress reported
+    async fn download(url: Url, max_bytes: Option<u64>) -> (Result<DownloadedFile, String>, Option<Vec<u8>>, Vec<DownloadProgress>) {
+        let options = HttpOptions::default();
+        let client = http::build_client(&options, Arc::new(NetworkPolicy::default())).unwrap();
+        let request = http::build_request(&client, url, &options, None).unwrap();
 
-    file.write_all(&content)
-        .map_err(|e| format!("Failed to write to temp file: {}", e))?;
+        let (channel, progress) = channel_sink();
 
-    Ok(temp_path.to_string_lossy().to_string())
+        let dir = create_download_dir().await.unwrap();
+        let result = stream_to_file(request, &options, &dir, max_bytes, &channel).await;
+        let content = result.as_ref().ok().map(|file| std::fs::read(&file.path).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        let progress = progress.lock().unwrap().clone();
+        (result, content, progress)
+    }
+
+    #[tokio::test]
+    async fn body_is_streamed_to_disk_with_progress() {
+        let body: Vec<u8> = (0..1_000_000u32).map(|i| b'a' + (i % 26) as u8).collect();
+        let url = serve(body.clone(), "Content-Disposition: attachment; filename=\"events.jsonl\"\r\n").await;
+        let (file, content, progress) = download(url, None).await;
+
+        let file = file.unwrap();
+        assert_eq!(file.file_name, "events.jsonl");
+        assert!(!file.decompressed);
+        assert_eq!(content.unwrap(), body);
+
+        // From nothing to everything, without going back, at the reporting interval
+        let received: Vec<u64> = progress.iter().map(|p| p.received).collect();
+        assert_eq!(received.first(), Some(&0));
+        assert_eq!(received.last(), Some(&(body.len() as u64)));
+        assert!(received.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(received.len() >= 2 + body.len() / PROGRESS_INTERVAL_BYTES as usize);
+        assert!(progress.iter().all(|p| p.total == Some(body.len() as u64)));
+    }
+
+    #[tokio::test]
+    async fn gzip_bodies_are_unpacked() {
+        let records = b"{\"a\":1}\n{\"a\":2}\n".repeat(1000);
+        let url = serve(gzip(&records).await, "Content-Type: application/octet-stream\r\n").await;
+        let (file, content, _) = download(url, None).await;
+
+        let file = file.unwrap();
+        assert!(file.decompressed);
+        assert_eq!(file.file_name, "records.jsonl");
+        assert_eq!(content.unwrap(), records);
+    }
+
+    #[tokio::test]
+    async fn size_limit_applies_to_the_announced_length() {
+        let url = serve(vec![b' '; 2 * 1024 * 1024], "").await;
+        let (file, _, _) = download(url, Some(1024 * 1024)).await;
+        assert_eq!(file.unwrap_err(), "File is larger than the 1 MB download limit");
+    }
+
+    #[tokio::test]
+    async fn size_limit_applies_to_the_unpacked_body() {
+        // A few kilobytes on the wire, megabytes on disk
+        let compressed = gzip(&vec![b' '; 5 * 1024 * 1024]).await;
+        assert!(compressed.len() < 1024 * 1024);
+        let url = serve(compressed, "").await;
+        let (file, _, _) = download(url, Some(1024 * 1024)).await;
+        assert_eq!(file.unwrap_err(), "File is larger than the 1 MB download limit");
+    }
+
+    #[tokio::test]
+    async fn limited_writer_stops_at_the_limit() {
+        let mut writer = LimitedWriter {
+            inner: Vec::new(),
+            written: 2,
+            max: Some(6),
+        };
+        writer.write_all(b"abcd").await.unwrap();
+        let error = writer.write_all(b"e").await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::FileTooLarge);
+        assert_eq!(writer.inner, b"abcd");
+        assert_eq!(write_error(error, Some(6 * 1024 * 1024)), "File is larger than the 6 MB download limit");
+
+        let mut unlimited = LimitedWriter {
+            inner: Vec::new(),
+            written: 0,
+            max: None,
+        };
+        unlimited.write_all(&[0; 10_000]).await.unwrap();
+        assert_eq!(unlimited.written, 10_000);
+    }
+
+    #[tokio::test]
+    async fn cancelling_before_the_download_waits_still_stops_it() {
+        let downloads = Downloads::default();
+        let cancel = downloads.register("job");
+        // What cancel_download does
+        downloads.active.lock().unwrap().get("job").unwrap().notify_one();
+        tokio::time::timeout(std::time::Duration::from_secs(1), cancel.notified())
+            .await
+            .unwrap();
+
+        downloads.unregister("job");
+        assert!(downloads.active.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn only_http_urls() {
+        assert!(parse_http_url("https://example.com/a.jsonl").is_ok());
+        assert!(parse_http_url("HTTP://example.com").is_ok());
+        assert_eq!(parse_http_url("ftp://example.com/a").unwrap_err(), "Only HTTP and HTTPS URLs are supported");
+        assert_eq!(parse_http_url("file:///etc/passwd").unwrap_err(), "Only HTTP and HTTPS URLs are supported");
+        assert!(parse_http_url("example.com/a.jsonl").unwrap_err().starts_with("Invalid URL"));
+    }
 }
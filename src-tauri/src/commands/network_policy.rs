@@ -0,0 +1,323 @@
+// Which hosts URL loading may reach: allow/deny host lists, private and link-local
+// addresses (checked on every resolved address, redirects included) and a redirect limit.
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::redirect;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NetworkPolicy {
+    /// When not empty, only these hosts can be loaded. An entry matches the host
+    /// and its subdomains; a leading `*.` is accepted.
+    #[serde(default)]
+    pub allow_hosts: Vec<String>,
+    /// Hosts never loaded, matched like `allow_hosts`
+    #[serde(default)]
+    pub deny_hosts: Vec<String>,
+    /// Refuse loopback, private, link-local (cloud metadata) and unspecified
+    /// addresses, unless the host is listed in `allow_hosts`. Off unless turned on;
+    /// while on, system proxies are bypassed so every address is checked here.
+    #[serde(default = "default_block_private_ips")]
+    pub block_private_ips: bool,
+    #[serde(default = "default_max_redirects")]
+    pub max_redirects: usize,
+}
+
+fn default_block_private_ips() -> bool {
+    false
+}
+
+fn default_max_redirects() -> usize {
+    DEFAULT_MAX_REDIRECTS
+}
+
+impl Default for NetworkPolicy {
+    fn default() -> Self {
+        NetworkPolicy {
+            allow_hosts: Vec::new(),
+            deny_hosts: Vec::new(),
+            block_private_ips: default_block_private_ips(),
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+        }
+    }
+}
+
+/// A request refused by the policy; found in reqwest's error chain to report it as is
+#[derive(Debug)]
+pub struct PolicyViolation(pub String);
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Blocked by the network policy: {}", self.0)
+    }
+}
+
+impl Error for PolicyViolation {}
+
+impl NetworkPolicy {
+    /// Check a URL before requesting it (the first URL, pages and redirect targets)
+    pub fn check_url(&self, url: &Url) -> Result<(), PolicyViolation> {
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return Err(PolicyViolation(format!("{} URLs are not allowed", url.scheme())));
+        }
+        let host = match url.host_str() {
+            // IPv6 literals come bracketed
+            Some(host) => host.trim_start_matches('[').trim_end_matches(']').to_lowercase(),
+            None => return Err(PolicyViolation("the URL has no host".to_string())),
+        };
+
+        if self.deny_hosts.iter().any(|pattern| host_matches(&host, pattern)) {
+            return Err(PolicyViolation(format!("{} is on the deny list", host)));
+        }
+        let allowed = self.is_allowed(&host);
+        if !self.allow_hosts.is_empty() && !allowed {
+            return Err(PolicyViolation(format!("{} is not on the allow list", host)));
+        }
+
+        // Literal addresses never reach the resolver, so they are checked here
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            if self.block_private_ips && !allowed && is_private_ip(ip) {
+                return Err(PolicyViolation(format!("{} is a private or link-local address", host)));
+            }
+        }
+        Ok(())
+    }
+
+    fn is_allowed(&self, host: &str) -> bool {
+        self.allow_hosts.iter().any(|pattern| host_matches(host, pattern))
+    }
+
+    /// Redirect policy enforcing the limit and checking every target. reqwest only drops
+    /// its own auth headers when a redirect changes host, so requests to an origin in
+    /// `header_origins` (sent with saved or custom headers) never leave that origin.
+    pub fn redirect_policy(self: &Arc<Self>, header_origins: Arc<Mutex<HashSet<String>>>) -> redirect::Policy {
+        let policy = self.clone();
+        redirect::Policy::custom(move |attempt| {
+            // `previous` starts with the original URL
+            if attempt.previous().len() > policy.max_redirects {
+                let limit = policy.max_redirects;
+                return attempt.error(PolicyViolation(format!("more than {} redirects", limit)));
+            }
+            let origin = attempt.previous()[0].origin();
+            if attempt.url().origin() != origin
+                && header_origins.lock().unwrap().contains(&origin.ascii_serialization())
+            {
+                let target = attempt.url().origin().ascii_serialization();
+                return attempt.error(PolicyViolation(format!(
+                    "the redirect to {} would send the request's headers to another site",
+                    target
+                )));
+            }
+            match policy.check_url(attempt.url()) {
+                Ok(()) => attempt.follow(),
+                Err(violation) => attempt.error(violation),
+            }
+        })
+    }
+
+    /// Resolver refusing host names that resolve to blocked addresses
+    pub fn resolver(self: &Arc<Self>) -> Arc<PolicyResolver> {
+        Arc::new(PolicyResolver(self.clone()))
+    }
+}
+
+/// DNS resolution through the policy. Checking what the connection will actually use
+/// also covers redirects and hosts whose DNS answer changes between requests.
+pub struct PolicyResolver(Arc<NetworkPolicy>);
+
+impl Resolve for PolicyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let policy = self.0.clone();
+        let host = name.as_str().to_lowercase();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            if policy.block_private_ips && !policy.is_allowed(&host) {
+                if let Some(addr) = addrs.iter().find(|addr| is_private_ip(addr.ip())) {
+                    let violation =
+                        PolicyViolation(format!("{} resolves to the private or link-local address {}", host, addr.ip()));
+                    return Err(Box::new(violation) as Box<dyn Error + Send + Sync>);
+                }
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// `pattern` is the host itself or one of its parent domains (`*.` and `.` prefixes are ignored)
+fn host_matches(host: &str, pattern: &str) -> bool {
+    let pattern = pattern.trim().trim_start_matches("*.").trim_start_matches('.').to_lowercase();
+    let pattern = pattern.trim_start_matches('[').trim_end_matches(']');
+    !pattern.is_empty()
+        && (host == pattern || host.strip_suffix(pattern).is_some_and(|prefix| prefix.ends_with('.')))
+}
+
+/// Loopback, private, shared (carrier NAT), benchmarking, link-local and unspecified
+/// addresses, including IPv4 ones embedded in IPv6 (mapped, 6to4 and NAT64)
+fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b))
+                || (a == 198 && (b == 18 || b == 19))
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_private_ip(IpAddr::V4(v4));
+            }
+            let segments = v6.segments();
+            let embedded = |high: u16, low: u16| Ipv4Addr::from(((high as u32) << 16) | low as u32);
+            // 6to4 (2002::/16) carries the IPv4 address right after the prefix
+            if segments[0] == 0x2002 && is_private_ip(IpAddr::V4(embedded(segments[1], segments[2]))) {
+                return true;
+            }
+            // NAT64 (64:ff9b::/96) carries it in the last 32 bits
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] && is_private_ip(IpAddr::V4(embedded(segments[6], segments[7]))) {
+                return true;
+            }
+            let first = segments[0];
+            v6.is_loopback()
+                || v6.is_unspecified()
+                || (first & 0xfe00) == 0xfc00 // unique local
+                || (first & 0xffc0) == 0xfe80 // link-local
+        }
+    }
+}
+
+/// Network policy persisted to a JSON file next to the app config (managed Tauri state)
+pub struct PolicyStore {
+    path: PathBuf,
+    policy: Mutex<Arc<NetworkPolicy>>,
+}
+
+impl PolicyStore {
+    /// Load the policy from `path`; a missing or unreadable file gives the default policy
+    pub fn load(path: PathBuf) -> PolicyStore {
+        let policy = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        PolicyStore {
+            path,
+            policy: Mutex::new(Arc::new(policy)),
+        }
+    }
+
+    pub fn get(&self) -> Arc<NetworkPolicy> {
+        self.policy.lock().unwrap().clone()
+    }
+}
+
+/// Command to read the network policy
+#[tauri::command]
+pub async fn get_network_policy(store: State<'_, PolicyStore>) -> Result<NetworkPolicy, String> {
+    Ok(store.get().as_ref().clone())
+}
+
+/// Command to replace the network policy and save it
+#[tauri::command]
+pub async fn set_network_policy(policy: NetworkPolicy, store: State<'_, PolicyStore>) -> Result<(), String> {
+    let clean = |hosts: Vec<String>| -> Vec<String> {
+        hosts
+            .into_iter()
+            .map(|h| h.trim().to_lowercase())
+            .filter(|h| !h.is_empty())
+            .collect()
+    };
+    let policy = NetworkPolicy {
+        allow_hosts: clean(policy.allow_hosts),
+        deny_hosts: clean(policy.deny_hosts),
+        ..policy
+    };
+
+    let content = serde_json::to_string_pretty(&policy).map_err(|e| format!("Failed to serialize network policy: {}", e))?;
+    if let Some(parent) = store.path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+    std::fs::write(&store.path, content).map_err(|e| format!("Failed to save network policy: {}", e))?;
+
+    *store.policy.lock().unwrap() = Arc::new(policy);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn private(ip: &str) -> bool {
+        is_private_ip(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn private_ipv4_ranges() {
+        for ip in [
+            "127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254",
+            "0.0.0.0", "0.1.2.3", "255.255.255.255", "100.64.0.1", "100.127.255.255",
+            "198.18.0.1", "198.19.255.255",
+        ] {
+            assert!(private(ip), "{} should be private", ip);
+        }
+        for ip in ["8.8.8.8", "100.63.255.255", "100.128.0.0", "198.17.255.255", "198.20.0.0", "172.32.0.1"] {
+            assert!(!private(ip), "{} should be public", ip);
+        }
+    }
+
+    #[test]
+    fn private_ipv6_ranges() {
+        for ip in [
+            "::1", "::", "fc00::1", "fd12:3456::1", "fe80::1",
+            // Mapped, 6to4 and NAT64 forms of private IPv4 addresses
+            "::ffff:127.0.0.1", "::ffff:169.254.169.254", "2002:c0a8:0101::1", "2002:7f00:1::",
+            "64:ff9b::10.0.0.1", "64:ff9b::a9fe:a9fe",
+        ] {
+            assert!(private(ip), "{} should be private", ip);
+        }
+        for ip in ["2606:4700::1111", "::ffff:8.8.8.8", "2002:0808:0808::1", "64:ff9b::8.8.8.8", "64:ff9b:1::10.0.0.1"] {
+            assert!(!private(ip), "{} should be public", ip);
+        }
+    }
+
+    #[test]
+    fn host_matches_host_and_subdomains() {
+        assert!(host_matches("example.com", "example.com"));
+        assert!(host_matches("api.example.com", "example.com"));
+        assert!(host_matches("api.example.com", "*.example.com"));
+        assert!(host_matches("api.example.com", ".Example.COM "));
+        assert!(host_matches("::1", "[::1]"));
+
+        assert!(!host_matches("badexample.com", "example.com"));
+        assert!(!host_matches("example.com.evil.io", "example.com"));
+        assert!(!host_matches("example.com", "api.example.com"));
+        assert!(!host_matches("example.com", ""));
+        assert!(!host_matches("example.com", "*."));
+    }
+
+    #[test]
+    fn private_addresses_are_blocked_only_when_enabled() {
+        let url = Url::parse("http://169.254.169.254/latest/meta-data").unwrap();
+        assert!(NetworkPolicy::default().check_url(&url).is_ok());
+
+        let blocking = NetworkPolicy { block_private_ips: true, ..NetworkPolicy::default() };
+        assert!(blocking.check_url(&url).is_err());
+        assert!(blocking.check_url(&Url::parse("http://[::ffff:127.0.0.1]/").unwrap()).is_err());
+
+        let allowed = NetworkPolicy { allow_hosts: vec!["169.254.169.254".to_string()], ..blocking };
+        assert!(allowed.check_url(&url).is_ok());
+    }
+}
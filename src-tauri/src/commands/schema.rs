@@ -0,0 +1,568 @@
+// Schema inference over a whole file: every field path with the JSON types seen at it,
+// optionality and examples, plus a drift report of where the shape of the records changes.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use crate::commands::field_path;
+use crate::commands::file_parser::FileFormat;
+use crate::commands::search::scan_matching_records;
+
+/// Path segment standing for every item of an array (`/tags/*/name`)
+pub const ARRAY_ITEMS: &str = "*";
+const DEFAULT_MAX_FIELDS: usize = 5_000;
+const DEFAULT_MAX_CHANGES: usize = 1_000;
+const EXAMPLES: usize = 3;
+/// Examples longer than this are cut
+const MAX_EXAMPLE_CHARS: usize = 200;
+/// Parent objects in a row a field has to be in before its disappearance is reported,
+/// so optional fields that come and go aren't
+const REMOVAL_MIN_RUN: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SchemaOptions {
+    #[serde(default)]
+    pub max_fields: Option<usize>,
+    /// Changes listed in the drift report (the total is still counted)
+    #[serde(default)]
+    pub max_changes: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FieldSchema {
+    /// JSON Pointer; `*` stands for the items of an array
+    pub path: String,
+    /// JSON types seen: `null`, `boolean`, `integer`, `number`, `string`, `array`, `object`
+    pub types: Vec<String>,
+    /// Records (or array items) the field is in
+    pub present: usize,
+    /// Missing from some of the objects it belongs to
+    pub optional: bool,
+    pub nullable: bool,
+    /// A few distinct scalar values, as JSON
+    pub examples: Vec<String>,
+    pub first_line: usize,
+    pub last_line: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    /// The field's type differs from the one it had before (null aside)
+    TypeChanged,
+    /// The field first shows up after records without it
+    Added,
+    /// The field was in every parent object for a while (`REMOVAL_MIN_RUN` in a row)
+    /// and is no longer seen after this line while its parent still is
+    Removed,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SchemaChange {
+    pub path: String,
+    /// Line id (0-based) to go to; the message counts lines from 1 like the viewer
+    pub line: usize,
+    pub kind: ChangeKind,
+    /// Types before and after, for type changes
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// e.g. "field `user/id` changes from number to string at line 48,211"
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SchemaReport {
+    pub records: usize,
+    pub invalid_lines: usize,
+    /// Sorted by path, parents before their fields
+    pub fields: Vec<FieldSchema>,
+    pub fields_truncated: bool,
+    /// Shape changes in line order
+    pub changes: Vec<SchemaChange>,
+    pub total_changes: usize,
+    /// The inferred schema as a JSON Schema (2020-12) document
+    pub json_schema: serde_json::Value,
+}
+
+/// Command to infer the schema of a whole file and report where it drifts
+#[tauri::command]
+pub async fn infer_schema(
+    path: String,
+    file_format: FileFormat,
+    options: Option<SchemaOptions>,
+) -> Result<SchemaReport, String> {
+    let file_path = PathBuf::from(&path);
+    let options = options.unwrap_or_default();
+    let mut inference = Inference {
+        max_fields: options.max_fields.unwrap_or(DEFAULT_MAX_FIELDS),
+        max_changes: options.max_changes.unwrap_or(DEFAULT_MAX_CHANGES),
+        ..Default::default()
+    };
+
+    let invalid_lines = scan_matching_records(&file_path, &file_format, None, |line_id, json| {
+        inference.add_record(line_id, json);
+        Ok(())
+    })
+    .await?;
+
+    Ok(inference.finish(invalid_lines))
+}
+
+#[derive(Default)]
+struct Inference {
+    max_fields: usize,
+    max_changes: usize,
+    records: usize,
+    fields: HashMap<String, FieldState>,
+    /// Keys found under each object path, in first-seen order
+    children: HashMap<String, Vec<String>>,
+    fields_truncated: bool,
+    changes: Vec<SchemaChange>,
+    total_changes: usize,
+}
+
+#[derive(Default)]
+struct FieldState {
+    types: BTreeSet<&'static str>,
+    present: usize,
+    nulls: usize,
+    /// Times the value was an object, i.e. how often its fields could have been present
+    objects: usize,
+    /// Type of the last non-null value, integers and floats counted as numbers
+    current: Option<&'static str>,
+    examples: Vec<String>,
+    first_line: usize,
+    last_line: usize,
+    /// Parent objects in a row the field was in, up to its last appearance
+    run: usize,
+    /// The parent's object count when the field was last seen
+    parent_objects_seen: usize,
+}
+
+impl Inference {
+    fn add_record(&mut self, line: usize, json: &serde_json::Value) {
+        self.records += 1;
+        self.visit(line, "", json);
+    }
+
+    /// Walk a value like `collect_headers` does, but recording containers too and
+    /// folding array items into one `*` path
+    fn visit(&mut self, line: usize, path: &str, value: &serde_json::Value) {
+        if !self.observe(line, path, value) {
+            return;
+        }
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, child) in map {
+                    let child_path = field_path::child(path, key);
+                    self.visit(line, &child_path, child);
+                }
+            }
+            serde_json::Value::Array(items) => {
+                let items_path = field_path::child(path, ARRAY_ITEMS);
+                for item in items {
+                    self.visit(line, &items_path, item);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Record one value at `path`; false when the field limit keeps it out
+    fn observe(&mut self, line: usize, path: &str, value: &serde_json::Value) -> bool {
+        if !self.fields.contains_key(path) {
+            if self.fields.len() >= self.max_fields {
+                self.fields_truncated = true;
+                return false;
+            }
+            let parent_objects_before = match parent(path) {
+                Some((parent_path, key)) => {
+                    let siblings = self.children.entry(parent_path.to_string()).or_default();
+                    if key != ARRAY_ITEMS {
+                        siblings.push(path.to_string());
+                    }
+                    // The parent's count already includes the current value
+                    self.fields.get(parent_path).map_or(0, |p| p.objects.saturating_sub(1))
+                }
+                None => 0,
+            };
+            if parent_objects_before > 0 && !is_array_items(path) {
+                let message = format!("field `{}` first appears at line {}", field_path::label(path), thousands(line + 1));
+                self.push_change(SchemaChange {
+                    path: path.to_string(),
+                    line,
+                    kind: ChangeKind::Added,
+                    from: None,
+                    to: None,
+                    message,
+                });
+            }
+            self.fields.insert(
+                path.to_string(),
+                FieldState {
+                    first_line: line,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let type_name = type_name(value);
+        let parent_objects = parent(path)
+            .and_then(|(parent_path, _)| self.fields.get(parent_path))
+            .map_or(0, |p| p.objects);
+        let state = self.fields.get_mut(path).unwrap();
+        state.present += 1;
+        state.last_line = line;
+        state.run = if parent_objects == state.parent_objects_seen + 1 { state.run + 1 } else { 1 };
+        state.parent_objects_seen = parent_objects;
+        state.types.insert(type_name);
+        match value {
+            serde_json::Value::Null => state.nulls += 1,
+            serde_json::Value::Object(_) => state.objects += 1,
+            serde_json::Value::Array(_) => {}
+            scalar => {
+                if state.examples.len() < EXAMPLES {
+                    let example: String = scalar.to_string().chars().take(MAX_EXAMPLE_CHARS).collect();
+                    if !state.examples.contains(&example) {
+                        state.examples.push(example);
+                    }
+                }
+            }
+        }
+
+        if !value.is_null() {
+            let kind = drift_type(type_name);
+            let previous = state.current.replace(kind);
+            if let Some(previous) = previous.filter(|&p| p != kind) {
+                let message = format!(
+                    "field `{}` changes from {} to {} at line {}",
+                    field_path::label(path),
+                    previous,
+                    kind,
+                    thousands(line + 1)
+                );
+                self.push_change(SchemaChange {
+                    path: path.to_string(),
+                    line,
+                    kind: ChangeKind::TypeChanged,
+                    from: Some(previous.to_string()),
+                    to: Some(kind.to_string()),
+                    message,
+                });
+            }
+        }
+        true
+    }
+
+    fn push_change(&mut self, change: SchemaChange) {
+        self.total_changes += 1;
+        if self.changes.len() < self.max_changes {
+            self.changes.push(change);
+        }
+    }
+
+    fn finish(mut self, invalid_lines: usize) -> SchemaReport {
+        let parent_of = |path: &str| parent(path).map(|(p, _)| p.to_string());
+
+        // Fields that were always there, then stop appearing while their parent goes on
+        let mut removed = Vec::new();
+        for (path, state) in &self.fields {
+            if is_array_items(path) {
+                continue;
+            }
+            let Some(parent_state) = parent_of(path).and_then(|p| self.fields.get(&p)) else {
+                continue;
+            };
+            if state.run >= REMOVAL_MIN_RUN && parent_state.objects > state.parent_objects_seen {
+                removed.push(SchemaChange {
+                    path: path.clone(),
+                    line: state.last_line,
+                    kind: ChangeKind::Removed,
+                    from: None,
+                    to: None,
+                    message: format!(
+                        "field `{}` is last seen at line {}",
+                        field_path::label(path),
+                        thousands(state.last_line + 1)
+                    ),
+                });
+            }
+        }
+        for change in removed {
+            self.push_change(change);
+        }
+        self.changes.sort_by(|a, b| a.line.cmp(&b.line).then_with(|| a.path.cmp(&b.path)));
+
+        let json_schema = serde_json::json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+        });
+        let json_schema = match (json_schema, self.schema_for("")) {
+            (serde_json::Value::Object(mut root), serde_json::Value::Object(schema)) => {
+                root.extend(schema);
+                serde_json::Value::Object(root)
+            }
+            (root, _) => root,
+        };
+
+        let mut fields: Vec<FieldSchema> = self
+            .fields
+            .iter()
+            .filter(|(path, _)| !path.is_empty())
+            .map(|(path, state)| {
+                let parent_objects = parent_of(path).and_then(|p| self.fields.get(&p)).map_or(0, |s| s.objects);
+                FieldSchema {
+                    path: path.clone(),
+                    types: state.types.iter().map(|t| t.to_string()).collect(),
+                    present: state.present,
+                    optional: !is_array_items(path) && state.present < parent_objects,
+                    nullable: state.nulls > 0,
+                    examples: state.examples.clone(),
+                    first_line: state.first_line,
+                    last_line: state.last_line,
+                }
+            })
+            .collect();
+        fields.sort_by_cached_key(|f| field_path::segments(&f.path));
+
+        SchemaReport {
+            records: self.records,
+            invalid_lines,
+            fields,
+            fields_truncated: self.fields_truncated,
+            changes: self.changes,
+            total_changes: self.total_changes,
+            json_schema,
+        }
+    }
+
+    /// JSON Schema of the values seen at `path`
+    fn schema_for(&self, path: &str) -> serde_json::Value {
+        let Some(state) = self.fields.get(path) else {
+            return serde_json::json!({});
+        };
+        let mut schema = serde_json::Map::new();
+
+        let types: Vec<&str> = state.types.iter().copied().collect();
+        // Integers are numbers too, so listing both would be redundant
+        let types: Vec<&str> = if types.contains(&"number") {
+            types.into_iter().filter(|&t| t != "integer").collect()
+        } else {
+            types
+        };
+        schema.insert(
+            "type".to_string(),
+            match types.as_slice() {
+                [single] => serde_json::Value::from(*single),
+                many => serde_json::Value::from(many.to_vec()),
+            },
+        );
+
+        if state.objects > 0 {
+            let mut properties = serde_json::Map::new();
+            let mut required = Vec::new();
+            for child_path in self.children.get(path).into_iter().flatten() {
+                let Some(child) = self.fields.get(child_path) else { continue };
+                let key = field_path::segments(child_path).pop().unwrap_or_default();
+                if child.present >= state.objects {
+                    required.push(key.clone());
+                }
+                properties.insert(key, self.schema_for(child_path));
+            }
+            schema.insert("properties".to_string(), serde_json::Value::Object(properties));
+            if !required.is_empty() {
+                schema.insert("required".to_string(), required.into());
+            }
+        }
+        if state.types.contains("array") {
+            let items_path = field_path::child(path, ARRAY_ITEMS);
+            if self.fields.contains_key(&items_path) {
+                schema.insert("items".to_string(), self.schema_for(&items_path));
+            }
+        }
+        serde_json::Value::Object(schema)
+    }
+}
+
+fn type_name(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "boolean",
+        serde_json::Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        serde_json::Value::Number(_) => "number",
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Array(_) => "array",
+        serde_json::Value::Object(_) => "object",
+    }
+}
+
+/// Type compared for drift: a field holding both integers and floats hasn't changed shape
+fn drift_type(type_name: &'static str) -> &'static str {
+    if type_name == "integer" { "number" } else { type_name }
+}
+
+/// The items of an array rather than a field of an object
+fn is_array_items(path: &str) -> bool {
+    parent(path).is_some_and(|(_, key)| key == ARRAY_ITEMS)
+}
+
+/// Parent pointer and last (escaped) segment of a pointer
+fn parent(path: &str) -> Option<(&str, &str)> {
+    path.rfind('/').map(|i| (&path[..i], &path[i + 1..]))
+}
+
+/// `48211` as `48,211`
+fn thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn infer(records: &[serde_json::Value]) -> SchemaReport {
+        let mut inference = Inference {
+            max_fields: DEFAULT_MAX_FIELDS,
+            max_changes: DEFAULT_MAX_CHANGES,
+            ..Default::default()
+        };
+        for (line_id, record) in records.iter().enumerate() {
+            inference.add_record(line_id, record);
+        }
+        inference.finish(0)
+    }
+
+    fn changes(records: &[serde_json::Value]) -> Vec<SchemaChange> {
+        infer(records).changes
+    }
+
+    fn removed(changes: &[SchemaChange]) -> Vec<(&str, usize)> {
+        changes
+            .iter()
+            .filter(|c| c.kind == ChangeKind::Removed)
+            .map(|c| (c.path.as_str(), c.line))
+            .collect()
+    }
+
+    #[test]
+    fn drift_messages_count_lines_from_one() {
+        let mut records: Vec<serde_json::Value> =
+            (0..REMOVAL_MIN_RUN).map(|i| serde_json::json!({ "id": i, "gone": true })).collect();
+        records.push(serde_json::json!({ "id": "10" }));
+        records.push(serde_json::json!({ "id": "11", "new": 1 }));
+        let changes = changes(&records);
+        let summary: Vec<(usize, &str)> = changes.iter().map(|c| (c.line, c.message.as_str())).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (9, "field `gone` is last seen at line 10"),
+                (10, "field `id` changes from number to string at line 11"),
+                (11, "field `new` first appears at line 12"),
+            ]
+        );
+    }
+
+    #[test]
+    fn sparse_fields_are_not_reported_removed() {
+        // An `error` object on a few lines, never for long
+        let records: Vec<serde_json::Value> = (0..100)
+            .map(|i| match i % 7 {
+                0 if i < 60 => serde_json::json!({ "level": "error", "error": { "code": i } }),
+                _ => serde_json::json!({ "level": "info" }),
+            })
+            .collect();
+        let changes = changes(&records);
+        assert_eq!(removed(&changes), vec![]);
+        // It is in the first record, so it isn't reported added either
+        let added: Vec<&str> = changes.iter().filter(|c| c.kind == ChangeKind::Added).map(|c| c.path.as_str()).collect();
+        assert!(added.is_empty(), "{:?}", added);
+    }
+
+    #[test]
+    fn removal_needs_a_run_of_records_with_the_field() {
+        let record = |with: bool| if with { serde_json::json!({ "a": 1, "b": 2 }) } else { serde_json::json!({ "a": 1 }) };
+
+        // Sporadic at first, then in every record for a while, then gone
+        let mut records: Vec<serde_json::Value> = (0..20).map(|i| record(i % 3 == 0)).collect();
+        records.extend((0..REMOVAL_MIN_RUN).map(|_| record(true)));
+        records.extend((0..5).map(|_| record(false)));
+        assert_eq!(removed(&changes(&records)), vec![("/b", 20 + REMOVAL_MIN_RUN - 1)]);
+
+        // A run one record short isn't enough
+        let mut records: Vec<serde_json::Value> = (0..REMOVAL_MIN_RUN - 1).map(|_| record(true)).collect();
+        records.extend((0..5).map(|_| record(false)));
+        assert_eq!(removed(&changes(&records)), vec![]);
+
+        // Nor is a field that lasts as long as its parent
+        let records: Vec<serde_json::Value> = (0..REMOVAL_MIN_RUN * 2).map(|_| record(true)).collect();
+        assert_eq!(removed(&changes(&records)), vec![]);
+
+        // Nested fields are measured against their parent object, not the records
+        let mut records: Vec<serde_json::Value> = (0..REMOVAL_MIN_RUN)
+            .flat_map(|i| [serde_json::json!({ "user": { "id": i, "name": "x" } }), serde_json::json!({})])
+            .collect();
+        records.push(serde_json::json!({ "user": { "id": 10 } }));
+        assert_eq!(removed(&changes(&records)), vec![("/user/name", 2 * REMOVAL_MIN_RUN - 2)]);
+    }
+
+    #[test]
+    fn fields_are_required_unless_missing_somewhere() {
+        let records = [
+            serde_json::json!({ "id": 1, "user": { "id": 1, "email": "a@x" }, "tags": [{ "n": "a" }, { "n": "b", "x": 1 }] }),
+            serde_json::json!({ "id": 2.5, "user": { "id": "u2" }, "tags": [], "note": null }),
+            serde_json::json!({ "id": 3, "user": { "id": 3 }, "tags": ["t"] }),
+        ];
+        let report = infer(&records);
+
+        let fields: Vec<(&str, bool, bool)> =
+            report.fields.iter().map(|f| (f.path.as_str(), f.optional, f.nullable)).collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("/id", false, false),
+                ("/note", true, true),
+                ("/tags", false, false),
+                // Array items are never optional, their fields can be
+                ("/tags/*", false, false),
+                ("/tags/*/n", false, false),
+                ("/tags/*/x", true, false),
+                ("/user", false, false),
+                ("/user/email", true, false),
+                ("/user/id", false, false),
+            ]
+        );
+        let field = |path: &str| report.fields.iter().find(|f| f.path == path).unwrap();
+        assert_eq!(field("/tags/*").types, vec!["object", "string"]);
+        assert_eq!(field("/tags/*").present, 3);
+        assert_eq!(field("/user/id").types, vec!["integer", "string"]);
+        assert_eq!(field("/user/id").examples, vec!["1", "\"u2\"", "3"]);
+
+        let schema = &report.json_schema;
+        assert_eq!(schema["$schema"], "https://json-schema.org/draft/2020-12/schema");
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["required"], serde_json::json!(["id", "tags", "user"]));
+        assert_eq!(schema["properties"]["id"]["type"], "number");
+        assert_eq!(schema["properties"]["note"]["type"], "null");
+        assert_eq!(schema["properties"]["user"]["required"], serde_json::json!(["id"]));
+        assert_eq!(schema["properties"]["user"]["properties"]["id"]["type"], serde_json::json!(["integer", "string"]));
+        let items = &schema["properties"]["tags"]["items"];
+        assert_eq!(items["type"], serde_json::json!(["object", "string"]));
+        assert_eq!(items["required"], serde_json::json!(["n"]));
+        assert_eq!(items["properties"]["x"]["type"], "integer");
+    }
+
+    #[test]
+    fn thousands_separators() {
+        assert_eq!(thousands(0), "0");
+        assert_eq!(thousands(999), "999");
+        assert_eq!(thousands(1_000), "1,000");
+        assert_eq!(thousands(48_211), "48,211");
+        assert_eq!(thousands(1_234_567), "1,234,567");
+    }
+}
@@ -1,19 +1,53 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tauri::ipc::Channel;
-use tokio::io::AsyncBufReadExt;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 use regex::Regex;
 use jsonpath_rust::JsonPathFinder;
 // use std::str::FromStr;
-use crate::commands::file_parser::FileFormat;
+use crate::commands::field_path;
+use crate::commands::file_parser::{detect_file_format, is_record_file, open_record_reader, FileFormat};
 
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SearchQuery {
     pub text: Option<String>,
     pub json_path: Option<String>,
     pub case_sensitive: bool,
     pub regex: bool,
+    /// Return the lines that do NOT match the query (like `grep -v`)
+    #[serde(default)]
+    pub invert: bool,
+    /// Only search these line ids, used to refine a previous result set
+    #[serde(default)]
+    pub within_line_ids: Option<Vec<usize>>,
+    /// Which part of each record the text is matched against
+    #[serde(default)]
+    pub scope: SearchScope,
+    /// Restrict the text search to these JSON Pointer field paths (e.g. `/user/name`)
+    #[serde(default)]
+    pub fields: Option<Vec<String>>,
+    /// Only match the text as a whole word
+    #[serde(default)]
+    pub whole_word: bool,
+    /// Maximum edit distance for fuzzy matching (ignored in regex mode)
+    #[serde(default)]
+    pub fuzzy: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub enum SearchScope {
+    /// The raw line, keys and values alike
+    #[default]
+    Line,
+    /// Scalar values only
+    Values,
+    /// Object key names only
+    Keys,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -21,49 +55,225 @@ pub struct SearchResult {
     pub line_id: usize,
     pub matches: Vec<String>,
     pub context: String,
+    /// A neighbouring record included for context, not a match itself
+    #[serde(default)]
+    pub is_context: bool,
+    /// The file the result comes from (multi-file search only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchStats {
     pub total_matches: usize,
     pub lines_searched: usize,
+    /// Per-value counts of the `group_by` field, most frequent first (count mode only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_counts: Option<Vec<GroupCount>>,
 }
 
-#[tauri::command]
-pub async fn search_in_file(
-    path: String,
-    query: SearchQuery,
-    file_format: FileFormat,
-    channel: Channel<Vec<SearchResult>>,
-) -> Result<SearchStats, String> {
-    let file_path = PathBuf::from(&path);
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MultiSearchStats {
+    pub total_matches: usize,
+    pub lines_searched: usize,
+    pub files: Vec<FileSearchStats>,
+}
 
-    // Common search logic helper
-    let check_match = |line_str: &str, json_val: Option<&serde_json::Value>| -> Option<Vec<String>> {
-        let mut collected_matches = Vec::new();
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileSearchStats {
+    pub path: String,
+    pub total_matches: usize,
+    pub lines_searched: usize,
+    /// Why the file could not be searched, the other files are still searched
+    pub error: Option<String>,
+}
 
-        // Scenario A: Text Search ONLY
-        if query.text.is_some() && query.json_path.is_none() {
-            let text = query.text.as_ref().unwrap();
-            let found = if query.regex {
-                 let regex_pattern = if query.case_sensitive {
-                    Regex::new(text)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroupCount {
+    /// The grouped field value, `None` when the field is missing
+    pub value: Option<String>,
+    pub count: usize,
+}
+
+/// How the query text is compared against a candidate string
+enum TextMatcher {
+    Plain { text: String, needle: String, case_sensitive: bool },
+    Regex(Regex),
+    Fuzzy { needle: Vec<String>, max_distance: usize, case_sensitive: bool },
+}
+
+impl TextMatcher {
+    fn new(query: &SearchQuery) -> Result<Option<Self>, String> {
+        let text = match query.text {
+            Some(ref text) => text,
+            None => return Ok(None),
+        };
+
+        if let Some(max_distance) = query.fuzzy.filter(|d| *d > 0 && !query.regex) {
+            let needle = text
+                .split_whitespace()
+                .map(|w| if query.case_sensitive { w.to_string() } else { w.to_lowercase() })
+                .collect();
+            return Ok(Some(TextMatcher::Fuzzy { needle, max_distance, case_sensitive: query.case_sensitive }));
+        }
+
+        if query.regex || query.whole_word {
+            // Checked as typed first, so the error points into the user's pattern
+            if query.regex {
+                Regex::new(text).map_err(|e| format!("Invalid regex: {}", e))?;
+            }
+            let mut pattern = if query.regex { text.clone() } else { regex::escape(text) };
+            if query.whole_word {
+                pattern = format!(r"\b(?:{})\b", pattern);
+            }
+            if !query.case_sensitive {
+                pattern = format!("(?i){}", pattern);
+            }
+            let regex = Regex::new(&pattern).map_err(|e| format!("Invalid regex: {}", e))?;
+            return Ok(Some(TextMatcher::Regex(regex)));
+        }
+
+        let needle = if query.case_sensitive { text.clone() } else { text.to_lowercase() };
+        Ok(Some(TextMatcher::Plain { text: text.clone(), needle, case_sensitive: query.case_sensitive }))
+    }
+
+    /// Returns the matched fragments of `haystack`, empty if there is no match
+    fn find(&self, haystack: &str) -> Vec<String> {
+        match self {
+            TextMatcher::Plain { text, needle, case_sensitive } => {
+                let found = if *case_sensitive {
+                    haystack.contains(needle.as_str())
                 } else {
-                    Regex::new(&format!("(?i){}", text))
+                    haystack.to_lowercase().contains(needle.as_str())
                 };
-                 if let Ok(regex) = regex_pattern {
-                    regex.find_iter(line_str).map(|m| m.as_str().to_string()).collect::<Vec<_>>()
-                 } else {
-                     vec![]
-                 }
+                if found { vec![text.clone()] } else { vec![] }
+            }
+            TextMatcher::Regex(regex) => {
+                regex.find_iter(haystack).map(|m| m.as_str().to_string()).collect()
+            }
+            TextMatcher::Fuzzy { needle, max_distance, case_sensitive } => {
+                let words: Vec<&str> = haystack
+                    .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+                    .filter(|w| !w.is_empty())
+                    .collect();
+                if needle.is_empty() || words.len() < needle.len() {
+                    return vec![];
+                }
+
+                words
+                    .windows(needle.len())
+                    .filter(|window| {
+                        let distance: usize = window
+                            .iter()
+                            .zip(needle.iter())
+                            .map(|(word, target)| {
+                                if *case_sensitive {
+                                    edit_distance(word, target)
+                                } else {
+                                    edit_distance(&word.to_lowercase(), target)
+                                }
+                            })
+                            .sum();
+                        distance <= *max_distance
+                    })
+                    .map(|window| window.join(" "))
+                    .collect()
+            }
+        }
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        !self.find(haystack).is_empty()
+    }
+}
+
+/// Compiled form of a `SearchQuery`, built once per search and evaluated per record
+pub struct LineMatcher<'a> {
+    query: &'a SearchQuery,
+    text: Option<TextMatcher>,
+    scope: Option<HashSet<usize>>,
+    last_in_scope: Option<usize>,
+}
+
+impl<'a> LineMatcher<'a> {
+    pub fn new(query: &'a SearchQuery) -> Result<Self, String> {
+        // Refine mode: restrict the search to the line ids of a previous result set
+        let scope: Option<HashSet<usize>> = query
+            .within_line_ids
+            .as_ref()
+            .map(|ids| ids.iter().copied().collect());
+        let last_in_scope = scope.as_ref().map(|ids| ids.iter().max().copied().unwrap_or(0));
+
+        Ok(LineMatcher {
+            query,
+            text: TextMatcher::new(query)?,
+            scope,
+            last_in_scope,
+        })
+    }
+
+    /// Whether a line id belongs to the refined result set (always true without one)
+    pub fn in_scope(&self, line_id: usize) -> bool {
+        self.scope.as_ref().is_none_or(|ids| ids.contains(&line_id))
+    }
+
+    /// Whether every line of the refined result set lies before `line_id`
+    pub fn is_past_scope(&self, line_id: usize) -> bool {
+        self.last_in_scope.is_some_and(|last| line_id > last)
+    }
+
+    /// Whether the record must be parsed to evaluate the query
+    fn needs_json(&self) -> bool {
+        self.query.json_path.is_some() || self.query.scope != SearchScope::Line || self.query.fields.is_some()
+    }
+
+    /// Evaluate the query against a record, honouring the inverted mode.
+    /// Returns the matched fragments, or `None` when the record is not a hit.
+    pub fn evaluate(&self, line_str: &str, json_val: Option<&serde_json::Value>) -> Option<Vec<String>> {
+        let found = if self.needs_json() {
+            match json_val {
+                Some(v) => self.check_match(line_str, Some(v)),
+                None => {
+                    let parsed = serde_json::from_str::<serde_json::Value>(line_str).ok();
+                    self.check_match(line_str, parsed.as_ref())
+                }
+            }
+        } else {
+            self.check_match(line_str, json_val)
+        };
+
+        if self.query.invert {
+            match found {
+                Some(_) => None,
+                None => Some(Vec::new()),
+            }
+        } else {
+            found
+        }
+    }
+
+    fn check_match(&self, line_str: &str, json_val: Option<&serde_json::Value>) -> Option<Vec<String>> {
+        let mut collected_matches = Vec::new();
+
+        // Scenario A: Text Search ONLY
+        if self.query.json_path.is_none() {
+            let matcher = self.text.as_ref()?;
+            let found = if self.query.scope == SearchScope::Line && self.query.fields.is_none() {
+                matcher.find(line_str)
             } else {
-                let search_line = if query.case_sensitive { line_str.to_string() } else { line_str.to_lowercase() };
-                let search_text = if query.case_sensitive { text.clone() } else { text.to_lowercase() };
-                if search_line.contains(&search_text) {
-                    vec![text.clone()]
-                } else {
-                    vec![]
+                let json = json_val?;
+                let mut candidates = Vec::new();
+                match self.query.fields {
+                    Some(ref fields) => {
+                        for path in fields {
+                            if let Some(v) = field_path::resolve(json, path) {
+                                collect_candidates(v, self.query.scope, &mut candidates);
+                            }
+                        }
+                    }
+                    None => collect_candidates(json, self.query.scope, &mut candidates),
                 }
+                candidates.iter().flat_map(|c| matcher.find(c)).collect()
             };
 
             if !found.is_empty() {
@@ -73,52 +283,20 @@ pub async fn search_in_file(
         }
 
         // Scenario B: JSONPath Search (with optional Text Filter on results)
-        if let Some(ref json_path) = query.json_path {
-            // We need a JSON value to query against
-             let val_to_check = if let Some(v) = json_val {
-                 Some(v.clone())
-             } else {
-                 serde_json::from_str::<serde_json::Value>(line_str).ok()
-             };
-
-            if let Some(v) = val_to_check {
-                if let Some(mut json_matches) = evaluate_jsonpath(&v, json_path) {
+        if let Some(ref json_path) = self.query.json_path {
+            if let Some(v) = json_val {
+                if let Some(json_matches) = evaluate_jsonpath(v, json_path) {
                     // Start with all JSONPath matches
                     // If there is NO text query, we accept all these matches.
                     // If there IS a text query, we filter these matches.
 
-                    if let Some(ref text) = query.text {
-                         // Filter the JSONPath results: keep only those containing the text
-                         let filtered_matches: Vec<String> = json_matches.into_iter().filter(|result_str| {
-                             if query.regex {
-                                 let regex_pattern = if query.case_sensitive {
-                                    Regex::new(text)
-                                } else {
-                                    Regex::new(&format!("(?i){}", text))
-                                };
-                                 if let Ok(regex) = regex_pattern {
-                                    regex.is_match(result_str)
-                                 } else {
-                                     false
-                                 }
-                             } else {
-                                let target = if query.case_sensitive { result_str.clone() } else { result_str.to_lowercase() };
-                                let query_text = if query.case_sensitive { text.clone() } else { text.to_lowercase() };
-                                target.contains(&query_text)
-                             }
-                         }).collect();
-
-                         if !filtered_matches.is_empty() {
-                             // Return the text matches found within the JSONPath results?
-                             // Or return the JSONPath values themselves?
-                             // Usually highlighting the "match" (the found text) is correct for search.
-                             // But the user might want to see the *values*.
-                             // Let's return the full field value (filtered_matches) as the "match".
-                             collected_matches.extend(filtered_matches);
-                         }
+                    if let Some(ref matcher) = self.text {
+                        // Filter the JSONPath results: keep only those containing the text
+                        // Return the full field value as the "match" so the user sees the values.
+                        collected_matches.extend(json_matches.into_iter().filter(|result_str| matcher.is_match(result_str)));
                     } else {
-                         // No text filter, just return the JSONPath matches
-                         collected_matches.extend(json_matches);
+                        // No text filter, just return the JSONPath matches
+                        collected_matches.extend(json_matches);
                     }
                 }
             }
@@ -129,85 +307,366 @@ pub async fn search_in_file(
         } else {
             None
         }
+    }
+}
+
+#[tauri::command]
+pub async fn search_in_file(
+    path: String,
+    query: SearchQuery,
+    file_format: FileFormat,
+    context_before: Option<usize>,
+    context_after: Option<usize>,
+    channel: Channel<Vec<SearchResult>>,
+) -> Result<SearchStats, String> {
+    let file_path = PathBuf::from(&path);
+    run_search(&file_path, file_format, &query, context_before, context_after, None, &channel).await
+}
+
+/// Search several files (or every JSON/JSONL file under a folder) with one query.
+/// Files are searched concurrently and every result is tagged with its source path.
+#[tauri::command]
+pub async fn search_in_files(
+    paths: Vec<String>,
+    query: SearchQuery,
+    channel: Channel<Vec<SearchResult>>,
+) -> Result<MultiSearchStats, String> {
+    const MAX_CONCURRENT_FILES: usize = 4;
+
+    let mut files = Vec::new();
+    for path in &paths {
+        collect_record_files(PathBuf::from(path), &mut files).await?;
+    }
+    files.sort();
+    files.dedup();
+
+    let query = Arc::new(query);
+    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_FILES));
+    let mut tasks = JoinSet::new();
+
+    for (index, file_path) in files.into_iter().enumerate() {
+        let query = query.clone();
+        let semaphore = semaphore.clone();
+        let channel = channel.clone();
+
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            let source = file_path.to_string_lossy().to_string();
+            let result = match detect_file_format(&file_path).await {
+                Ok(format) => run_search(&file_path, format, &query, None, None, Some(source.clone()), &channel).await,
+                Err(e) => Err(e),
+            };
+            (index, source, result)
+        });
+    }
+
+    let mut file_stats = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        let (index, path, result) = joined.map_err(|e| format!("Search task failed: {}", e))?;
+        file_stats.push((index, FileSearchStats {
+            path,
+            total_matches: result.as_ref().map(|s| s.total_matches).unwrap_or(0),
+            lines_searched: result.as_ref().map(|s| s.lines_searched).unwrap_or(0),
+            error: result.err(),
+        }));
+    }
+    file_stats.sort_by_key(|(index, _)| *index);
+    let files: Vec<FileSearchStats> = file_stats.into_iter().map(|(_, stats)| stats).collect();
+
+    Ok(MultiSearchStats {
+        total_matches: files.iter().map(|f| f.total_matches).sum(),
+        lines_searched: files.iter().map(|f| f.lines_searched).sum(),
+        files,
+    })
+}
+
+/// Expand a path into the record files it refers to, walking folders recursively
+async fn collect_record_files(path: PathBuf, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let metadata = tokio::fs::metadata(&path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    if !metadata.is_dir() {
+        files.push(path);
+        return Ok(());
+    }
+
+    let mut pending = vec![path];
+    while let Some(dir) = pending.pop() {
+        let mut entries = tokio::fs::read_dir(&dir)
+            .await
+            .map_err(|e| format!("Failed to read folder {}: {}", dir.display(), e))?;
+
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|e| format!("Failed to read folder {}: {}", dir.display(), e))?
+        {
+            let entry_path = entry.path();
+            let file_type = entry.file_type().await.map_err(|e| e.to_string())?;
+            if file_type.is_dir() {
+                pending.push(entry_path);
+            } else if is_record_file(&entry_path) {
+                files.push(entry_path);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Stream the results of one query over one file, tagging them with `source` when set
+async fn run_search(
+    file_path: &Path,
+    file_format: FileFormat,
+    query: &SearchQuery,
+    context_before: Option<usize>,
+    context_after: Option<usize>,
+    source: Option<String>,
+    channel: &Channel<Vec<SearchResult>>,
+) -> Result<SearchStats, String> {
+    let matcher = LineMatcher::new(query)?;
+    let context_before = context_before.unwrap_or(0);
+    let context_after = context_after.unwrap_or(0);
+
+    let mut total_matches = 0;
+    let mut lines_searched = 0;
+    const CHUNK_SIZE: usize = 100;
+    let mut chunk: Vec<SearchResult> = Vec::with_capacity(CHUNK_SIZE);
+
+    // Context windows (like `grep -C`): records not yet emitted that may precede the
+    // next hit, and how many records after the last hit are still owed. A record is
+    // emitted at most once, so overlapping windows merge naturally.
+    let mut pending_before: VecDeque<(usize, String)> = VecDeque::with_capacity(context_before);
+    let mut after_remaining = 0;
+
+    let mut push_result = |result: SearchResult| -> Result<(), String> {
+        chunk.push(result);
+        if chunk.len() >= CHUNK_SIZE {
+            channel.send(chunk.clone()).map_err(|e| format!("Failed to send: {}", e))?;
+            chunk.clear();
+        }
+        Ok(())
     };
 
+    scan_records(file_path, &file_format, |line_id, line_str, json_val| {
+        if matcher.is_past_scope(line_id) && after_remaining == 0 {
+            // Nothing left to refine past the last requested line
+            return Ok(false);
+        }
+
+        let hit = if matcher.in_scope(line_id) {
+            lines_searched += 1;
+            matcher.evaluate(line_str, json_val)
+        } else {
+            None
+        };
+
+        match hit {
+            Some(matches) => {
+                for (context_id, context) in pending_before.drain(..) {
+                    push_result(SearchResult {
+                        line_id: context_id,
+                        matches: Vec::new(),
+                        context,
+                        is_context: true,
+                        source: source.clone(),
+                    })?;
+                }
+                push_result(SearchResult {
+                    line_id,
+                    matches,
+                    context: line_str.to_string(),
+                    is_context: false,
+                    source: source.clone(),
+                })?;
+                total_matches += 1;
+                after_remaining = context_after;
+            }
+            None if after_remaining > 0 => {
+                push_result(SearchResult {
+                    line_id,
+                    matches: Vec::new(),
+                    context: line_str.to_string(),
+                    is_context: true,
+                    source: source.clone(),
+                })?;
+                after_remaining -= 1;
+            }
+            None if context_before > 0 => {
+                if pending_before.len() == context_before {
+                    pending_before.pop_front();
+                }
+                pending_before.push_back((line_id, line_str.to_string()));
+            }
+            None => {}
+        }
+        Ok(true)
+    })
+    .await?;
+
+    if !chunk.is_empty() {
+        channel.send(chunk).map_err(|e| format!("Failed to send: {}", e))?;
+    }
+
+    Ok(SearchStats { total_matches, lines_searched, group_counts: None })
+}
+
+/// Count the lines matching a query without streaming them back, optionally
+/// grouped by the value of a field (JSON Pointer)
+#[tauri::command]
+pub async fn count_in_file(
+    path: String,
+    query: SearchQuery,
+    file_format: FileFormat,
+    group_by: Option<String>,
+) -> Result<SearchStats, String> {
+    let file_path = PathBuf::from(&path);
+    let matcher = LineMatcher::new(&query)?;
+
+    let mut total_matches = 0;
+    let mut lines_searched = 0;
+    let mut groups: HashMap<Option<String>, usize> = HashMap::new();
+
+    scan_records(&file_path, &file_format, |line_id, line_str, json_val| {
+        if matcher.is_past_scope(line_id) {
+            return Ok(false);
+        }
+        if !matcher.in_scope(line_id) {
+            return Ok(true);
+        }
+        lines_searched += 1;
+
+        match group_by {
+            Some(ref group_path) => {
+                // Parse once and share the value between matching and grouping
+                let parsed = match json_val {
+                    Some(_) => None,
+                    None => serde_json::from_str::<serde_json::Value>(line_str).ok(),
+                };
+                let json = json_val.or(parsed.as_ref());
+                if matcher.evaluate(line_str, json).is_some() {
+                    let key = json
+                        .and_then(|v| field_path::resolve(v, group_path))
+                        .map(|v| match v {
+                            serde_json::Value::String(s) => s.clone(),
+                            v => v.to_string(),
+                        });
+                    *groups.entry(key).or_insert(0) += 1;
+                    total_matches += 1;
+                }
+            }
+            None => {
+                if matcher.evaluate(line_str, json_val).is_some() {
+                    total_matches += 1;
+                }
+            }
+        }
+        Ok(true)
+    })
+    .await?;
+
+    let group_counts = group_by.map(|_| {
+        let mut counts: Vec<GroupCount> = groups
+            .into_iter()
+            .map(|(value, count)| GroupCount { value, count })
+            .collect();
+        counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+        counts
+    });
+
+    Ok(SearchStats { total_matches, lines_searched, group_counts })
+}
+
+/// Walk every record of a file in order, calling `visit` with its line id, raw text
+/// and (for JSON arrays) the already parsed value. Stops early when `visit` returns `Ok(false)`.
+pub async fn scan_records<F>(file_path: &Path, file_format: &FileFormat, mut visit: F) -> Result<(), String>
+where
+    F: FnMut(usize, &str, Option<&serde_json::Value>) -> Result<bool, String>,
+{
+    let mut reader = open_record_reader(file_path).await?;
+
     // Handle JsonArray (pretty printed or single line) separately
     if matches!(file_format, FileFormat::JsonArray) {
-        let content = tokio::fs::read_to_string(&file_path)
+        let mut content = String::new();
+        reader
+            .read_to_string(&mut content)
             .await
             .map_err(|e| format!("Failed to read file: {}", e))?;
 
         let json: serde_json::Value = serde_json::from_str(&content)
             .map_err(|e| format!("Failed to parse JSON: {}", e))?;
 
-        let mut total_matches = 0;
-        let mut lines_searched = 0;
-        const CHUNK_SIZE: usize = 100;
-        let mut chunk: Vec<SearchResult> = Vec::with_capacity(CHUNK_SIZE);
-
         if let Some(array) = json.as_array() {
             for (index, item) in array.iter().enumerate() {
-                lines_searched += 1;
                 let line_str = serde_json::to_string(item).unwrap_or_default();
-
-                if let Some(matches) = check_match(&line_str, Some(item)) {
-                     let result = SearchResult {
-                        line_id: index,
-                        matches,
-                        context: line_str,
-                    };
-                    chunk.push(result);
-                    if chunk.len() >= CHUNK_SIZE {
-                         channel.send(chunk.clone()).map_err(|e| format!("Failed to send: {}", e))?;
-                         chunk.clear();
-                    }
-                    total_matches += 1;
+                if !visit(index, &line_str, Some(item))? {
+                    break;
                 }
             }
         }
 
-        if !chunk.is_empty() {
-            channel.send(chunk).map_err(|e| format!("Failed to send: {}", e))?;
-        }
-
-        return Ok(SearchStats { total_matches, lines_searched });
+        return Ok(());
     }
 
     // Default JsonL
-    let file = tokio::fs::File::open(&file_path)
-        .await
-        .map_err(|e| format!("Failed to open file: {}", e))?;
-
-    let reader = tokio::io::BufReader::new(file);
     let mut lines = reader.lines();
-
     let mut line_num = 0;
-    let mut total_matches = 0;
-    const CHUNK_SIZE: usize = 100;
-    let mut chunk: Vec<SearchResult> = Vec::with_capacity(CHUNK_SIZE);
 
     while let Ok(Some(line)) = lines.next_line().await {
-         if let Some(matches) = check_match(&line, None) {
-             let result = SearchResult {
-                line_id: line_num,
-                matches,
-                context: line.clone(),
-            };
-            chunk.push(result);
-            if chunk.len() >= CHUNK_SIZE {
-                channel.send(chunk.clone()).map_err(|e| format!("Failed to send: {}", e))?;
-                chunk.clear();
-            }
-            total_matches += 1;
+        if !visit(line_num, &line, None)? {
+            break;
         }
         line_num += 1;
     }
 
-    if !chunk.is_empty() {
-        channel.send(chunk).map_err(|e| format!("Failed to send: {}", e))?;
-    }
+    Ok(())
+}
+
+/// Walk the parsed records of a file, only the ones matching `query` when given.
+/// Lines that aren't valid JSON are skipped; returns how many there were.
+pub async fn scan_matching_records<F>(
+    file_path: &Path,
+    file_format: &FileFormat,
+    query: Option<&SearchQuery>,
+    mut visit: F,
+) -> Result<usize, String>
+where
+    F: FnMut(usize, &serde_json::Value) -> Result<(), String>,
+{
+    let matcher = query.map(LineMatcher::new).transpose()?;
+    let mut invalid_lines = 0;
+
+    scan_records(file_path, file_format, |line_id, line_str, json_val| {
+        if let Some(ref matcher) = matcher {
+            if matcher.is_past_scope(line_id) {
+                return Ok(false);
+            }
+            if !matcher.in_scope(line_id) {
+                return Ok(true);
+            }
+        }
 
-    Ok(SearchStats { total_matches, lines_searched: line_num })
+        let parsed = match json_val {
+            Some(_) => None,
+            None => serde_json::from_str::<serde_json::Value>(line_str).ok(),
+        };
+        let Some(json) = json_val.or(parsed.as_ref()) else {
+            if !line_str.trim().is_empty() {
+                invalid_lines += 1;
+            }
+            return Ok(true);
+        };
+        if matcher.as_ref().is_some_and(|m| m.evaluate(line_str, Some(json)).is_none()) {
+            return Ok(true);
+        }
+
+        visit(line_id, json)?;
+        Ok(true)
+    })
+    .await?;
+
+    Ok(invalid_lines)
 }
 
 fn evaluate_jsonpath(json: &serde_json::Value, path: &str) -> Option<Vec<String>> {
@@ -238,4 +697,446 @@ fn evaluate_jsonpath(json: &serde_json::Value, path: &str) -> Option<Vec<String>
         }
         Err(_) => None
     }
-}
\ No newline at end of file
+}
+
+/// Collect the strings a scoped search is matched against: key names or scalar values
+fn collect_candidates(json: &serde_json::Value, scope: SearchScope, out: &mut Vec<String>) {
+    match json {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                if scope == SearchScope::Keys {
+                    out.push(key.clone());
+                }
+                collect_candidates(value, scope, out);
+            }
+        }
+        serde_json::Value::Array(arr) => {
+            for item in arr {
+                collect_candidates(item, scope, out);
+            }
+        }
+        serde_json::Value::String(s) if scope != SearchScope::Keys => out.push(s.clone()),
+        serde_json::Value::Null => {}
+        v if scope != SearchScope::Keys => out.push(v.to_string()),
+        _ => {}
+    }
+}
+
+/// Levenshtein distance between two strings, counted in chars
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        curr[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            curr[j] = (prev[j] + 1).min(curr[j - 1] + 1).min(prev[j - 1] + cost);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_util::{channel_sink, gzip, temp_file, temp_path};
+
+    fn query(text: &str) -> SearchQuery {
+        SearchQuery {
+            text: Some(text.to_string()),
+            json_path: None,
+            case_sensitive: false,
+            regex: false,
+            invert: false,
+            within_line_ids: None,
+            scope: SearchScope::Line,
+            fields: None,
+            whole_word: false,
+            fuzzy: None,
+        }
+    }
+
+    fn hits(query: &SearchQuery, lines: &[&str]) -> Vec<usize> {
+        let matcher = LineMatcher::new(query).unwrap();
+        lines
+            .iter()
+            .enumerate()
+            .filter(|(line_id, line)| matcher.in_scope(*line_id) && matcher.evaluate(line, None).is_some())
+            .map(|(line_id, _)| line_id)
+            .collect()
+    }
+
+    const LINES: [&str; 4] = [
+        r#"{"level":"error","msg":"disk full"}"#,
+        r#"{"level":"info","msg":"started"}"#,
+        r#"{"level":"ERROR","msg":"timeout"}"#,
+        r#"{"level":"debug"}"#,
+    ];
+
+    #[test]
+    fn invert_returns_the_lines_without_a_match() {
+        let mut q = query("error");
+        assert_eq!(hits(&q, &LINES), vec![0, 2]);
+
+        q.invert = true;
+        assert_eq!(hits(&q, &LINES), vec![1, 3]);
+        // Inverted hits have nothing to highlight
+        let matcher = LineMatcher::new(&q).unwrap();
+        assert_eq!(matcher.evaluate(LINES[1], None), Some(Vec::new()));
+    }
+
+    #[test]
+    fn invert_applies_to_json_path_queries() {
+        let mut q = query("");
+        q.text = None;
+        q.json_path = Some("$.msg".to_string());
+        assert_eq!(hits(&q, &LINES), vec![0, 1, 2]);
+
+        q.invert = true;
+        assert_eq!(hits(&q, &LINES), vec![3]);
+    }
+
+    #[test]
+    fn refine_searches_only_the_given_lines() {
+        let mut q = query("error");
+        q.within_line_ids = Some(vec![2, 3]);
+        assert_eq!(hits(&q, &LINES), vec![2]);
+
+        // Refining an inverted search inverts within the previous results only
+        q.invert = true;
+        assert_eq!(hits(&q, &LINES), vec![3]);
+    }
+
+    #[test]
+    fn refine_scope_ends_after_the_last_line() {
+        let mut q = query("x");
+        q.within_line_ids = Some(vec![7, 3, 5]);
+        let matcher = LineMatcher::new(&q).unwrap();
+        assert!(matcher.in_scope(5));
+        assert!(!matcher.in_scope(4));
+        assert!(!matcher.is_past_scope(7));
+        assert!(matcher.is_past_scope(8));
+
+        // An empty result set leaves nothing to search
+        q.within_line_ids = Some(Vec::new());
+        let matcher = LineMatcher::new(&q).unwrap();
+        assert!(!matcher.in_scope(0));
+        assert!(matcher.is_past_scope(1));
+
+        q.within_line_ids = None;
+        let matcher = LineMatcher::new(&q).unwrap();
+        assert!(matcher.in_scope(1_000_000));
+        assert!(!matcher.is_past_scope(1_000_000));
+    }
+
+
+    fn found(query: &SearchQuery, line: &str) -> Option<Vec<String>> {
+        LineMatcher::new(query).unwrap().evaluate(line, None)
+    }
+
+    #[test]
+    fn scope_keys_and_values() {
+        let line = r#"{"name":"status","status":"ok","nested":{"code":404}}"#;
+        let mut q = query("status");
+        q.scope = SearchScope::Keys;
+        assert_eq!(found(&q, line), Some(vec!["status".to_string()]));
+        q.text = Some("ok".to_string());
+        assert_eq!(found(&q, line), None);
+
+        q.scope = SearchScope::Values;
+        assert_eq!(found(&q, line), Some(vec!["ok".to_string()]));
+        // Numbers are values too; keys are not
+        q.text = Some("404".to_string());
+        assert!(found(&q, line).is_some());
+        q.text = Some("nested".to_string());
+        assert_eq!(found(&q, line), None);
+    }
+
+    #[test]
+    fn fields_restrict_the_search() {
+        let line = r#"{"user":{"name":"Ada","city":"London"},"note":"Ada was here"}"#;
+        let mut q = query("ada");
+        q.scope = SearchScope::Values;
+        q.fields = Some(vec!["/user/name".to_string()]);
+        assert_eq!(found(&q, line), Some(vec!["ada".to_string()]));
+
+        q.fields = Some(vec!["/user/city".to_string(), "/missing".to_string()]);
+        assert_eq!(found(&q, line), None);
+
+        // A field holding an object is searched throughout
+        q.fields = Some(vec!["/user".to_string()]);
+        assert!(found(&q, line).is_some());
+
+        // Lines that aren't JSON can't match a field
+        q.fields = Some(vec!["/user/name".to_string()]);
+        assert_eq!(found(&q, "Ada, not json"), None);
+    }
+
+    #[test]
+    fn whole_word() {
+        let mut q = query("err");
+        q.whole_word = true;
+        assert_eq!(found(&q, r#"{"msg":"error"}"#), None);
+        assert_eq!(found(&q, r#"{"msg":"an ERR here"}"#), Some(vec!["ERR".to_string()]));
+
+        q.case_sensitive = true;
+        assert_eq!(found(&q, r#"{"msg":"an ERR here"}"#), None);
+
+        // The text is taken literally, not as a pattern
+        let mut q = query("a.b");
+        q.whole_word = true;
+        assert!(found(&q, "x a.b y").is_some());
+        assert_eq!(found(&q, "x aXb y"), None);
+    }
+
+    #[test]
+    fn fuzzy_matches_within_the_distance() {
+        let mut q = query("conection refused");
+        q.fuzzy = Some(1);
+        assert_eq!(
+            found(&q, r#"{"msg":"Connection refused by peer"}"#),
+            Some(vec!["Connection refused".to_string()])
+        );
+        assert_eq!(found(&q, r#"{"msg":"connect refused"}"#), None);
+
+        // The distance is summed over the words
+        assert_eq!(found(&q, r#"{"msg":"connection refuse"}"#), None);
+        q.fuzzy = Some(2);
+        assert!(found(&q, r#"{"msg":"connection refuse"}"#).is_some());
+
+        // Fuzzy matching doesn't apply to regexes
+        let mut q = query("c.nnection");
+        q.fuzzy = Some(1);
+        q.regex = true;
+        assert!(found(&q, "connection").is_some());
+        assert_eq!(found(&q, "conection"), None);
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("flaw", "lawn"), 2);
+        assert_eq!(edit_distance("same", "same"), 0);
+        // Counted in chars, not bytes
+        assert_eq!(edit_distance("café", "cafe"), 1);
+        assert_eq!(edit_distance("日本語", "日本"), 1);
+    }
+
+
+    const LOG: &str = r#"{"level":"error","code":500}
+{"level":"info","code":200}
+{"level":"error","code":503}
+not json, error
+{"code":500,"msg":"error"}
+{"level":"error","code":500}
+"#;
+
+    fn groups(stats: &SearchStats) -> Vec<(Option<&str>, usize)> {
+        stats
+            .group_counts
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|g| (g.value.as_deref(), g.count))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn count_matches() {
+        let path = temp_file("log.jsonl", LOG);
+        let path_str = path.to_string_lossy().to_string();
+
+        let stats = count_in_file(path_str.clone(), query("error"), FileFormat::JsonL, None).await.unwrap();
+        assert_eq!((stats.total_matches, stats.lines_searched), (5, 6));
+        assert!(stats.group_counts.is_none());
+
+        let mut q = query("error");
+        q.invert = true;
+        q.within_line_ids = Some(vec![0, 1, 2]);
+        let stats = count_in_file(path_str, q, FileFormat::JsonL, None).await.unwrap();
+        assert_eq!((stats.total_matches, stats.lines_searched), (1, 3));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn count_grouped_by_a_field() {
+        let path = temp_file("log.jsonl", LOG);
+        let path_str = path.to_string_lossy().to_string();
+
+        // Most frequent first, ties by value; a missing field (or a line that isn't
+        // JSON) is its own group
+        let stats = count_in_file(path_str.clone(), query("error"), FileFormat::JsonL, Some("/level".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(stats.total_matches, 5);
+        assert_eq!(groups(&stats), vec![(Some("error"), 3), (None, 2)]);
+
+        // Non-string values are grouped by their JSON text
+        let stats = count_in_file(path_str, query("error"), FileFormat::JsonL, Some("/code".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(groups(&stats), vec![(Some("500"), 3), (None, 1), (Some("503"), 1)]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn count_in_a_json_array() {
+        let path = temp_file("log.json", r#"[{"level":"error"},{"level":"info"},{"level":"error"}]"#);
+        let stats = count_in_file(
+            path.to_string_lossy().to_string(),
+            query("error"),
+            FileFormat::JsonArray,
+            Some("/level".to_string()),
+        )
+        .await
+        .unwrap();
+        assert_eq!((stats.total_matches, stats.lines_searched), (2, 3));
+        assert_eq!(groups(&stats), vec![(Some("error"), 2)]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+
+    /// Run a search over `content`, returning (line id, is context) of what was streamed
+    async fn search_with_context(
+        content: &str,
+        query: &SearchQuery,
+        before: usize,
+        after: usize,
+    ) -> (Vec<(usize, bool)>, SearchStats) {
+        let path = temp_file("records.jsonl", content);
+        let (channel, results) = channel_sink::<Vec<SearchResult>>();
+        let stats = run_search(&path, FileFormat::JsonL, query, Some(before), Some(after), None, &channel)
+            .await
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let results: Vec<SearchResult> = results.lock().unwrap().concat();
+        for result in results.iter().filter(|r| r.is_context) {
+            assert!(result.matches.is_empty());
+        }
+        (results.iter().map(|r| (r.line_id, r.is_context)).collect(), stats)
+    }
+
+    fn numbered(count: usize, hits: &[usize]) -> String {
+        (0..count)
+            .map(|i| format!("{{\"n\":{},\"hit\":{}}}\n", i, hits.contains(&i)))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn context_around_hits() {
+        let content = numbered(10, &[3, 7]);
+        let (results, stats) = search_with_context(&content, &query("true"), 1, 2).await;
+        assert_eq!(
+            results,
+            vec![(2, true), (3, false), (4, true), (5, true), (6, true), (7, false), (8, true), (9, true)]
+        );
+        // Context records are not matches
+        assert_eq!(stats.total_matches, 2);
+    }
+
+    #[tokio::test]
+    async fn overlapping_context_is_sent_once() {
+        let content = numbered(10, &[3, 4, 6]);
+        let (results, _) = search_with_context(&content, &query("true"), 2, 2).await;
+        assert_eq!(
+            results,
+            vec![(1, true), (2, true), (3, false), (4, false), (5, true), (6, false), (7, true), (8, true)]
+        );
+    }
+
+    #[tokio::test]
+    async fn context_is_cut_at_the_file_edges() {
+        let content = numbered(3, &[0, 2]);
+        let (results, _) = search_with_context(&content, &query("true"), 5, 5).await;
+        assert_eq!(results, vec![(0, false), (1, true), (2, false)]);
+
+        let (results, _) = search_with_context(&content, &query("true"), 0, 0).await;
+        assert_eq!(results, vec![(0, false), (2, false)]);
+    }
+
+    #[tokio::test]
+    async fn context_of_a_refined_search_reaches_outside_the_scope() {
+        let content = numbered(10, &[3, 5]);
+        let mut q = query("true");
+        q.within_line_ids = Some(vec![3]);
+        let (results, stats) = search_with_context(&content, &q, 1, 2).await;
+        assert_eq!(results, vec![(2, true), (3, false), (4, true), (5, true)]);
+        assert_eq!((stats.total_matches, stats.lines_searched), (1, 1));
+    }
+
+
+    #[tokio::test]
+    async fn search_across_a_folder() {
+        let dir = temp_path("folder");
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("a.jsonl"), "{\"msg\":\"error\"}\n{\"msg\":\"ok\"}\n").unwrap();
+        let compressed = gzip(r#"[{"msg":"error"},{"msg":"error again"}]"#).await;
+        std::fs::write(dir.join("nested").join("b.json.gz"), compressed).unwrap();
+        std::fs::write(dir.join("broken.json"), "[{\"msg\":\"error\"").unwrap();
+        // Not a record file, never searched
+        std::fs::write(dir.join("notes.txt"), "error").unwrap();
+
+        let (channel, results) = channel_sink::<Vec<SearchResult>>();
+        let stats = search_in_files(vec![dir.to_string_lossy().to_string()], query("error"), channel)
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Files in path order; one that can't be read reports its error without
+        // failing the others
+        let name = |path: &str| Path::new(path).file_name().unwrap().to_string_lossy().to_string();
+        let files: Vec<(String, usize, bool)> = stats
+            .files
+            .iter()
+            .map(|f| (name(&f.path), f.total_matches, f.error.is_some()))
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                ("a.jsonl".to_string(), 1, false),
+                ("broken.json".to_string(), 0, true),
+                ("b.json.gz".to_string(), 2, false),
+            ]
+        );
+        assert_eq!((stats.total_matches, stats.lines_searched), (3, 4));
+
+        let mut sources: Vec<(String, usize)> = results
+            .lock()
+            .unwrap()
+            .concat()
+            .iter()
+            .map(|r| (name(r.source.as_deref().unwrap()), r.line_id))
+            .collect();
+        sources.sort();
+        assert_eq!(
+            sources,
+            vec![("a.jsonl".to_string(), 0), ("b.json.gz".to_string(), 0), ("b.json.gz".to_string(), 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_regex_is_an_error_not_an_empty_result() {
+        let mut q = query("(error");
+        q.regex = true;
+        let expected = LineMatcher::new(&q).err().unwrap();
+        assert!(expected.starts_with("Invalid regex: "), "{}", expected);
+        assert!(expected.contains("\n    (error\n") && expected.ends_with("unclosed group"), "{}", expected);
+
+        let path = temp_file("log.jsonl", LOG);
+        let error = count_in_file(path.to_string_lossy().to_string(), q, FileFormat::JsonL, None).await.unwrap_err();
+        assert_eq!(error, expected);
+        std::fs::remove_file(&path).unwrap();
+
+        // The same text as a plain search is fine
+        assert_eq!(hits(&query("(error"), &LINES), Vec::<usize>::new());
+    }
+}
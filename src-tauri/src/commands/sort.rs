@@ -1,39 +1,169 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BinaryHeap;
+use std::io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tauri::ipc::Channel;
-use tokio::io::AsyncBufReadExt;
-use crate::commands::file_parser::{JsonLine, FileFormat};
+use tauri::State;
+use uuid::Uuid;
+use icu_collator::{options::CollatorOptions, Collator, CollatorBorrowed};
+use icu_locale_core::Locale;
+use crate::commands::expression::Expression;
+use crate::commands::field_path;
+use crate::commands::file_parser::{self, JsonLine, FileFormat};
 use crate::commands::search::SearchResult;
+use crate::commands::sort_view::SortViews;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Memory used for sort keys before sorted runs are spilled to disk
+pub(crate) const DEFAULT_SORT_MEMORY_MB: usize = 256;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct SortColumn {
+	/// JSON Pointer to the field, e.g. `/user/id`
+	#[serde(default)]
 	pub column: String,
 	pub direction: String,
+	/// Force how values are interpreted instead of auto-detecting them
+	#[serde(default)]
+	pub value_type: Option<SortValueType>,
+	/// How strings are compared, case-insensitive by default
+	#[serde(default)]
+	pub collation: Option<Collation>,
+	/// Locale for `Collation::Locale`, e.g. `de`, `sv` or `ja` (root collation if unset)
+	#[serde(default)]
+	pub locale: Option<String>,
+	/// Sort by a value computed per record instead of `column`, e.g. `$.end - $.start`,
+	/// `len($.items)` or `lower(trim($.name))` (see `expression`)
+	#[serde(default)]
+	pub expression: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum Collation {
+	/// Lowercased strings compared by code point
+	CaseInsensitive,
+	/// Strings compared by code point as-is
+	CaseSensitive,
+	/// Case-insensitive with embedded numbers in numeric order
+	Natural,
+	/// Unicode collation tailored to the column's locale
+	Locale,
+}
+
+/// A `SortColumn` prepared for comparisons (collator built once per sort)
+pub(crate) struct SortSpec {
+	path: String,
+	descending: bool,
+	value_type: Option<SortValueType>,
+	collation: Collation,
+	collator: Option<CollatorBorrowed<'static>>,
+	expression: Option<Expression>,
+}
+
+impl SortSpec {
+	pub(crate) fn compile(columns: &[SortColumn]) -> Result<Vec<SortSpec>, String> {
+		columns
+			.iter()
+			.map(|col| {
+				let collation = col.collation.unwrap_or(if col.value_type == Some(SortValueType::Natural) {
+					Collation::Natural
+				} else {
+					Collation::CaseInsensitive
+				});
+
+				let collator = if collation == Collation::Locale {
+					let locale: Locale = col
+						.locale
+						.as_deref()
+						.unwrap_or("und")
+						.parse()
+						.map_err(|e| format!("Invalid locale {:?}: {}", col.locale, e))?;
+					let collator = Collator::try_new((&locale).into(), CollatorOptions::default())
+						.map_err(|e| format!("No collation available for {}: {}", locale, e))?;
+					Some(collator)
+				} else {
+					None
+				};
+
+				let expression = col
+					.expression
+					.as_deref()
+					.map(|e| e.parse::<Expression>().map_err(|err| format!("Invalid sort expression {:?}: {}", e, err)))
+					.transpose()?;
+
+				Ok(SortSpec {
+					path: col.column.clone(),
+					descending: col.direction == "desc",
+					value_type: col.value_type,
+					collation,
+					collator,
+					expression,
+				})
+			})
+			.collect()
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum SortValueType {
+	Number,
+	Date,
+	String,
+	/// Strings compared with embedded numbers in numeric order (`item2` < `item10`)
+	Natural,
 }
 
 /// Sortable value representation for comparison
-enum SortValue {
+#[derive(Serialize, Deserialize)]
+pub(crate) enum SortValue {
 	Null,
 	Number(f64),
-	Date(i64),  // Unix timestamp in seconds
+	Date(i64),  // Unix timestamp in microseconds
 	String(String),
 }
 
-/// Extract value from nested JSON by underscore-separated path
-fn get_nested_value(json: &serde_json::Value, path: &str) -> Option<serde_json::Value> {
-	let parts: Vec<&str> = path.split('_').collect();
-	let mut current = json;
 
-	for part in parts {
-		match current {
-			serde_json::Value::Object(obj) => {
-				current = obj.get(part)?;
+/// Extract the sort keys of one record, one per column
+pub(crate) fn extract_sort_keys(json: &serde_json::Value, columns: &[SortSpec]) -> Vec<SortValue> {
+	columns
+		.iter()
+		.map(|col| {
+			let to_value = |v: &serde_json::Value| match col.value_type {
+				Some(value_type) => to_sort_value_as(v, value_type),
+				None => to_sort_value(v),
+			};
+			match col.expression {
+				Some(ref expression) => to_value(&expression.evaluate(json)),
+				None => field_path::resolve(json, &col.path).map(to_value).unwrap_or(SortValue::Null),
+			}
+		})
+		.collect()
+}
+
+/// Convert JSON value to sortable representation using an explicit type
+fn to_sort_value_as(val: &serde_json::Value, value_type: SortValueType) -> SortValue {
+	let as_string = || match val {
+		serde_json::Value::String(s) => s.clone(),
+		v => v.to_string(),
+	};
+
+	match (value_type, val) {
+		(_, serde_json::Value::Null) => SortValue::Null,
+		(SortValueType::Number, serde_json::Value::Number(n)) => {
+			n.as_f64().map(SortValue::Number).unwrap_or_else(|| SortValue::String(n.to_string()))
+		}
+		(SortValueType::Number, serde_json::Value::String(s)) => {
+			match s.trim().parse::<f64>() {
+				Ok(n) if n.is_finite() => SortValue::Number(n),
+				_ => SortValue::String(s.clone()),
 			}
-			_ => return None,
 		}
+		(SortValueType::Date, serde_json::Value::String(_)) | (SortValueType::Date, serde_json::Value::Number(_)) => {
+			parse_timestamp(val).map(SortValue::Date).unwrap_or_else(|| SortValue::String(as_string()))
+		}
+		(SortValueType::Number, _) | (SortValueType::Date, _) => to_sort_value(val),
+		(SortValueType::String, _) | (SortValueType::Natural, _) => SortValue::String(as_string()),
 	}
-
-	Some(current.clone())
 }
 
 /// Convert JSON value to sortable representation
@@ -51,13 +181,15 @@ fn to_sort_value(val: &serde_json::Value) -> SortValue {
 			}
 		}
 		serde_json::Value::String(s) => {
-			// Try to parse as ISO 8601 date first
-			if let Ok(timestamp) = parse_iso_date(s) {
+			// Try to parse as a date first
+			if let Ok(timestamp) = parse_date_string(s) {
 				return SortValue::Date(timestamp);
 			}
-			// Try to parse as number
+			// Try to parse as number (NaN/inf stay strings so keys can be spilled as JSON)
 			if let Ok(n) = s.parse::<f64>() {
-				return SortValue::Number(n);
+				if n.is_finite() {
+					return SortValue::Number(n);
+				}
 			}
 			SortValue::String(s.clone())
 		}
@@ -66,40 +198,157 @@ fn to_sort_value(val: &serde_json::Value) -> SortValue {
 	}
 }
 
-/// Parse ISO 8601 date string to Unix timestamp
-fn parse_iso_date(s: &str) -> Result<i64, ()> {
-	// Formats with timezone (Z suffix)
-	const TIMEZONE_FORMATS: &[&str] = &[
-		"%Y-%m-%dT%H:%M:%S%.fZ",      // 2024-01-15T10:30:00.123Z
-		"%Y-%m-%dT%H:%M:%SZ",          // 2024-01-15T10:30:00Z
+/// Parse a date/time string to a Unix timestamp in microseconds.
+/// Accepts RFC 3339 with any offset, RFC 2822, common log format and the usual
+/// naive layouts (treated as UTC).
+pub(crate) fn parse_date_string(s: &str) -> Result<i64, ()> {
+	let s = s.trim();
+
+	// Cheap pre-check so plain strings don't go through every format
+	if s.len() < 8 || s.len() > 64 || s.bytes().filter(|b| b.is_ascii_digit()).count() < 4 {
+		return Err(());
+	}
+
+	if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(s) {
+		return Ok(dt.timestamp_micros());
+	}
+	if let Ok(dt) = chrono::DateTime::parse_from_rfc2822(s) {
+		return Ok(dt.timestamp_micros());
+	}
+
+	// Formats with an explicit offset
+	const OFFSET_FORMATS: &[&str] = &[
+		"%Y-%m-%dT%H:%M:%S%.f%z",      // 2024-01-15T10:30:00.123+0200
+		"%Y-%m-%d %H:%M:%S%.f%:z",     // 2024-01-15 10:30:00.123+02:00
+		"%Y-%m-%d %H:%M:%S%.f %z",     // 2024-01-15 10:30:00.123 +0200
+		"%d/%b/%Y:%H:%M:%S %z",        // 10/Oct/2000:13:55:36 -0700 (common log format)
 	];
 
 	// Formats without timezone (treated as UTC)
 	const NAIVE_FORMATS: &[&str] = &[
+		"%Y-%m-%dT%H:%M:%S%.f",        // 2024-01-15T10:30:00.123
+		"%Y-%m-%dT%H:%M",              // 2024-01-15T10:30
 		"%Y-%m-%d %H:%M:%S%.f",        // 2024-01-15 10:30:00.123
-		"%Y-%m-%d %H:%M:%S",           // 2024-01-15 10:30:00
+		"%Y-%m-%d %H:%M",              // 2024-01-15 10:30
+		"%Y/%m/%d %H:%M:%S%.f",        // 2024/01/15 10:30:00
+		"%d/%b/%Y:%H:%M:%S",           // 10/Oct/2000:13:55:36
+	];
+
+	// Date-only formats (midnight UTC)
+	const DATE_FORMATS: &[&str] = &[
 		"%Y-%m-%d",                    // 2024-01-15
+		"%Y/%m/%d",                    // 2024/01/15
 	];
 
-	// Try timezone-aware formats first
-	for fmt in TIMEZONE_FORMATS {
+	for fmt in OFFSET_FORMATS {
 		if let Ok(dt) = chrono::DateTime::parse_from_str(s, fmt) {
-			return Ok(dt.timestamp());
+			return Ok(dt.timestamp_micros());
 		}
 	}
 
-	// Try timezone-less formats (treat as UTC)
 	for fmt in NAIVE_FORMATS {
 		if let Ok(dt) = chrono::NaiveDateTime::parse_from_str(s, fmt) {
-			return Ok(dt.and_utc().timestamp());
+			return Ok(dt.and_utc().timestamp_micros());
+		}
+	}
+
+	for fmt in DATE_FORMATS {
+		if let Ok(date) = chrono::NaiveDate::parse_from_str(s, fmt) {
+			return Ok(date.and_time(chrono::NaiveTime::MIN).and_utc().timestamp_micros());
 		}
 	}
 
 	Err(())
 }
 
-/// Compare two sort values with direction
-fn compare_sort_values(a: &SortValue, b: &SortValue, direction: &str) -> std::cmp::Ordering {
+/// Interpret an epoch number as microseconds, guessing the unit from its magnitude
+/// (seconds, milliseconds, microseconds or nanoseconds)
+pub(crate) fn epoch_to_micros(n: f64) -> Option<i64> {
+	if !n.is_finite() {
+		return None;
+	}
+	let magnitude = n.abs();
+	let micros = if magnitude < 1e11 {
+		n * 1e6
+	} else if magnitude < 1e14 {
+		n * 1e3
+	} else if magnitude < 1e17 {
+		n
+	} else {
+		n / 1e3
+	};
+	Some(micros as i64)
+}
+
+/// Timestamp (microseconds) of a JSON value: date strings, or epoch numbers
+/// and numeric strings
+pub(crate) fn parse_timestamp(val: &serde_json::Value) -> Option<i64> {
+	match val {
+		serde_json::Value::String(s) => parse_date_string(s)
+			.ok()
+			.or_else(|| s.trim().parse::<f64>().ok().and_then(epoch_to_micros)),
+		serde_json::Value::Number(n) => n.as_f64().and_then(epoch_to_micros),
+		_ => None,
+	}
+}
+
+/// Compare two records' sort keys column by column, falling through on ties
+pub(crate) fn compare_sort_keys(a: &[SortValue], b: &[SortValue], columns: &[SortSpec]) -> std::cmp::Ordering {
+	for ((a, b), col) in a.iter().zip(b.iter()).zip(columns.iter()) {
+		let cmp = compare_sort_values(a, b, col);
+		if cmp != std::cmp::Ordering::Equal {
+			return cmp;
+		}
+	}
+	std::cmp::Ordering::Equal
+}
+
+/// Compare strings treating runs of digits as numbers, so `item2` sorts before `item10`
+fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+	let mut a_chars = a.chars().peekable();
+	let mut b_chars = b.chars().peekable();
+
+	loop {
+		match (a_chars.peek().copied(), b_chars.peek().copied()) {
+			(None, None) => return std::cmp::Ordering::Equal,
+			(None, Some(_)) => return std::cmp::Ordering::Less,
+			(Some(_), None) => return std::cmp::Ordering::Greater,
+			(Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+				let mut a_num = String::new();
+				while let Some(c) = a_chars.peek().copied().filter(|c| c.is_ascii_digit()) {
+					a_num.push(c);
+					a_chars.next();
+				}
+				let mut b_num = String::new();
+				while let Some(c) = b_chars.peek().copied().filter(|c| c.is_ascii_digit()) {
+					b_num.push(c);
+					b_chars.next();
+				}
+				// Compare digit runs by magnitude without overflowing on long numbers
+				let a_trim = a_num.trim_start_matches('0');
+				let b_trim = b_num.trim_start_matches('0');
+				let cmp = a_trim
+					.len()
+					.cmp(&b_trim.len())
+					.then_with(|| a_trim.cmp(b_trim))
+					.then_with(|| a_num.len().cmp(&b_num.len()));
+				if cmp != std::cmp::Ordering::Equal {
+					return cmp;
+				}
+			}
+			(Some(x), Some(y)) => {
+				if x != y {
+					return x.cmp(&y);
+				}
+				a_chars.next();
+				b_chars.next();
+			}
+		}
+	}
+}
+
+/// Compare two sort values with the column's direction
+fn compare_sort_values(a: &SortValue, b: &SortValue, column: &SortSpec) -> std::cmp::Ordering {
 	// Null values always come last
 	let a_is_null = matches!(a, SortValue::Null);
 	let b_is_null = matches!(b, SortValue::Null);
@@ -119,7 +368,12 @@ fn compare_sort_values(a: &SortValue, b: &SortValue, direction: &str) -> std::cm
 			a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)
 		}
 		(SortValue::Date(a), SortValue::Date(b)) => a.cmp(b),
-		(SortValue::String(a), SortValue::String(b)) => a.to_lowercase().cmp(&b.to_lowercase()),
+		(SortValue::String(a), SortValue::String(b)) => match (column.collation, &column.collator) {
+			(Collation::Locale, Some(collator)) => collator.compare(a, b),
+			(Collation::CaseSensitive, _) => a.cmp(b),
+			(Collation::Natural, _) => natural_cmp(&a.to_lowercase(), &b.to_lowercase()),
+			_ => a.to_lowercase().cmp(&b.to_lowercase()),
+		},
 		(SortValue::String(_), SortValue::Number(_)) => std::cmp::Ordering::Greater,
 		(SortValue::Number(_), SortValue::String(_)) => std::cmp::Ordering::Less,
 		(SortValue::Date(_), _) => std::cmp::Ordering::Less,
@@ -127,67 +381,40 @@ fn compare_sort_values(a: &SortValue, b: &SortValue, direction: &str) -> std::cm
 		_ => std::cmp::Ordering::Equal,
 	};
 
-	if direction == "desc" {
+	if column.descending {
 		cmp.reverse()
 	} else {
 		cmp
 	}
 }
 
-/// Helper function to sort JsonLine items and stream results
-async fn sort_and_stream_json_lines(
-	mut items: Vec<(usize, JsonLine, SortValue)>,
-	direction: String,
-	channel: Channel<Vec<JsonLine>>,
-) -> Result<usize, String> {
-	// Sort by pre-extracted values
-	items.sort_by(|a, b| {
-		let cmp = compare_sort_values(&a.2, &b.2, &direction);
-		if cmp == std::cmp::Ordering::Equal {
-			a.0.cmp(&b.0)  // Stable sort by original index
-		} else {
-			cmp
-		}
-	});
-
-	// Extract sorted lines for streaming
-	let lines: Vec<JsonLine> = items.into_iter().map(|(_, line, _)| line).collect();
-	let lines_len = lines.len();
-
-	// Stream sorted results
-	const CHUNK_SIZE: usize = 2000;
-	let mut chunk: Vec<JsonLine> = Vec::with_capacity(CHUNK_SIZE);
-
-	for line in &lines {
-		chunk.push(line.clone());
-		if chunk.len() >= CHUNK_SIZE {
-			channel.send(chunk.clone()).map_err(|e| format!("Failed to send: {}", e))?;
-			chunk.clear();
-		}
-	}
-
-	if !chunk.is_empty() {
-		channel.send(chunk).map_err(|e| format!("Failed to send: {}", e))?;
-	}
-
-	Ok(lines_len)
-}
-
 /// Helper function to sort SearchResult items and stream results
 async fn sort_and_stream_search_results(
-	mut items: Vec<(usize, SearchResult, SortValue)>,
-	direction: String,
+	mut items: Vec<(usize, SearchResult, Vec<SortValue>)>,
+	columns: &[SortSpec],
+	limit: Option<usize>,
 	channel: Channel<Vec<SearchResult>>,
 ) -> Result<usize, String> {
 	// Sort by pre-extracted values
-	items.sort_by(|a, b| {
-		let cmp = compare_sort_values(&a.2, &b.2, &direction);
+	let compare = |a: &(usize, _, Vec<SortValue>), b: &(usize, _, Vec<SortValue>)| {
+		let cmp = compare_sort_keys(&a.2, &b.2, columns);
 		if cmp == std::cmp::Ordering::Equal {
 			a.0.cmp(&b.0)  // Stable sort by original index
 		} else {
 			cmp
 		}
-	});
+	};
+
+	// Top-K: partition around the limit first so only the kept items get sorted
+	if let Some(limit) = limit {
+		if limit < items.len() {
+			if limit > 0 {
+				items.select_nth_unstable_by(limit - 1, compare);
+			}
+			items.truncate(limit);
+		}
+	}
+	items.sort_by(compare);
 
 	// Extract sorted results for streaming
 	let sorted_results: Vec<SearchResult> = items.into_iter().map(|(_, result, _)| result).collect();
@@ -212,106 +439,693 @@ async fn sort_and_stream_search_results(
 	Ok(sorted_len)
 }
 
-/// Command to sort all lines in a file by a column
-#[tauri::command]
-pub async fn sort_file_lines(
-	path: String,
-	sort_column: SortColumn,
-	file_format: FileFormat,
-	channel: Channel<Vec<JsonLine>>,
-) -> Result<usize, String> {
-	let file_path = PathBuf::from(&path);
-	let direction = sort_column.direction.clone();
-	let column_path = sort_column.column.clone();
+/// A record reduced to what sorting needs: its position in the file and its sort keys
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SortEntry {
+	pub(crate) line_id: usize,
+	pub(crate) byte_offset: u64,
+	pub(crate) keys: Vec<SortValue>,
+}
 
-	// Handle JsonArray format
-	if matches!(file_format, FileFormat::JsonArray) {
-		let content = tokio::fs::read_to_string(&file_path)
-			.await
-			.map_err(|e| format!("Failed to read file: {}", e))?;
+impl SortEntry {
+	/// Rough heap footprint, used to enforce the sort memory budget
+	fn estimated_size(&self) -> usize {
+		let strings: usize = self
+			.keys
+			.iter()
+			.map(|k| match k {
+				SortValue::String(s) => s.capacity(),
+				_ => 0,
+			})
+			.sum();
+		std::mem::size_of::<SortEntry>() + self.keys.len() * std::mem::size_of::<SortValue>() + strings
+	}
+}
+
+/// Order entries by their keys, falling back to file order for a stable sort
+fn compare_entries(a: &SortEntry, b: &SortEntry, columns: &[SortSpec]) -> std::cmp::Ordering {
+	compare_sort_keys(&a.keys, &b.keys, columns).then_with(|| a.line_id.cmp(&b.line_id))
+}
+
+/// Sorted run spilled to a temp file, removed when dropped
+struct RunFile(PathBuf);
+
+impl Drop for RunFile {
+	fn drop(&mut self) {
+		let _ = std::fs::remove_file(&self.0);
+	}
+}
+
+/// Disk-backed sorter: buffers entries up to a memory budget, then writes them
+/// out as sorted runs which are merged back on `finish`. With a limit it keeps
+/// only the first `limit` entries in a bounded heap and never touches the disk.
+struct ExternalSorter {
+	columns: Arc<Vec<SortSpec>>,
+	budget_bytes: usize,
+	buffer: Vec<SortEntry>,
+	buffered_bytes: usize,
+	runs: Vec<RunFile>,
+	limit: Option<usize>,
+	top: BinaryHeap<RankedEntry>,
+}
+
+impl ExternalSorter {
+	fn new(columns: Arc<Vec<SortSpec>>, budget_bytes: usize, limit: Option<usize>) -> Self {
+		ExternalSorter {
+			columns,
+			budget_bytes,
+			buffer: Vec::new(),
+			buffered_bytes: 0,
+			runs: Vec::new(),
+			limit,
+			top: BinaryHeap::new(),
+		}
+	}
+
+	fn push(&mut self, entry: SortEntry) -> Result<(), String> {
+		if let Some(limit) = self.limit {
+			let ranked = RankedEntry { entry, columns: self.columns.clone() };
+			if self.top.len() < limit {
+				self.top.push(ranked);
+			} else if self.top.peek().is_some_and(|worst| ranked < *worst) {
+				// Replace the current worst of the kept entries
+				self.top.pop();
+				self.top.push(ranked);
+			}
+			return Ok(());
+		}
+
+		self.buffered_bytes += entry.estimated_size();
+		self.buffer.push(entry);
+		if self.buffered_bytes >= self.budget_bytes {
+			self.spill()?;
+		}
+		Ok(())
+	}
+
+	fn sort_buffer(&mut self) {
+		let columns = self.columns.clone();
+		self.buffer.sort_by(|a, b| compare_entries(a, b, &columns));
+	}
+
+	fn spill(&mut self) -> Result<(), String> {
+		if self.buffer.is_empty() {
+			return Ok(());
+		}
+		self.sort_buffer();
+
+		let run = RunFile(std::env::temp_dir().join(format!("jsonl-viewer-sort-{}.run", Uuid::new_v4())));
+		let file = std::fs::File::create(&run.0).map_err(|e| format!("Failed to create sort run: {}", e))?;
+		let mut writer = BufWriter::new(file);
+		for entry in self.buffer.drain(..) {
+			serde_json::to_writer(&mut writer, &entry).map_err(|e| format!("Failed to write sort run: {}", e))?;
+			writer.write_all(b"\n").map_err(|e| format!("Failed to write sort run: {}", e))?;
+		}
+		writer.flush().map_err(|e| format!("Failed to write sort run: {}", e))?;
+
+		self.runs.push(run);
+		self.buffered_bytes = 0;
+		Ok(())
+	}
+
+	/// Produce every entry in sorted order, merging spilled runs if there are any
+	fn finish(mut self) -> Result<Box<dyn Iterator<Item = Result<SortEntry, String>>>, String> {
+		if self.limit.is_some() {
+			let top = std::mem::take(&mut self.top);
+			return Ok(Box::new(top.into_sorted_vec().into_iter().map(|ranked| Ok(ranked.entry))));
+		}
+		if self.runs.is_empty() {
+			self.sort_buffer();
+			return Ok(Box::new(self.buffer.into_iter().map(Ok)));
+		}
+
+		self.spill()?;
+		Ok(Box::new(RunMerger::new(self.runs, self.columns)?))
+	}
+}
+
+/// Entry ordered by its sort keys, so the max-heap of a top-K sort keeps the worst on top
+struct RankedEntry {
+	entry: SortEntry,
+	columns: Arc<Vec<SortSpec>>,
+}
+
+impl PartialEq for RankedEntry {
+	fn eq(&self, other: &Self) -> bool {
+		self.cmp(other) == std::cmp::Ordering::Equal
+	}
+}
+
+impl Eq for RankedEntry {}
+
+impl PartialOrd for RankedEntry {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for RankedEntry {
+	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+		compare_entries(&self.entry, &other.entry, &self.columns)
+	}
+}
+
+/// Head of one sorted run inside the merge heap
+struct MergeHead {
+	entry: SortEntry,
+	run: usize,
+	columns: Arc<Vec<SortSpec>>,
+}
+
+impl PartialEq for MergeHead {
+	fn eq(&self, other: &Self) -> bool {
+		self.cmp(other) == std::cmp::Ordering::Equal
+	}
+}
+
+impl Eq for MergeHead {}
+
+impl PartialOrd for MergeHead {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for MergeHead {
+	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+		// Reversed: BinaryHeap is a max-heap and we want the smallest entry first
+		compare_entries(&other.entry, &self.entry, &self.columns)
+	}
+}
+
+/// K-way merge over the sorted runs of an `ExternalSorter`
+struct RunMerger {
+	readers: Vec<std::io::Lines<BufReader<std::fs::File>>>,
+	heap: BinaryHeap<MergeHead>,
+	columns: Arc<Vec<SortSpec>>,
+	_runs: Vec<RunFile>,
+}
+
+impl RunMerger {
+	fn new(runs: Vec<RunFile>, columns: Arc<Vec<SortSpec>>) -> Result<Self, String> {
+		let mut merger = RunMerger {
+			readers: Vec::with_capacity(runs.len()),
+			heap: BinaryHeap::with_capacity(runs.len()),
+			columns,
+			_runs: Vec::new(),
+		};
+
+		for run in &runs {
+			let file = std::fs::File::open(&run.0).map_err(|e| format!("Failed to open sort run: {}", e))?;
+			merger.readers.push(BufReader::new(file).lines());
+		}
+		merger._runs = runs;
+
+		for run in 0..merger.readers.len() {
+			merger.advance(run)?;
+		}
+		Ok(merger)
+	}
+
+	/// Push the next entry of a run onto the heap, if it has one
+	fn advance(&mut self, run: usize) -> Result<(), String> {
+		if let Some(line) = self.readers[run].next() {
+			let line = line.map_err(|e| format!("Failed to read sort run: {}", e))?;
+			let entry: SortEntry = serde_json::from_str(&line).map_err(|e| format!("Corrupt sort run: {}", e))?;
+			self.heap.push(MergeHead { entry, run, columns: self.columns.clone() });
+		}
+		Ok(())
+	}
+}
+
+impl Iterator for RunMerger {
+	type Item = Result<SortEntry, String>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let head = self.heap.pop()?;
+		if let Err(e) = self.advance(head.run) {
+			return Some(Err(e));
+		}
+		Some(Ok(head.entry))
+	}
+}
+
+/// Read the raw line starting at `byte_offset`, without its line terminator
+fn read_line_at(reader: &mut BufReader<std::fs::File>, byte_offset: u64) -> Result<String, String> {
+	reader
+		.seek(SeekFrom::Start(byte_offset))
+		.map_err(|e| format!("Failed to seek in file: {}", e))?;
+	let mut line = String::new();
+	reader
+		.read_line(&mut line)
+		.map_err(|e| format!("Failed to read file: {}", e))?;
+	let trimmed_len = line.trim_end_matches(['\n', '\r']).len();
+	line.truncate(trimmed_len);
+	Ok(line)
+}
 
+/// Rows are read back by byte offset, which a gzipped file can't do: refuse it up front
+/// rather than sorting compressed bytes
+pub(crate) fn check_seekable(file_path: &Path) -> Result<(), String> {
+	if file_parser::is_gzip(file_path) {
+		return Err("Compressed files can't be sorted; decompress the file first".to_string());
+	}
+	Ok(())
+}
+
+/// Sort every record of a file, keeping only sort keys and byte offsets in memory.
+/// JSONL files spill to disk past `budget_bytes`; JSON arrays are already in memory.
+pub(crate) fn sort_entries(
+	file_path: &Path,
+	file_format: FileFormat,
+	columns: Arc<Vec<SortSpec>>,
+	budget_bytes: usize,
+	limit: Option<usize>,
+) -> Result<Box<dyn Iterator<Item = Result<SortEntry, String>>>, String> {
+	check_seekable(file_path)?;
+	if matches!(file_format, FileFormat::JsonArray) {
+		let content = std::fs::read_to_string(file_path).map_err(|e| format!("Failed to read file: {}", e))?;
 		let json: serde_json::Value = serde_json::from_str(&content)
 			.map_err(|e| format!("Failed to parse JSON: {}", e))?;
 
+		let mut sorter = ExternalSorter::new(columns.clone(), usize::MAX, limit);
 		if let Some(array) = json.as_array() {
-			// Extract sort keys once per item for better performance
-			let items: Vec<(usize, JsonLine, SortValue)> = array
-				.iter()
-				.enumerate()
-				.map(|(index, item)| {
-					let sort_val = get_nested_value(item, &column_path);
-					let sort_key = sort_val.as_ref().map(|v| to_sort_value(v)).unwrap_or(SortValue::Null);
-					(
-						index,
-						JsonLine {
-							id: index,
-							content: serde_json::to_string(item).unwrap_or_default(),
-							parsed: item.clone(),
-							byte_offset: 0,
-						},
-						sort_key,
-					)
-				})
-				.collect();
+			for (index, item) in array.iter().enumerate() {
+				sorter.push(SortEntry {
+					line_id: index,
+					byte_offset: 0,
+					keys: extract_sort_keys(item, &columns),
+				})?;
+			}
+		}
+		return sorter.finish();
+	}
+
+	let file = std::fs::File::open(file_path).map_err(|e| format!("Failed to open file: {}", e))?;
+	let mut reader = BufReader::new(file);
+	let mut sorter = ExternalSorter::new(columns.clone(), budget_bytes, limit);
+
+	let mut buf = Vec::new();
+	let mut line_num = 0;
+	let mut byte_offset: u64 = 0;
 
-			return sort_and_stream_json_lines(items, direction, channel).await;
+	loop {
+		buf.clear();
+		let read = reader
+			.read_until(b'\n', &mut buf)
+			.map_err(|e| format!("Failed to read file: {}", e))?;
+		if read == 0 {
+			break;
+		}
+		if let Ok(json) = serde_json::from_slice::<serde_json::Value>(&buf) {
+			sorter.push(SortEntry {
+				line_id: line_num,
+				byte_offset,
+				keys: extract_sort_keys(&json, &columns),
+			})?;
 		}
+		byte_offset += read as u64;
+		line_num += 1;
 	}
 
-	// Default JsonL format
-	let file = tokio::fs::File::open(&file_path)
-		.await
-		.map_err(|e| format!("Failed to open file: {}", e))?;
+	sorter.finish()
+}
 
-	let reader = tokio::io::BufReader::new(file);
-	let mut lines = reader.lines();
+/// Re-read records by `(line_id, byte_offset)` in the given order.
+/// JSONL lines are read by offset; JSON arrays are parsed once and indexed.
+pub(crate) fn read_rows<I, F>(file_path: &Path, file_format: FileFormat, rows: I, mut visit: F) -> Result<(), String>
+where
+	I: Iterator<Item = Result<(usize, u64), String>>,
+	F: FnMut(JsonLine) -> Result<(), String>,
+{
+	check_seekable(file_path)?;
+	if matches!(file_format, FileFormat::JsonArray) {
+		let content = std::fs::read_to_string(file_path).map_err(|e| format!("Failed to read file: {}", e))?;
+		let json: serde_json::Value = serde_json::from_str(&content)
+			.map_err(|e| format!("Failed to parse JSON: {}", e))?;
+		let array = json.as_array().map(|a| a.as_slice()).unwrap_or(&[]);
 
-	let mut line_num = 0;
-	let mut items: Vec<(usize, JsonLine, SortValue)> = Vec::new();
-
-	while let Ok(Some(line)) = lines.next_line().await {
-		if let Ok(json) = serde_json::from_str::<serde_json::Value>(&line) {
-			let sort_val = get_nested_value(&json, &column_path);
-			let sort_key = sort_val.as_ref().map(|v| to_sort_value(v)).unwrap_or(SortValue::Null);
-
-			items.push((
-				line_num,
-				JsonLine {
-					id: line_num,
-					content: line.clone(),
-					parsed: json,
+		for row in rows {
+			let (line_id, _) = row?;
+			if let Some(item) = array.get(line_id) {
+				visit(JsonLine {
+					id: line_id,
+					content: serde_json::to_string(item).unwrap_or_default(),
+					parsed: item.clone(),
 					byte_offset: 0,
-				},
-				sort_key,
-			));
+				})?;
+			}
 		}
-		line_num += 1;
+	} else {
+		let file = std::fs::File::open(file_path).map_err(|e| format!("Failed to open file: {}", e))?;
+		let mut reader = BufReader::new(file);
+
+		for row in rows {
+			let (line_id, byte_offset) = row?;
+			let content = read_line_at(&mut reader, byte_offset)?;
+			let parsed = serde_json::from_str(&content).map_err(|e| format!("Failed to parse line: {}", e))?;
+			visit(JsonLine {
+				id: line_id,
+				content,
+				parsed,
+				byte_offset,
+			})?;
+		}
+	}
+
+	Ok(())
+}
+
+/// `read_rows`, streamed to the frontend in chunks
+pub(crate) fn stream_rows<I>(
+	file_path: &Path,
+	file_format: FileFormat,
+	rows: I,
+	channel: &Channel<Vec<JsonLine>>,
+) -> Result<usize, String>
+where
+	I: Iterator<Item = Result<(usize, u64), String>>,
+{
+	const CHUNK_SIZE: usize = 2000;
+	let mut chunk: Vec<JsonLine> = Vec::with_capacity(CHUNK_SIZE);
+	let mut sent = 0;
+
+	read_rows(file_path, file_format, rows, |line| {
+		chunk.push(line);
+		sent += 1;
+		if chunk.len() >= CHUNK_SIZE {
+			channel.send(chunk.clone()).map_err(|e| format!("Failed to send: {}", e))?;
+			chunk.clear();
+		}
+		Ok(())
+	})?;
+
+	if !chunk.is_empty() {
+		channel.send(chunk).map_err(|e| format!("Failed to send: {}", e))?;
+	}
+
+	Ok(sent)
+}
+
+/// Command to sort all lines in a file by one or more columns.
+/// The sorted order is cached as a permutation (see `sort_view`), so sorting the
+/// same file again or flipping the direction does not rescan it. JSONL files are
+/// sorted on disk once the keys outgrow `memory_budget_mb`; with a `limit` only
+/// the first rows are kept (top-K in a single pass). Gzipped files are refused.
+#[tauri::command]
+pub async fn sort_file_lines(
+	path: String,
+	sort_columns: Vec<SortColumn>,
+	file_format: FileFormat,
+	memory_budget_mb: Option<usize>,
+	limit: Option<usize>,
+	channel: Channel<Vec<JsonLine>>,
+	views: State<'_, SortViews>,
+) -> Result<usize, String> {
+	let file_path = PathBuf::from(&path);
+
+	// Top-K without a cached full view: single pass with a bounded heap, nothing cached
+	if limit.is_some() && views.find(&file_path, &sort_columns).is_none() {
+		let specs = Arc::new(SortSpec::compile(&sort_columns)?);
+		return tokio::task::spawn_blocking(move || {
+			let entries = sort_entries(&file_path, file_format, specs, usize::MAX, limit)?;
+			stream_rows(&file_path, file_format, entries.map(|e| e.map(|e| (e.line_id, e.byte_offset))), &channel)
+		})
+		.await
+		.map_err(|e| format!("Sort task failed: {}", e))?;
 	}
 
-	sort_and_stream_json_lines(items, direction, channel).await
+	let view = views.get_or_create(file_path, file_format, sort_columns, memory_budget_mb).await?;
+	tokio::task::spawn_blocking(move || {
+		stream_rows(&view.path, view.format, view.rows(0, limit.unwrap_or(usize::MAX)), &channel)
+	})
+	.await
+	.map_err(|e| format!("Sort task failed: {}", e))?
 }
 
-/// Command to sort search results by a column
+/// Command to sort search results by one or more columns, optionally keeping the first `limit`
 #[tauri::command]
 pub async fn sort_search_results(
 	results: Vec<SearchResult>,
-	sort_column: SortColumn,
+	sort_columns: Vec<SortColumn>,
+	limit: Option<usize>,
 	channel: Channel<Vec<SearchResult>>,
 ) -> Result<usize, String> {
-	let direction = sort_column.direction.clone();
-	let column_path = sort_column.column.clone();
+	let specs = SortSpec::compile(&sort_columns)?;
 
 	// Extract sort keys once per item for better performance
-	let items: Vec<(usize, SearchResult, SortValue)> = results
+	let items: Vec<(usize, SearchResult, Vec<SortValue>)> = results
 		.into_iter()
 		.enumerate()
 		.map(|(index, result)| {
-			let parsed = serde_json::from_str::<serde_json::Value>(&result.context).ok();
-			let val = parsed.as_ref().and_then(|v| get_nested_value(v, &column_path));
-			let sort_key = val.as_ref().map(|v| to_sort_value(v)).unwrap_or(SortValue::Null);
+			let sort_key = match serde_json::from_str::<serde_json::Value>(&result.context) {
+				Ok(parsed) => extract_sort_keys(&parsed, &specs),
+				Err(_) => specs.iter().map(|_| SortValue::Null).collect(),
+			};
 			(index, result, sort_key)
 		})
 		.collect();
 
-	sort_and_stream_search_results(items, direction, channel).await
+	sort_and_stream_search_results(items, &specs, limit, channel).await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::commands::test_util::{temp_file, temp_path};
+
+	fn specs(columns: serde_json::Value) -> Arc<Vec<SortSpec>> {
+		let columns: Vec<SortColumn> = serde_json::from_value(columns).unwrap();
+		Arc::new(SortSpec::compile(&columns).unwrap())
+	}
+
+	fn by_n(direction: &str) -> Arc<Vec<SortSpec>> {
+		specs(serde_json::json!([{ "column": "/n", "direction": direction }]))
+	}
+
+	fn entry(line_id: usize, key: Option<f64>) -> SortEntry {
+		SortEntry {
+			line_id,
+			byte_offset: line_id as u64 * 10,
+			keys: vec![key.map_or(SortValue::Null, SortValue::Number)],
+		}
+	}
+
+	fn line_ids(entries: Box<dyn Iterator<Item = Result<SortEntry, String>>>) -> Vec<usize> {
+		entries.map(|e| e.unwrap().line_id).collect()
+	}
+
+	/// Keys with many duplicates, spread so that equal keys land in different runs
+	fn keys() -> Vec<Option<f64>> {
+		(0..40).map(|i| if i % 9 == 4 { None } else { Some(((i * 7) % 5) as f64) }).collect()
+	}
+
+	/// Stable reference order: key (nulls last), then file order
+	fn expected(keys: &[Option<f64>], descending: bool) -> Vec<usize> {
+		let mut ids: Vec<usize> = (0..keys.len()).collect();
+		ids.sort_by(|&a, &b| match (keys[a], keys[b]) {
+			(Some(x), Some(y)) if descending => y.total_cmp(&x),
+			(Some(x), Some(y)) => x.total_cmp(&y),
+			(None, None) => std::cmp::Ordering::Equal,
+			(None, _) => std::cmp::Ordering::Greater,
+			(_, None) => std::cmp::Ordering::Less,
+		});
+		ids
+	}
+
+	#[test]
+	fn spilled_runs_merge_in_stable_order() {
+		for direction in ["asc", "desc"] {
+			// A one-byte budget spills after every entry: one run per record
+			let mut sorter = ExternalSorter::new(by_n(direction), 1, None);
+			for (id, key) in keys().into_iter().enumerate() {
+				sorter.push(entry(id, key)).unwrap();
+			}
+			assert_eq!(sorter.runs.len(), 40);
+			let run_paths: Vec<PathBuf> = sorter.runs.iter().map(|run| run.0.clone()).collect();
+
+			let merged = sorter.finish().unwrap();
+			assert_eq!(line_ids(merged), expected(&keys(), direction == "desc"));
+			// Run files are removed once the merge is dropped
+			assert!(run_paths.iter().all(|path| !path.exists()));
+		}
+	}
+
+	#[test]
+	fn equal_keys_across_runs_keep_file_order() {
+		// Runs of 3-4 entries, all with the same key
+		let mut sorter = ExternalSorter::new(by_n("asc"), 3 * std::mem::size_of::<SortEntry>() + 1, None);
+		for id in 0..20 {
+			sorter.push(entry(id, Some(1.0))).unwrap();
+		}
+		assert!(sorter.runs.len() > 1);
+		assert_eq!(line_ids(sorter.finish().unwrap()), (0..20).collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn in_memory_and_spilled_sorts_agree() {
+		let sort = |budget| {
+			let mut sorter = ExternalSorter::new(by_n("desc"), budget, None);
+			for (id, key) in keys().into_iter().enumerate() {
+				sorter.push(entry(id, key)).unwrap();
+			}
+			line_ids(sorter.finish().unwrap())
+		};
+		assert_eq!(sort(usize::MAX), sort(200));
+	}
+
+	#[test]
+	fn sort_entries_spills_a_jsonl_file() {
+		let path = temp_path("records.jsonl");
+		let lines: Vec<String> = keys()
+			.iter()
+			.map(|key| key.map_or("{}".to_string(), |n| format!("{{\"n\":{}}}", n)))
+			.collect();
+		std::fs::write(&path, lines.join("\n")).unwrap();
+
+		let entries: Vec<SortEntry> = sort_entries(&path, FileFormat::JsonL, by_n("asc"), 64, None)
+			.unwrap()
+			.map(|e| e.unwrap())
+			.collect();
+		let ids: Vec<usize> = entries.iter().map(|e| e.line_id).collect();
+		assert_eq!(ids, expected(&keys(), false));
+
+		// Byte offsets point back at the right lines
+		let mut reader = BufReader::new(std::fs::File::open(&path).unwrap());
+		for entry in &entries {
+			assert_eq!(read_line_at(&mut reader, entry.byte_offset).unwrap(), lines[entry.line_id]);
+		}
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn natural_order_of_digit_runs() {
+		use std::cmp::Ordering::*;
+		assert_eq!(natural_cmp("item2", "item10"), Less);
+		assert_eq!(natural_cmp("item10", "item2"), Greater);
+		assert_eq!(natural_cmp("a", "a1"), Less);
+		assert_eq!(natural_cmp("v1.10", "v1.9"), Greater);
+		// Longer than any integer type
+		assert_eq!(natural_cmp("n99999999999999999999999", "n100000000000000000000000"), Less);
+	}
+
+	#[test]
+	fn natural_order_with_leading_zeros() {
+		use std::cmp::Ordering::*;
+		// Same value: the shorter spelling first, so the order stays total
+		assert_eq!(natural_cmp("a1", "a01"), Less);
+		assert_eq!(natural_cmp("a001", "a01"), Greater);
+		assert_eq!(natural_cmp("a007", "a007"), Equal);
+		// Zeros don't change the magnitude
+		assert_eq!(natural_cmp("a007", "a10"), Less);
+		assert_eq!(natural_cmp("file0010b", "file10a"), Greater);
+		assert_eq!(natural_cmp("0", "00"), Less);
+	}
+
+	#[test]
+	fn collations_order_strings() {
+		let sort = |collation: &str, locale: Option<&str>| {
+			let columns = specs(serde_json::json!([{ "column": "/s", "direction": "asc", "collation": collation, "locale": locale }]));
+			let mut values = vec!["item10", "Item2", "item1", "Zebra", "apple", "Äpfel"];
+			values.sort_by(|a, b| {
+				compare_sort_values(&SortValue::String(a.to_string()), &SortValue::String(b.to_string()), &columns[0])
+			});
+			values
+		};
+		assert_eq!(sort("Natural", None), vec!["apple", "item1", "Item2", "item10", "Zebra", "Äpfel"]);
+		assert_eq!(sort("CaseSensitive", None), vec!["Item2", "Zebra", "apple", "item1", "item10", "Äpfel"]);
+		assert_eq!(sort("CaseInsensitive", None), vec!["apple", "item1", "item10", "Item2", "Zebra", "Äpfel"]);
+		// Locale collation sorts the umlaut with its base letter (äpfel < apple)
+		assert_eq!(sort("Locale", Some("de")), vec!["Äpfel", "apple", "item1", "item10", "Item2", "Zebra"]);
+	}
+
+	fn utc(rfc3339: &str) -> i64 {
+		chrono::DateTime::parse_from_rfc3339(rfc3339).unwrap().timestamp_micros()
+	}
+
+	#[test]
+	fn dates_with_offsets() {
+		assert_eq!(parse_date_string("2024-01-15T10:30:00+02:00"), Ok(utc("2024-01-15T08:30:00Z")));
+		// RFC 2822
+		assert_eq!(parse_date_string("Mon, 15 Jan 2024 10:30:00 +0200"), Ok(utc("2024-01-15T08:30:00Z")));
+		assert_eq!(parse_date_string("Mon, 15 Jan 2024 10:30:00 GMT"), Ok(utc("2024-01-15T10:30:00Z")));
+		// Common log format
+		assert_eq!(parse_date_string("10/Oct/2000:13:55:36 -0700"), Ok(utc("2000-10-10T20:55:36Z")));
+		assert_eq!(parse_date_string("2024-01-15 10:30:00.5 +0100"), Ok(utc("2024-01-15T09:30:00.5Z")));
+	}
+
+	#[test]
+	fn naive_dates_are_utc() {
+		assert_eq!(parse_date_string("2024-01-15T10:30:00.123"), Ok(utc("2024-01-15T10:30:00.123Z")));
+		assert_eq!(parse_date_string("2024-01-15 10:30"), Ok(utc("2024-01-15T10:30:00Z")));
+		assert_eq!(parse_date_string("2024/01/15 10:30:00"), Ok(utc("2024-01-15T10:30:00Z")));
+		assert_eq!(parse_date_string("10/Oct/2000:13:55:36"), Ok(utc("2000-10-10T13:55:36Z")));
+		assert_eq!(parse_date_string(" 2024-01-15 "), Ok(utc("2024-01-15T00:00:00Z")));
+		assert_eq!(parse_date_string("2024/01/15"), Ok(utc("2024-01-15T00:00:00Z")));
+	}
+
+	#[test]
+	fn non_dates_are_rejected() {
+		for s in ["", "hello world", "12345", "2024", "version 1.2.3.4", "2024-13-45", "31/Foo/2000:13:55:36"] {
+			assert_eq!(parse_date_string(s), Err(()), "{:?}", s);
+		}
+	}
+
+	#[test]
+	fn epoch_units_from_magnitude() {
+		let micros = 1_700_000_000_000_000;
+		assert_eq!(epoch_to_micros(1_700_000_000.0), Some(micros));
+		assert_eq!(epoch_to_micros(1_700_000_000_000.0), Some(micros));
+		assert_eq!(epoch_to_micros(1_700_000_000_000_000.0), Some(micros));
+		assert_eq!(epoch_to_micros(1_700_000_000_000_000_000.0), Some(micros));
+		assert_eq!(epoch_to_micros(1.5), Some(1_500_000));
+		assert_eq!(epoch_to_micros(-86_400.0), Some(-86_400_000_000));
+		assert_eq!(epoch_to_micros(f64::NAN), None);
+		assert_eq!(epoch_to_micros(f64::INFINITY), None);
+
+		assert_eq!(parse_timestamp(&serde_json::json!("1700000000")), Some(micros));
+		assert_eq!(parse_timestamp(&serde_json::json!(1_700_000_000_000u64)), Some(micros));
+		assert_eq!(parse_timestamp(&serde_json::json!(true)), None);
+	}
+
+	#[test]
+	fn top_k_matches_the_head_of_a_full_sort() {
+		for direction in ["asc", "desc"] {
+			let full = expected(&keys(), direction == "desc");
+			for k in [0, 1, 5, 13, 39, 40, 41, 1000] {
+				// A tiny budget would spill a full sort; the heap never does
+				let mut sorter = ExternalSorter::new(by_n(direction), 1, Some(k));
+				for (id, key) in keys().into_iter().enumerate() {
+					sorter.push(entry(id, key)).unwrap();
+				}
+				assert!(sorter.runs.is_empty());
+				assert!(sorter.top.len() <= k);
+
+				let top = line_ids(sorter.finish().unwrap());
+				assert_eq!(top, full[..k.min(full.len())], "{} k={}", direction, k);
+			}
+		}
+	}
+
+	#[test]
+	fn top_k_of_a_json_array() {
+		let path = temp_file("records.json", r#"[{"n":3},{"n":1},{"n":2},{"n":1},{}]"#);
+
+		let top = |k| line_ids(sort_entries(&path, FileFormat::JsonArray, by_n("asc"), usize::MAX, Some(k)).unwrap());
+		assert_eq!(top(2), vec![1, 3]);
+		assert_eq!(top(10), vec![1, 3, 2, 0, 4]);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn gzipped_files_are_refused() {
+		// Never opened: the name alone decides
+		let path = temp_path("records.jsonl.gz");
+		let expected = "Compressed files can't be sorted; decompress the file first";
+
+		let error = sort_entries(&path, FileFormat::JsonL, by_n("asc"), usize::MAX, None).err().unwrap();
+		assert_eq!(error, expected);
+		let rows = std::iter::once(Ok((0, 0)));
+		assert_eq!(read_rows(&path, FileFormat::JsonArray, rows, |_| Ok(())).unwrap_err(), expected);
+	}
 }
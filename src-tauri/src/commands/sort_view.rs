@@ -0,0 +1,402 @@
+// A sorted view is the sort order of a file kept as a permutation of line ids
+// and byte offsets. Pages are read back from the original file on demand, so a
+// sort never writes a second copy of the data.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tauri::State;
+use uuid::Uuid;
+use crate::commands::file_parser::{JsonLine, FileFormat};
+use crate::commands::sort::{
+	compare_sort_keys, read_rows, sort_entries, SortColumn, SortSpec, SortValue, DEFAULT_SORT_MEMORY_MB,
+};
+
+/// Sorted views kept around at once; the oldest is dropped beyond this
+const MAX_SORTED_VIEWS: usize = 8;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SortedViewInfo {
+	pub view_id: String,
+	pub total_rows: usize,
+}
+
+/// Size and modification time, to notice when a file changes under a view
+#[derive(Clone, Copy, PartialEq)]
+struct FileStamp {
+	len: u64,
+	modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+	fn of(path: &Path) -> Result<FileStamp, String> {
+		let meta = std::fs::metadata(path).map_err(|e| format!("Failed to read file metadata: {}", e))?;
+		Ok(FileStamp {
+			len: meta.len(),
+			modified: meta.modified().ok(),
+		})
+	}
+}
+
+pub struct SortedView {
+	pub id: String,
+	pub path: PathBuf,
+	pub format: FileFormat,
+	columns: Vec<SortColumn>,
+	stamp: FileStamp,
+	pub line_ids: Vec<usize>,
+	pub byte_offsets: Vec<u64>,
+	/// Single-column views only: positions where a new key value starts, and where
+	/// the trailing null/missing keys begin. Enough to flip the direction exactly.
+	group_starts: Option<Vec<usize>>,
+	null_start: usize,
+}
+
+impl SortedView {
+	/// Sort the file once, keeping the permutation (blocking)
+	fn build(
+		path: PathBuf,
+		format: FileFormat,
+		columns: Vec<SortColumn>,
+		budget_bytes: usize,
+	) -> Result<SortedView, String> {
+		let stamp = FileStamp::of(&path)?;
+		let specs = Arc::new(SortSpec::compile(&columns)?);
+		let track_groups = specs.len() == 1;
+
+		let mut line_ids = Vec::new();
+		let mut byte_offsets = Vec::new();
+		let mut group_starts = Vec::new();
+		let mut null_start = None;
+		let mut previous: Option<Vec<SortValue>> = None;
+
+		for entry in sort_entries(&path, format, specs.clone(), budget_bytes, None)? {
+			let entry = entry?;
+			if track_groups {
+				let position = line_ids.len();
+				let new_group = previous
+					.as_ref()
+					.is_none_or(|prev| compare_sort_keys(prev, &entry.keys, &specs) != std::cmp::Ordering::Equal);
+				if new_group {
+					group_starts.push(position);
+				}
+				if null_start.is_none() && matches!(entry.keys[0], SortValue::Null) {
+					null_start = Some(position);
+				}
+				previous = Some(entry.keys);
+			}
+			line_ids.push(entry.line_id);
+			byte_offsets.push(entry.byte_offset);
+		}
+
+		let total = line_ids.len();
+		Ok(SortedView {
+			id: Uuid::new_v4().to_string(),
+			path,
+			format,
+			columns,
+			stamp,
+			line_ids,
+			byte_offsets,
+			group_starts: track_groups.then_some(group_starts),
+			null_start: null_start.unwrap_or(total),
+		})
+	}
+
+	/// The same view in the opposite direction, without rescanning the file.
+	/// Key groups are reversed while rows inside a group keep file order and
+	/// nulls stay last, which is exactly what a fresh sort would produce.
+	fn reversed(&self) -> Option<SortedView> {
+		let group_starts = self.group_starts.as_ref()?;
+		let mut column = self.columns[0].clone();
+		column.direction = if column.direction == "desc" { "asc".to_string() } else { "desc".to_string() };
+
+		let mut line_ids = Vec::with_capacity(self.line_ids.len());
+		let mut byte_offsets = Vec::with_capacity(self.byte_offsets.len());
+		let mut new_starts = Vec::with_capacity(group_starts.len());
+
+		let value_groups: Vec<usize> = group_starts.iter().copied().filter(|&s| s < self.null_start).collect();
+		let mut end = self.null_start;
+		for &start in value_groups.iter().rev() {
+			new_starts.push(line_ids.len());
+			line_ids.extend_from_slice(&self.line_ids[start..end]);
+			byte_offsets.extend_from_slice(&self.byte_offsets[start..end]);
+			end = start;
+		}
+		if self.null_start < self.line_ids.len() {
+			new_starts.push(self.null_start);
+			line_ids.extend_from_slice(&self.line_ids[self.null_start..]);
+			byte_offsets.extend_from_slice(&self.byte_offsets[self.null_start..]);
+		}
+
+		Some(SortedView {
+			id: Uuid::new_v4().to_string(),
+			path: self.path.clone(),
+			format: self.format,
+			columns: vec![column],
+			stamp: self.stamp,
+			line_ids,
+			byte_offsets,
+			group_starts: Some(new_starts),
+			null_start: self.null_start,
+		})
+	}
+
+	/// `(line_id, byte_offset)` pairs for a page of the view
+	pub fn rows(&self, offset: usize, limit: usize) -> impl Iterator<Item = Result<(usize, u64), String>> + '_ {
+		self.line_ids
+			.iter()
+			.zip(self.byte_offsets.iter())
+			.skip(offset)
+			.take(limit)
+			.map(|(id, off)| Ok((*id, *off)))
+	}
+
+	/// `(line_id, byte_offset)` pairs for `line_ids`, in the order given. Ids the
+	/// view doesn't contain (unparseable lines) are left out.
+	pub fn rows_for(&self, line_ids: &[usize]) -> Vec<(usize, u64)> {
+		let offsets: HashMap<usize, u64> =
+			self.line_ids.iter().copied().zip(self.byte_offsets.iter().copied()).collect();
+		line_ids.iter().filter_map(|&id| offsets.get(&id).map(|&offset| (id, offset))).collect()
+	}
+
+	fn is_stale(&self) -> bool {
+		!FileStamp::of(&self.path).is_ok_and(|stamp| stamp == self.stamp)
+	}
+
+	/// True when `columns` is this view's sort with every direction flipped
+	fn is_reverse_of(&self, columns: &[SortColumn]) -> bool {
+		self.group_starts.is_some()
+			&& columns.len() == 1
+			&& columns[0].direction != self.columns[0].direction
+			&& SortColumn { direction: self.columns[0].direction.clone(), ..columns[0].clone() } == self.columns[0]
+	}
+}
+
+/// Sorted views shared between commands (managed Tauri state)
+#[derive(Default)]
+pub struct SortViews {
+	views: Mutex<Vec<Arc<SortedView>>>,
+}
+
+impl SortViews {
+	fn insert(&self, view: Arc<SortedView>) {
+		let mut views = self.views.lock().unwrap();
+		views.push(view);
+		if views.len() > MAX_SORTED_VIEWS {
+			views.remove(0);
+		}
+	}
+
+	/// Cached view of `path` sorted by `columns`, reversing an opposite-direction
+	/// view when possible. Views of files that changed since are dropped.
+	pub fn find(&self, path: &Path, columns: &[SortColumn]) -> Option<Arc<SortedView>> {
+		let reversible = {
+			let mut views = self.views.lock().unwrap();
+			views.retain(|v| v.path != path || !v.is_stale());
+
+			if let Some(view) = views.iter().find(|v| v.path == path && v.columns == columns) {
+				return Some(view.clone());
+			}
+			views.iter().find(|v| v.path == path && v.is_reverse_of(columns)).cloned()
+		}?;
+
+		let view = Arc::new(reversible.reversed()?);
+		self.insert(view.clone());
+		Some(view)
+	}
+
+	/// Cached view, or sort the file now and cache the result
+	pub async fn get_or_create(
+		&self,
+		path: PathBuf,
+		format: FileFormat,
+		columns: Vec<SortColumn>,
+		memory_budget_mb: Option<usize>,
+	) -> Result<Arc<SortedView>, String> {
+		if let Some(view) = self.find(&path, &columns) {
+			return Ok(view);
+		}
+
+		let budget_bytes = memory_budget_mb.unwrap_or(DEFAULT_SORT_MEMORY_MB) * 1024 * 1024;
+		let view = tokio::task::spawn_blocking(move || SortedView::build(path, format, columns, budget_bytes))
+			.await
+			.map_err(|e| format!("Sort task failed: {}", e))??;
+
+		let view = Arc::new(view);
+		self.insert(view.clone());
+		Ok(view)
+	}
+
+	/// View by id, refusing views whose file changed since they were built
+	pub fn get(&self, view_id: &str) -> Result<Arc<SortedView>, String> {
+		let view = self
+			.views
+			.lock()
+			.unwrap()
+			.iter()
+			.find(|v| v.id == view_id)
+			.cloned()
+			.ok_or_else(|| format!("Sorted view not found: {}", view_id))?;
+		if view.is_stale() {
+			self.release(view_id);
+			return Err("File changed since it was sorted, sort it again".to_string());
+		}
+		Ok(view)
+	}
+
+	fn release(&self, view_id: &str) {
+		self.views.lock().unwrap().retain(|v| v.id != view_id);
+	}
+}
+
+/// Command to sort a file into a sorted view without streaming it; rows are
+/// then fetched page by page with `get_sorted_page`. Gzipped files are refused,
+/// since rows are read back by byte offset.
+#[tauri::command]
+pub async fn create_sorted_view(
+	path: String,
+	sort_columns: Vec<SortColumn>,
+	file_format: FileFormat,
+	memory_budget_mb: Option<usize>,
+	views: State<'_, SortViews>,
+) -> Result<SortedViewInfo, String> {
+	let view = views
+		.get_or_create(PathBuf::from(path), file_format, sort_columns, memory_budget_mb)
+		.await?;
+	Ok(SortedViewInfo {
+		view_id: view.id.clone(),
+		total_rows: view.line_ids.len(),
+	})
+}
+
+/// Command to read one page of a sorted view from the original file
+#[tauri::command]
+pub async fn get_sorted_page(
+	view_id: String,
+	offset: usize,
+	limit: usize,
+	views: State<'_, SortViews>,
+) -> Result<Vec<JsonLine>, String> {
+	let view = views.get(&view_id)?;
+	tokio::task::spawn_blocking(move || {
+		let mut page = Vec::with_capacity(limit.min(view.line_ids.len()));
+		read_rows(&view.path, view.format, view.rows(offset, limit), |line| {
+			page.push(line);
+			Ok(())
+		})?;
+		Ok(page)
+	})
+	.await
+	.map_err(|e| format!("Sort task failed: {}", e))?
+}
+
+/// Command to list the line ids of a sorted view in order, e.g. to export some
+/// rows in sorted order through `ExportFilter.line_ids` and `ExportFilter.view_id`
+#[tauri::command]
+pub async fn get_sorted_line_ids(
+	view_id: String,
+	offset: Option<usize>,
+	limit: Option<usize>,
+	views: State<'_, SortViews>,
+) -> Result<Vec<usize>, String> {
+	let view = views.get(&view_id)?;
+	Ok(view
+		.line_ids
+		.iter()
+		.skip(offset.unwrap_or(0))
+		.take(limit.unwrap_or(usize::MAX))
+		.copied()
+		.collect())
+}
+
+/// Command to put line ids (e.g. search hits) in the order of a sorted view.
+/// Ids the view doesn't contain (unparseable lines) go last.
+#[tauri::command]
+pub async fn order_line_ids(
+	view_id: String,
+	mut line_ids: Vec<usize>,
+	views: State<'_, SortViews>,
+) -> Result<Vec<usize>, String> {
+	let view = views.get(&view_id)?;
+
+	let max_id = view.line_ids.iter().copied().max().unwrap_or(0);
+	let mut rank = vec![usize::MAX; max_id + 1];
+	for (position, &id) in view.line_ids.iter().enumerate() {
+		rank[id] = position;
+	}
+
+	line_ids.sort_by_key(|&id| (rank.get(id).copied().unwrap_or(usize::MAX), id));
+	Ok(line_ids)
+}
+
+/// Command to drop a sorted view once the frontend no longer needs it
+#[tauri::command]
+pub async fn release_sorted_view(view_id: String, views: State<'_, SortViews>) -> Result<(), String> {
+	views.release(&view_id);
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::commands::test_util::temp_file;
+
+	fn write_file(lines: &[&str]) -> PathBuf {
+		temp_file("records.jsonl", lines.join("\n"))
+	}
+
+	fn by_k(direction: &str) -> Vec<SortColumn> {
+		vec![serde_json::from_value(serde_json::json!({ "column": "/k", "direction": direction })).unwrap()]
+	}
+
+	fn build(path: &Path, direction: &str) -> SortedView {
+		SortedView::build(path.to_path_buf(), FileFormat::JsonL, by_k(direction), 1024 * 1024).unwrap()
+	}
+
+	#[test]
+	fn reversed_keeps_equal_keys_in_file_order() {
+		let path = write_file(&[r#"{"k":2}"#, r#"{"k":1}"#, r#"{"k":2}"#, r#"{}"#, r#"{"k":1}"#, r#"{"k":null}"#, r#"{"k":2}"#]);
+		let asc = build(&path, "asc");
+		assert_eq!(asc.line_ids, vec![1, 4, 0, 2, 6, 3, 5]);
+
+		let desc = asc.reversed().unwrap();
+		// Groups flip, rows inside a group stay in file order, nulls stay last
+		assert_eq!(desc.line_ids, vec![0, 2, 6, 1, 4, 3, 5]);
+		assert_eq!(desc.line_ids, build(&path, "desc").line_ids);
+		assert!(desc.is_reverse_of(&by_k("asc")));
+
+		// Back again gives the original order
+		assert_eq!(desc.reversed().unwrap().line_ids, asc.line_ids);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn reversed_byte_offsets_follow_line_ids() {
+		let path = write_file(&[r#"{"k":"b"}"#, r#"{"k":"a"}"#, r#"{"k":"c"}"#]);
+		let desc = build(&path, "asc").reversed().unwrap();
+		let rows: Vec<(usize, u64)> = desc.rows(0, usize::MAX).map(|r| r.unwrap()).collect();
+		assert_eq!(rows, vec![(2, 20), (0, 0), (1, 10)]);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn stale_views_are_detected_and_dropped() {
+		let path = write_file(&[r#"{"k":2}"#, r#"{"k":1}"#]);
+		let views = SortViews::default();
+		let view = tokio::runtime::Runtime::new()
+			.unwrap()
+			.block_on(views.get_or_create(path.clone(), FileFormat::JsonL, by_k("asc"), None))
+			.unwrap();
+		assert!(!view.is_stale());
+		assert!(views.get(&view.id).is_ok());
+
+		std::fs::write(&path, "{\"k\":2}\n{\"k\":1}\n{\"k\":0}").unwrap();
+		assert!(view.is_stale());
+		assert!(views.get(&view.id).is_err());
+		assert!(views.find(&path, &by_k("asc")).is_none());
+		std::fs::remove_file(&path).unwrap();
+	}
+}
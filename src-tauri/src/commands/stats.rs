@@ -0,0 +1,733 @@
+// Column statistics computed in one pass over the whole file: per field path the
+// type distribution, null/missing rates, distinct counts, numeric and length summaries.
+
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use crate::commands::field_path;
+use crate::commands::file_parser::FileFormat;
+use crate::commands::search::{scan_matching_records, SearchQuery};
+
+/// Distinct values counted exactly before switching to a HyperLogLog estimate
+const DEFAULT_EXACT_DISTINCT_LIMIT: usize = 10_000;
+const DEFAULT_TOP_N: usize = 10;
+const DEFAULT_PERCENTILES: [f64; 5] = [25.0, 50.0, 75.0, 90.0, 99.0];
+/// Numbers kept per field for percentiles; beyond this they come from a uniform sample
+const PERCENTILE_SAMPLE_SIZE: usize = 20_000;
+/// Fields described at most when they are discovered from the records
+const MAX_FIELDS: usize = 1_000;
+/// Exact distinct hashes, value counters and percentile samples kept across all
+/// fields together. Past it, fields fall back to estimates instead of growing.
+const MAX_TOTAL_COUNTERS: usize = 2_000_000;
+/// 2^12 registers, about 1.6% standard error
+const HLL_PRECISION: u32 = 12;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct StatsOptions {
+    /// Field paths (JSON Pointers) to describe. When unset every field found is
+    /// described; objects are described through their fields, arrays as values.
+    #[serde(default)]
+    pub fields: Option<Vec<String>>,
+    #[serde(default)]
+    pub exact_distinct_limit: Option<usize>,
+    /// Most frequent values returned per field
+    #[serde(default)]
+    pub top_n: Option<usize>,
+    /// Percentiles (0-100) of numeric fields
+    #[serde(default)]
+    pub percentiles: Option<Vec<f64>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TypeCounts {
+    pub null: usize,
+    pub boolean: usize,
+    pub number: usize,
+    pub string: usize,
+    pub array: usize,
+    pub object: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Percentile {
+    pub percentile: f64,
+    pub value: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NumericStats {
+    pub min: f64,
+    pub max: f64,
+    pub sum: f64,
+    pub mean: f64,
+    /// Population standard deviation
+    pub stddev: f64,
+    pub percentiles: Vec<Percentile>,
+    /// False when the percentiles come from a sample of the values
+    pub percentiles_exact: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LengthStats {
+    /// In characters
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ValueCount {
+    /// Strings as is, other values as JSON
+    pub value: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FieldStats {
+    pub path: String,
+    /// Records with the field, null included
+    pub present: usize,
+    pub missing: usize,
+    pub nulls: usize,
+    /// Shares of all records described
+    pub null_rate: f64,
+    pub missing_rate: f64,
+    pub types: TypeCounts,
+    /// Distinct non-null values
+    pub distinct: usize,
+    /// False when `distinct` is a HyperLogLog estimate
+    pub distinct_exact: bool,
+    pub numeric: Option<NumericStats>,
+    pub string_lengths: Option<LengthStats>,
+    /// Most frequent non-null values, most frequent first
+    pub top_values: Vec<ValueCount>,
+    /// False when there were too many distinct values to count them all; the
+    /// counts are then lower bounds
+    pub top_values_exact: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StatsReport {
+    /// Records described (the matching ones when a query was given)
+    pub records: usize,
+    /// Lines skipped because they aren't valid JSON
+    pub invalid_lines: usize,
+    pub fields: Vec<FieldStats>,
+    /// More than `MAX_FIELDS` fields were found and the rest were left out
+    pub fields_truncated: bool,
+}
+
+/// Command to compute column statistics over the whole file, or over the records
+/// matching `query`, reading the file once
+#[tauri::command]
+pub async fn compute_stats(
+    path: String,
+    file_format: FileFormat,
+    options: Option<StatsOptions>,
+    query: Option<SearchQuery>,
+) -> Result<StatsReport, String> {
+    let file_path = PathBuf::from(&path);
+    let options = options.unwrap_or_default();
+
+    let percentiles = options.percentiles.clone().unwrap_or_else(|| DEFAULT_PERCENTILES.to_vec());
+    if let Some(p) = percentiles.iter().find(|p| !(0.0..=100.0).contains(*p)) {
+        return Err(format!("Percentile {} is outside 0-100", p));
+    }
+
+    let mut collector = StatsCollector::new(&options);
+    collector.invalid_lines = scan_matching_records(&file_path, &file_format, query.as_ref(), |_, json| {
+        collector.add_record(json);
+        Ok(())
+    })
+    .await?;
+
+    Ok(collector.finish(&percentiles, options.top_n.unwrap_or(DEFAULT_TOP_N)))
+}
+
+/// Accumulates statistics record by record
+struct StatsCollector {
+    /// Explicit fields, or None to discover them
+    fields: Option<Vec<String>>,
+    index: HashMap<String, usize>,
+    accumulators: Vec<(String, FieldAccumulator)>,
+    exact_distinct_limit: usize,
+    /// What is left of `MAX_TOTAL_COUNTERS`
+    counters_left: usize,
+    records: usize,
+    invalid_lines: usize,
+    fields_truncated: bool,
+}
+
+impl StatsCollector {
+    fn new(options: &StatsOptions) -> Self {
+        let exact_distinct_limit = options.exact_distinct_limit.unwrap_or(DEFAULT_EXACT_DISTINCT_LIMIT);
+        let mut collector = StatsCollector {
+            fields: options.fields.clone(),
+            index: HashMap::new(),
+            accumulators: Vec::new(),
+            exact_distinct_limit,
+            counters_left: MAX_TOTAL_COUNTERS,
+            records: 0,
+            invalid_lines: 0,
+            fields_truncated: false,
+        };
+        // Requested fields are reported in the order asked, even when never present
+        for path in options.fields.iter().flatten() {
+            collector.position(path);
+        }
+        collector
+    }
+
+    fn add_record(&mut self, json: &serde_json::Value) {
+        self.records += 1;
+        match self.fields.take() {
+            Some(fields) => {
+                for path in &fields {
+                    if let Some(value) = field_path::resolve(json, path) {
+                        self.add_value(path, value);
+                    }
+                }
+                self.fields = Some(fields);
+            }
+            None => self.add_leaves(json, ""),
+        }
+    }
+
+    fn add_leaves(&mut self, value: &serde_json::Value, prefix: &str) {
+        match value {
+            serde_json::Value::Object(map) if !map.is_empty() => {
+                for (key, child) in map {
+                    self.add_leaves(child, &field_path::child(prefix, key));
+                }
+            }
+            // A record that isn't an object is described as a whole
+            _ => self.add_value(prefix, value),
+        }
+    }
+
+    fn add_value(&mut self, path: &str, value: &serde_json::Value) {
+        if let Some(position) = self.position(path) {
+            self.accumulators[position].1.add(value, &mut self.counters_left);
+        }
+    }
+
+    /// Position of the field's accumulator, created on first sight
+    fn position(&mut self, path: &str) -> Option<usize> {
+        if let Some(&position) = self.index.get(path) {
+            return Some(position);
+        }
+        if self.accumulators.len() >= MAX_FIELDS {
+            self.fields_truncated = true;
+            return None;
+        }
+        let position = self.accumulators.len();
+        self.index.insert(path.to_string(), position);
+        self.accumulators
+            .push((path.to_string(), FieldAccumulator::new(self.exact_distinct_limit)));
+        Some(position)
+    }
+
+    fn finish(self, percentiles: &[f64], top_n: usize) -> StatsReport {
+        let records = self.records;
+        let rate = |count: usize| if records == 0 { 0.0 } else { count as f64 / records as f64 };
+
+        let fields = self
+            .accumulators
+            .into_iter()
+            .map(|(path, acc)| {
+                let missing = records.saturating_sub(acc.present);
+                let (distinct, distinct_exact) = acc.distinct.count();
+                let (top_values, top_values_exact) = acc.top.most_frequent(top_n);
+                FieldStats {
+                    path,
+                    present: acc.present,
+                    missing,
+                    nulls: acc.types.null,
+                    null_rate: rate(acc.types.null),
+                    missing_rate: rate(missing),
+                    distinct,
+                    distinct_exact,
+                    numeric: acc.numbers.finish(percentiles),
+                    string_lengths: acc.lengths.map(|(min, max, total)| LengthStats {
+                        min,
+                        max,
+                        mean: total as f64 / acc.types.string as f64,
+                    }),
+                    top_values,
+                    top_values_exact,
+                    types: acc.types,
+                }
+            })
+            .collect();
+
+        StatsReport {
+            records,
+            invalid_lines: self.invalid_lines,
+            fields,
+            fields_truncated: self.fields_truncated,
+        }
+    }
+}
+
+struct FieldAccumulator {
+    present: usize,
+    types: TypeCounts,
+    distinct: Distinct,
+    top: TopValues,
+    numbers: NumberAccumulator,
+    /// min, max and total string length
+    lengths: Option<(usize, usize, u64)>,
+}
+
+impl FieldAccumulator {
+    fn new(exact_distinct_limit: usize) -> Self {
+        FieldAccumulator {
+            present: 0,
+            types: TypeCounts::default(),
+            distinct: Distinct::Exact(HashSet::new(), exact_distinct_limit),
+            top: TopValues::new(exact_distinct_limit),
+            numbers: NumberAccumulator::default(),
+            lengths: None,
+        }
+    }
+
+    /// Add one value; new counters are taken from `counters_left`
+    fn add(&mut self, value: &serde_json::Value, counters_left: &mut usize) {
+        self.present += 1;
+        match value {
+            serde_json::Value::Null => {
+                self.types.null += 1;
+                return;
+            }
+            serde_json::Value::Bool(_) => self.types.boolean += 1,
+            serde_json::Value::Number(n) => {
+                self.types.number += 1;
+                if let Some(n) = n.as_f64() {
+                    self.numbers.add(n, counters_left);
+                }
+            }
+            serde_json::Value::String(s) => {
+                self.types.string += 1;
+                let len = s.chars().count();
+                self.lengths = Some(match self.lengths {
+                    Some((min, max, total)) => (min.min(len), max.max(len), total + len as u64),
+                    None => (len, len, len as u64),
+                });
+            }
+            serde_json::Value::Array(_) => self.types.array += 1,
+            serde_json::Value::Object(_) => self.types.object += 1,
+        }
+
+        let key = match value {
+            serde_json::Value::String(s) => std::borrow::Cow::Borrowed(s.as_str()),
+            v => std::borrow::Cow::Owned(v.to_string()),
+        };
+        self.distinct.add(&key, counters_left);
+        self.top.add(&key, counters_left);
+    }
+}
+
+/// Distinct values: exact (hashes) up to a limit, or until the counters run out,
+/// then a HyperLogLog estimate
+enum Distinct {
+    Exact(HashSet<u64>, usize),
+    Estimated(Box<HyperLogLog>),
+}
+
+impl Distinct {
+    fn add(&mut self, key: &str, counters_left: &mut usize) {
+        let hash = hash_key(key);
+        match self {
+            Distinct::Exact(hashes, limit) => {
+                if hashes.contains(&hash) {
+                    return;
+                }
+                if hashes.len() < *limit && *counters_left > 0 {
+                    hashes.insert(hash);
+                    *counters_left -= 1;
+                    return;
+                }
+                let mut hll = Box::new(HyperLogLog::new());
+                hashes.iter().for_each(|&h| hll.add(h));
+                hll.add(hash);
+                *counters_left += hashes.len();
+                *self = Distinct::Estimated(hll);
+            }
+            Distinct::Estimated(hll) => hll.add(hash),
+        }
+    }
+
+    fn count(&self) -> (usize, bool) {
+        match self {
+            Distinct::Exact(hashes, _) => (hashes.len(), true),
+            Distinct::Estimated(hll) => (hll.estimate(), false),
+        }
+    }
+}
+
+fn hash_key(key: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    fn new() -> Self {
+        HyperLogLog {
+            registers: vec![0; 1 << HLL_PRECISION],
+        }
+    }
+
+    fn add(&mut self, hash: u64) {
+        let index = (hash >> (64 - HLL_PRECISION)) as usize;
+        // Position of the first set bit in the remaining bits (a sentinel bounds it)
+        let rest = (hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+        }
+    }
+
+    fn estimate(&self) -> usize {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+
+        // Small range correction (linear counting)
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as usize
+        } else {
+            estimate.round() as usize
+        }
+    }
+}
+
+/// Value counts, exact while they fit in `capacity` counters (and the shared ones
+/// last). Beyond that the Misra-Gries summary keeps the frequent values with
+/// lower-bound counts.
+struct TopValues {
+    counts: HashMap<String, usize>,
+    capacity: usize,
+    exact: bool,
+}
+
+impl TopValues {
+    fn new(capacity: usize) -> Self {
+        TopValues {
+            counts: HashMap::new(),
+            capacity: capacity.max(1),
+            exact: true,
+        }
+    }
+
+    fn add(&mut self, key: &str, counters_left: &mut usize) {
+        if let Some(count) = self.counts.get_mut(key) {
+            *count += 1;
+            return;
+        }
+        if self.counts.len() < self.capacity && *counters_left > 0 {
+            self.counts.insert(key.to_string(), 1);
+            *counters_left -= 1;
+            return;
+        }
+        // Full: the new value and every counter lose one occurrence
+        self.exact = false;
+        let before = self.counts.len();
+        self.counts.retain(|_, count| {
+            *count -= 1;
+            *count > 0
+        });
+        *counters_left += before - self.counts.len();
+    }
+
+    fn most_frequent(&self, n: usize) -> (Vec<ValueCount>, bool) {
+        let mut values: Vec<ValueCount> = self
+            .counts
+            .iter()
+            .map(|(value, &count)| ValueCount { value: value.clone(), count })
+            .collect();
+        values.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+        values.truncate(n);
+        (values, self.exact)
+    }
+}
+
+/// Running min/max/mean/variance (Welford) and a reservoir sample for percentiles
+#[derive(Default)]
+struct NumberAccumulator {
+    count: usize,
+    min: f64,
+    max: f64,
+    sum: f64,
+    mean: f64,
+    m2: f64,
+    sample: Vec<f64>,
+    /// The sample stopped growing, short of `PERCENTILE_SAMPLE_SIZE` when the counters ran
+    /// out; it always keeps the field's first number
+    sample_full: bool,
+    rng: u64,
+}
+
+impl NumberAccumulator {
+    fn add(&mut self, n: f64, counters_left: &mut usize) {
+        if self.count == 0 {
+            self.min = n;
+            self.max = n;
+            self.rng = 0x9E37_79B9_7F4A_7C15;
+        } else {
+            self.min = self.min.min(n);
+            self.max = self.max.max(n);
+        }
+        self.count += 1;
+        self.sum += n;
+        let delta = n - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (n - self.mean);
+
+        if !self.sample_full
+            && (self.sample.len() >= PERCENTILE_SAMPLE_SIZE || (*counters_left == 0 && !self.sample.is_empty()))
+        {
+            self.sample_full = true;
+        }
+        if !self.sample_full {
+            self.sample.push(n);
+            *counters_left = counters_left.saturating_sub(1);
+        } else {
+            // Reservoir sampling (xorshift keeps it deterministic and dependency free)
+            self.rng ^= self.rng << 13;
+            self.rng ^= self.rng >> 7;
+            self.rng ^= self.rng << 17;
+            let slot = (self.rng % self.count as u64) as usize;
+            if slot < self.sample.len() {
+                self.sample[slot] = n;
+            }
+        }
+    }
+
+    fn finish(mut self, percentiles: &[f64]) -> Option<NumericStats> {
+        if self.count == 0 {
+            return None;
+        }
+        self.sample.sort_by(f64::total_cmp);
+        let last = self.sample.len().saturating_sub(1) as f64;
+        let percentiles = percentiles
+            .iter()
+            .filter(|_| !self.sample.is_empty())
+            .map(|&p| {
+                // Linear interpolation between the closest ranks
+                let rank = p / 100.0 * last;
+                let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+                let value = self.sample[low] + (self.sample[high] - self.sample[low]) * (rank - low as f64);
+                Percentile { percentile: p, value }
+            })
+            .collect();
+
+        Some(NumericStats {
+            min: self.min,
+            max: self.max,
+            sum: self.sum,
+            mean: self.mean,
+            stddev: (self.m2 / self.count as f64).sqrt(),
+            percentiles,
+            percentiles_exact: self.sample.len() == self.count,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn within(actual: usize, expected: usize, tolerance: f64) -> bool {
+        (actual as f64 - expected as f64).abs() <= expected as f64 * tolerance
+    }
+
+    #[test]
+    fn hyperloglog_error_bounds() {
+        // Three standard errors (1.6% each), plus linear counting for small sets
+        for n in [100, 1_000, 10_000, 100_000, 1_000_000] {
+            let mut hll = HyperLogLog::new();
+            for i in 0..n {
+                hll.add(hash_key(&format!("value-{}", i)));
+            }
+            let estimate = hll.estimate();
+            assert!(within(estimate, n, 0.05), "n={} estimate={}", n, estimate);
+        }
+
+        // Duplicates don't count
+        let mut hll = HyperLogLog::new();
+        for i in 0..50_000 {
+            hll.add(hash_key(&(i % 500).to_string()));
+        }
+        assert!(within(hll.estimate(), 500, 0.05));
+    }
+
+    #[test]
+    fn distinct_switches_to_an_estimate_past_the_limit() {
+        let mut counters_left = usize::MAX;
+        let mut distinct = Distinct::Exact(HashSet::new(), 1_000);
+        for i in 0..1_000 {
+            distinct.add(&i.to_string(), &mut counters_left);
+            distinct.add(&i.to_string(), &mut counters_left);
+        }
+        assert_eq!(distinct.count(), (1_000, true));
+
+        for i in 1_000..20_000 {
+            distinct.add(&i.to_string(), &mut counters_left);
+        }
+        let (estimate, exact) = distinct.count();
+        assert!(!exact);
+        assert!(within(estimate, 20_000, 0.05), "estimate={}", estimate);
+        // The exact hashes were handed back
+        assert_eq!(counters_left, usize::MAX);
+    }
+
+    #[test]
+    fn misra_gries_keeps_heavy_hitters() {
+        let capacity = 10;
+        let mut counters_left = usize::MAX;
+        let mut top = TopValues::new(capacity);
+        let mut stream = Vec::new();
+        for i in 0..1_000 {
+            stream.push(format!("unique-{}", i));
+            if i % 2 == 0 {
+                stream.push("a".to_string());
+            }
+            if i % 4 == 0 {
+                stream.push("b".to_string());
+            }
+        }
+        for value in &stream {
+            top.add(value, &mut counters_left);
+        }
+
+        // Any value seen more than n / (k + 1) times is kept, with a count at most
+        // that much below the truth
+        let n = stream.len();
+        let slack = n / (capacity + 1);
+        let (values, exact) = top.most_frequent(2);
+        assert!(!exact);
+        assert_eq!(values.iter().map(|v| v.value.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+        for (value, truth) in [("a", 500), ("b", 250)] {
+            let count = values.iter().find(|v| v.value == value).unwrap().count;
+            assert!(count <= truth && count + slack >= truth, "{}: {} of {}", value, count, truth);
+        }
+        assert!(top.counts.len() <= capacity);
+    }
+
+    #[test]
+    fn top_values_are_exact_within_capacity() {
+        let mut counters_left = usize::MAX;
+        let mut top = TopValues::new(10);
+        for value in ["x", "y", "x", "z", "x", "y"] {
+            top.add(value, &mut counters_left);
+        }
+        let (values, exact) = top.most_frequent(10);
+        assert!(exact);
+        let counts: Vec<(&str, usize)> = values.iter().map(|v| (v.value.as_str(), v.count)).collect();
+        assert_eq!(counts, vec![("x", 3), ("y", 2), ("z", 1)]);
+    }
+
+    #[test]
+    fn welford_mean_and_variance() {
+        // Mean 5, population standard deviation 2
+        let data = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        for offset in [0.0, 1e9] {
+            let mut counters_left = usize::MAX;
+            let mut numbers = NumberAccumulator::default();
+            for n in data {
+                numbers.add(n + offset, &mut counters_left);
+            }
+            let stats = numbers.finish(&[0.0, 50.0, 100.0]).unwrap();
+            assert_eq!((stats.min, stats.max), (2.0 + offset, 9.0 + offset));
+            assert!((stats.mean - (5.0 + offset)).abs() < 1e-6);
+            assert!((stats.stddev - 2.0).abs() < 1e-6, "offset={} stddev={}", offset, stats.stddev);
+            assert!(stats.percentiles_exact);
+            assert_eq!(stats.percentiles[1].value, 4.5 + offset);
+        }
+    }
+
+    #[test]
+    fn reservoir_percentiles_on_large_input() {
+        let mut counters_left = usize::MAX;
+        let mut numbers = NumberAccumulator::default();
+        let n = 200_000;
+        for i in 0..n {
+            numbers.add(i as f64, &mut counters_left);
+        }
+        let stats = numbers.finish(&[10.0, 50.0, 90.0]).unwrap();
+        assert!(!stats.percentiles_exact);
+        assert_eq!(stats.mean, (n - 1) as f64 / 2.0);
+        // Exact moments, sampled percentiles within 2% of the range
+        let expected_stddev = ((n as f64 * n as f64 - 1.0) / 12.0).sqrt();
+        assert!((stats.stddev - expected_stddev).abs() < 1e-6 * expected_stddev);
+        for (p, expected) in stats.percentiles.iter().zip([20_000.0, 100_000.0, 180_000.0]) {
+            assert!((p.value - expected).abs() < 0.02 * n as f64, "p{}={}", p.percentile, p.value);
+        }
+    }
+
+    #[test]
+    fn counters_are_capped_across_fields() {
+        let mut collector = StatsCollector::new(&StatsOptions::default());
+        collector.counters_left = 500;
+        for i in 0..1_000 {
+            let record = serde_json::json!({ "a": format!("a{}", i), "b": i, "c": format!("c{}", i % 3) });
+            collector.add_record(&record);
+        }
+
+        let held: usize = collector
+            .accumulators
+            .iter()
+            .map(|(_, acc)| {
+                let hashes = match &acc.distinct {
+                    Distinct::Exact(hashes, _) => hashes.len(),
+                    Distinct::Estimated(_) => 0,
+                };
+                hashes + acc.top.counts.len() + acc.numbers.sample.len()
+            })
+            .sum();
+        assert_eq!(held + collector.counters_left, 500);
+
+        let report = collector.finish(&[50.0], 3);
+        let field = |path: &str| report.fields.iter().find(|f| f.path == path).unwrap();
+        assert!(!field("/a").distinct_exact && !field("/a").top_values_exact);
+        assert!(!field("/b").numeric.as_ref().unwrap().percentiles_exact);
+        assert!(within(field("/a").distinct, 1_000, 0.05));
+        // Fields with few values stay exact
+        assert!(field("/c").distinct_exact && field("/c").top_values_exact);
+        assert_eq!(field("/c").top_values[0].count, 334);
+    }
+
+    #[test]
+    fn numbers_keep_a_sample_after_the_counters_run_out() {
+        let mut collector = StatsCollector::new(&StatsOptions::default());
+        // Other fields used up the budget before the numeric field first appears
+        collector.counters_left = 0;
+        for i in 0..10 {
+            collector.add_record(&serde_json::json!({ "b": i }));
+        }
+
+        let report = collector.finish(&[0.0, 50.0, 100.0], 3);
+        let numeric = report.fields.iter().find(|f| f.path == "/b").unwrap().numeric.clone().unwrap();
+        assert_eq!((numeric.min, numeric.max), (0.0, 9.0));
+        assert!(!numeric.percentiles_exact);
+        assert_eq!(numeric.percentiles.len(), 3);
+        assert!(numeric.percentiles.iter().all(|p| (0.0..=9.0).contains(&p.value)));
+    }
+
+    #[test]
+    fn empty_sample_has_no_percentiles() {
+        let numbers = NumberAccumulator { count: 3, ..NumberAccumulator::default() };
+        let stats = numbers.finish(&[50.0]).unwrap();
+        assert!(stats.percentiles.is_empty());
+        assert!(!stats.percentiles_exact);
+    }
+}
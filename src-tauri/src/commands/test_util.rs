@@ -0,0 +1,47 @@
+// Helpers shared by the unit tests of the commands: temporary files, gzip data and
+// a channel that keeps what is sent on it.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tauri::ipc::{Channel, InvokeResponseBody};
+use tokio::io::AsyncReadExt;
+
+/// Fresh path in the temp directory ending in `name` (e.g. `records.jsonl.gz`), not created
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("test-{}-{}", uuid::Uuid::new_v4(), name))
+}
+
+/// Write `content` to a fresh temp file ending in `name`
+pub fn temp_file(name: &str, content: impl AsRef<[u8]>) -> PathBuf {
+    let path = temp_path(name);
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+/// `content` gzip-compressed, as a `.gz` file holds it
+pub async fn gzip(content: impl AsRef<[u8]>) -> Vec<u8> {
+    let mut compressed = Vec::new();
+    async_compression::tokio::bufread::GzipEncoder::new(content.as_ref())
+        .read_to_end(&mut compressed)
+        .await
+        .unwrap();
+    compressed
+}
+
+/// A channel for a command, and everything sent on it so far
+pub fn channel_sink<T>() -> (Channel<T>, Arc<Mutex<Vec<T>>>)
+where
+    T: Serialize + DeserializeOwned + Send + 'static,
+{
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let sink = sent.clone();
+    let channel = Channel::new(move |body| {
+        if let InvokeResponseBody::Json(json) = body {
+            sink.lock().unwrap().push(serde_json::from_str(&json).unwrap());
+        }
+        Ok(())
+    });
+    (channel, sent)
+}
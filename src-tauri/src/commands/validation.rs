@@ -0,0 +1,360 @@
+// JSON Schema validation of every record: the schema is loaded from a file (draft 2020-12
+// or draft-07) and each record's violations are streamed back in batches.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use jsonschema::error::ValidationErrorKind;
+use tauri::ipc::Channel;
+use crate::commands::file_parser::FileFormat;
+use crate::commands::search::{scan_matching_records, SearchQuery};
+
+const CHUNK_SIZE: usize = 100;
+const DEFAULT_MAX_VIOLATIONS: usize = 20;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum SchemaDraft {
+    Draft7,
+    Draft202012,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ValidationOptions {
+    /// Detected from the schema's `$schema` when unset (2020-12 without one)
+    #[serde(default)]
+    pub draft: Option<SchemaDraft>,
+    /// Only report records with violations
+    #[serde(default = "default_invalid_only")]
+    pub invalid_only: bool,
+    /// Violations kept per record
+    #[serde(default)]
+    pub max_violations: Option<usize>,
+}
+
+fn default_invalid_only() -> bool {
+    true
+}
+
+impl Default for ValidationOptions {
+    fn default() -> Self {
+        ValidationOptions {
+            draft: None,
+            invalid_only: default_invalid_only(),
+            max_violations: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Violation {
+    /// JSON Pointer to the offending value in the record
+    pub instance_path: String,
+    /// JSON Pointer to the failing keyword in the schema
+    pub schema_path: String,
+    /// The failing keyword, e.g. `type`, `required` or `maxLength`; `false` for a
+    /// `false` subschema
+    pub keyword: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecordValidation {
+    pub line_id: usize,
+    pub valid: bool,
+    pub violations: Vec<Violation>,
+    /// The record, so invalid rows can be shown on their own
+    pub content: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ValidationSummary {
+    pub records: usize,
+    pub valid: usize,
+    pub invalid: usize,
+    /// Lines that aren't JSON at all
+    pub invalid_lines: usize,
+    pub violations: usize,
+}
+
+/// Command to validate every record of a file (or the ones matching `query`) against
+/// the JSON Schema in `schema_path`, streaming the results
+#[tauri::command]
+pub async fn validate_file(
+    path: String,
+    file_format: FileFormat,
+    schema_path: String,
+    options: Option<ValidationOptions>,
+    query: Option<SearchQuery>,
+    channel: Channel<Vec<RecordValidation>>,
+) -> Result<ValidationSummary, String> {
+    let options = options.unwrap_or_default();
+    let mut chunk: Vec<RecordValidation> = Vec::with_capacity(CHUNK_SIZE);
+
+    let summary = validate_records(
+        &PathBuf::from(&path),
+        &file_format,
+        Path::new(&schema_path),
+        &options,
+        query.as_ref(),
+        |record| {
+            chunk.push(record);
+            if chunk.len() >= CHUNK_SIZE {
+                channel.send(std::mem::take(&mut chunk)).map_err(|e| format!("Failed to send: {}", e))?;
+            }
+            Ok(())
+        },
+    )
+    .await?;
+
+    if !chunk.is_empty() {
+        channel.send(chunk).map_err(|e| format!("Failed to send: {}", e))?;
+    }
+    Ok(summary)
+}
+
+/// Validate the records, handing each reported one to `visit` (shared by the command and the exports)
+pub(crate) async fn validate_records<F>(
+    file_path: &Path,
+    file_format: &FileFormat,
+    schema_path: &Path,
+    options: &ValidationOptions,
+    query: Option<&SearchQuery>,
+    mut visit: F,
+) -> Result<ValidationSummary, String>
+where
+    F: FnMut(RecordValidation) -> Result<(), String>,
+{
+    let validator = load_validator(schema_path, options.draft).await?;
+    let max_violations = options.max_violations.unwrap_or(DEFAULT_MAX_VIOLATIONS).max(1);
+    let mut summary = ValidationSummary::default();
+
+    summary.invalid_lines = scan_matching_records(file_path, file_format, query, |line_id, json| {
+        summary.records += 1;
+        let mut violations = Vec::new();
+        for error in validator.iter_errors(json) {
+            summary.violations += 1;
+            if violations.len() < max_violations {
+                violations.push(violation(&error));
+            }
+        }
+
+        let valid = violations.is_empty();
+        if valid {
+            summary.valid += 1;
+            if options.invalid_only {
+                return Ok(());
+            }
+        } else {
+            summary.invalid += 1;
+        }
+        visit(RecordValidation {
+            line_id,
+            valid,
+            violations,
+            content: json.to_string(),
+        })
+    })
+    .await?;
+
+    Ok(summary)
+}
+
+async fn load_validator(schema_path: &Path, draft: Option<SchemaDraft>) -> Result<jsonschema::Validator, String> {
+    let content = tokio::fs::read_to_string(schema_path)
+        .await
+        .map_err(|e| format!("Failed to read schema: {}", e))?;
+    let schema: serde_json::Value =
+        serde_json::from_str(&content).map_err(|e| format!("The schema is not valid JSON: {}", e))?;
+
+    let mut builder = jsonschema::options();
+    match draft {
+        Some(SchemaDraft::Draft7) => builder = builder.with_draft(jsonschema::Draft::Draft7),
+        Some(SchemaDraft::Draft202012) => builder = builder.with_draft(jsonschema::Draft::Draft202012),
+        None => {}
+    }
+    builder.build(&schema).map_err(|e| format!("Invalid JSON Schema: {}", e))
+}
+
+fn violation(error: &jsonschema::ValidationError) -> Violation {
+    let schema_path = error.schema_path.as_str().to_string();
+    let keyword = match keyword(&error.kind) {
+        Some(keyword) => keyword.to_string(),
+        // Custom errors only have their place in the schema to go by
+        None => schema_path.rsplit('/').next().unwrap_or_default().to_string(),
+    };
+    Violation {
+        instance_path: error.instance_path.as_str().to_string(),
+        schema_path,
+        keyword,
+        message: error.to_string(),
+    }
+}
+
+/// The keyword behind an error. The schema path can't tell: a `false` subschema
+/// fails at `/properties/x`.
+fn keyword(kind: &ValidationErrorKind) -> Option<&'static str> {
+    Some(match kind {
+        ValidationErrorKind::AdditionalItems { .. } => "additionalItems",
+        ValidationErrorKind::AdditionalProperties { .. } => "additionalProperties",
+        ValidationErrorKind::AnyOf => "anyOf",
+        ValidationErrorKind::BacktrackLimitExceeded { .. } | ValidationErrorKind::Pattern { .. } => "pattern",
+        ValidationErrorKind::Constant { .. } => "const",
+        ValidationErrorKind::Contains => "contains",
+        ValidationErrorKind::ContentEncoding { .. } | ValidationErrorKind::FromUtf8 { .. } => "contentEncoding",
+        ValidationErrorKind::ContentMediaType { .. } => "contentMediaType",
+        ValidationErrorKind::Custom { .. } => return None,
+        ValidationErrorKind::Enum { .. } => "enum",
+        ValidationErrorKind::ExclusiveMaximum { .. } => "exclusiveMaximum",
+        ValidationErrorKind::ExclusiveMinimum { .. } => "exclusiveMinimum",
+        ValidationErrorKind::FalseSchema => "false",
+        ValidationErrorKind::Format { .. } => "format",
+        ValidationErrorKind::MaxItems { .. } => "maxItems",
+        ValidationErrorKind::Maximum { .. } => "maximum",
+        ValidationErrorKind::MaxLength { .. } => "maxLength",
+        ValidationErrorKind::MaxProperties { .. } => "maxProperties",
+        ValidationErrorKind::MinItems { .. } => "minItems",
+        ValidationErrorKind::Minimum { .. } => "minimum",
+        ValidationErrorKind::MinLength { .. } => "minLength",
+        ValidationErrorKind::MinProperties { .. } => "minProperties",
+        ValidationErrorKind::MultipleOf { .. } => "multipleOf",
+        ValidationErrorKind::Not { .. } => "not",
+        ValidationErrorKind::OneOfMultipleValid | ValidationErrorKind::OneOfNotValid => "oneOf",
+        ValidationErrorKind::PropertyNames { .. } => "propertyNames",
+        ValidationErrorKind::Required { .. } => "required",
+        ValidationErrorKind::Type { .. } => "type",
+        ValidationErrorKind::UnevaluatedItems { .. } => "unevaluatedItems",
+        ValidationErrorKind::UnevaluatedProperties { .. } => "unevaluatedProperties",
+        ValidationErrorKind::UniqueItems => "uniqueItems",
+        ValidationErrorKind::Referencing(_) => "$ref",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::commands::test_util::{temp_file, temp_path};
+
+    fn violations(schema: serde_json::Value, record: serde_json::Value) -> Vec<Violation> {
+        let validator = jsonschema::validator_for(&schema).unwrap();
+        validator.iter_errors(&record).map(|error| violation(&error)).collect()
+    }
+
+    #[test]
+    fn keywords_of_common_failures() {
+        let schema = json!({
+            "type": "object",
+            "required": ["id"],
+            "properties": {
+                "id": {"type": "integer"},
+                "name": {"type": "string", "maxLength": 3},
+            },
+        });
+
+        let found = violations(schema.clone(), json!({"name": "abc"}));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].keyword, "required");
+        assert_eq!(found[0].schema_path, "/required");
+        assert_eq!(found[0].instance_path, "");
+
+        let found = violations(schema, json!({"id": "7", "name": "abcd"}));
+        let mut keywords: Vec<(&str, &str, &str)> = found
+            .iter()
+            .map(|v| (v.keyword.as_str(), v.schema_path.as_str(), v.instance_path.as_str()))
+            .collect();
+        keywords.sort();
+        assert_eq!(
+            keywords,
+            vec![
+                ("maxLength", "/properties/name/maxLength", "/name"),
+                ("type", "/properties/id/type", "/id"),
+            ]
+        );
+    }
+
+    #[test]
+    fn false_subschemas_and_combinators() {
+        let found = violations(json!({"properties": {"x": false}}), json!({"x": 1}));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].keyword, "false");
+        assert_eq!(found[0].schema_path, "/properties/x");
+        assert_eq!(found[0].instance_path, "/x");
+
+        let keywords = |schema, record| -> Vec<String> {
+            violations(schema, record).into_iter().map(|v| v.keyword).collect()
+        };
+        assert_eq!(keywords(json!({"oneOf": [{"type": "string"}, {"minimum": 5}]}), json!(1)), vec!["oneOf"]);
+        assert_eq!(keywords(json!({"const": "a"}), json!("b")), vec!["const"]);
+        assert_eq!(keywords(json!({"properties": {"a": {}}, "additionalProperties": false}), json!({"y": 1})), vec!["additionalProperties"]);
+        assert_eq!(keywords(json!({"uniqueItems": true}), json!([1, 1])), vec!["uniqueItems"]);
+    }
+
+    #[test]
+    fn keyword_of_nested_array_items() {
+        let schema = json!({
+            "properties": {"tags": {"type": "array", "items": {"enum": ["a", "b"]}}},
+        });
+        let found = violations(schema, json!({"tags": ["a", "z"]}));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].keyword, "enum");
+        assert_eq!(found[0].schema_path, "/properties/tags/items/enum");
+        assert_eq!(found[0].instance_path, "/tags/1");
+    }
+
+    #[tokio::test]
+    async fn draft_changes_how_the_schema_reads() {
+        // Array-form `items` is a tuple in draft-07 but not a valid 2020-12 schema
+        let schema = temp_file("schema.json", r#"{"items": [{"type": "integer"}]}"#);
+        assert!(load_validator(&schema, Some(SchemaDraft::Draft7)).await.is_ok());
+        assert!(load_validator(&schema, Some(SchemaDraft::Draft202012)).await.is_err());
+        std::fs::remove_file(&schema).unwrap();
+
+        let missing = temp_path("schema.json");
+        assert!(load_validator(&missing, None).await.unwrap_err().starts_with("Failed to read schema"));
+    }
+
+    #[tokio::test]
+    async fn records_are_counted_and_violations_capped() {
+        let schema = temp_file(
+            "schema.json",
+            r#"{"properties": {"a": {"type": "integer"}, "b": {"type": "integer"}, "c": {"type": "integer"}}}"#,
+        );
+        let data = temp_file(
+            "records.jsonl",
+            "{\"a\":1}\n{\"a\":\"x\",\"b\":\"y\",\"c\":\"z\"}\nnot json\n{\"b\":\"y\"}\n",
+        );
+        let options = ValidationOptions { max_violations: Some(2), ..ValidationOptions::default() };
+
+        let mut reported = Vec::new();
+        let summary = validate_records(&data, &FileFormat::JsonL, &schema, &options, None, |record| {
+            reported.push(record);
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(summary.records, 3);
+        assert_eq!(summary.valid, 1);
+        assert_eq!(summary.invalid, 2);
+        assert_eq!(summary.invalid_lines, 1);
+        assert_eq!(summary.violations, 4);
+
+        // Only the invalid records are reported by default, each keeping at most two violations
+        let lines: Vec<usize> = reported.iter().map(|r| r.line_id).collect();
+        assert_eq!(lines, vec![1, 3]);
+        assert_eq!(reported[0].violations.len(), 2);
+        assert!(reported.iter().all(|r| !r.valid && r.violations.iter().all(|v| v.keyword == "type")));
+
+        let options = ValidationOptions { invalid_only: false, ..ValidationOptions::default() };
+        let mut lines = Vec::new();
+        validate_records(&data, &FileFormat::JsonL, &schema, &options, None, |record| {
+            lines.push((record.line_id, record.valid));
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(lines, vec![(0, true), (1, false), (3, false)]);
+
+        std::fs::remove_file(&schema).unwrap();
+        std::fs::remove_file(&data).unwrap();
+    }
+}
@@ -3,18 +3,38 @@
 
 mod commands;
 
-use commands::{file_parser, search, export, network, sort};
+use commands::{aggregate, download_cache, file_parser, histogram, schema, search, export, http, live_stream, network, network_policy, sort, sort_view, stats, validation};
 
 use tauri::menu::{Menu, MenuItem, PredefinedMenuItem, Submenu};
-use tauri::Emitter;
+use tauri::{Emitter, Manager};
 
 fn main() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
+        .manage(sort_view::SortViews::default())
+        .manage(network::Downloads::default())
         .setup(|app| {
             let handle = app.handle();
 
+            // Credentials saved per host for Open from URL
+            let credentials_path = app.path().app_config_dir()?.join("credentials.json");
+            app.manage(http::CredentialStore::load(credentials_path));
+
+            // Hosts and addresses Open from URL may reach
+            let policy_path = app.path().app_config_dir()?.join("network-policy.json");
+            app.manage(network_policy::PolicyStore::load(policy_path));
+
+            // Download cache for Open from URL, pruned at startup along with old temp files
+            let cache_dir = app.path().app_cache_dir()?.join("downloads");
+            app.manage(download_cache::DownloadCache::new(cache_dir));
+            let prune_handle = handle.clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                let cache = prune_handle.state::<download_cache::DownloadCache>();
+                cache.prune(download_cache::DEFAULT_CACHE_MAX_MB * 1024 * 1024, download_cache::DEFAULT_CACHE_MAX_AGE);
+                download_cache::cleanup_temp_files(download_cache::DEFAULT_CACHE_MAX_AGE, cache.viewed().as_deref());
+            });
+
             // App configurations
             #[cfg(target_os = "macos")]
             let app_name = "JSONL Viewer";
@@ -100,11 +120,38 @@ fn main() {
 
             file_parser::parse_file_streaming,
             search::search_in_file,
+            search::count_in_file,
+            stats::compute_stats,
+            histogram::compute_histogram,
+            aggregate::aggregate,
+            schema::infer_schema,
+            validation::validate_file,
+            search::search_in_files,
             export::export_to_csv,
             export::export_to_excel,
+            export::export_aggregate_to_csv,
+            export::export_aggregate_to_excel,
+            export::export_violations_to_csv,
+            export::export_violations_to_excel,
             network::download_url_to_temp,
+            network::cancel_download,
+            network::download_api_pages,
+            download_cache::prune_download_cache,
+            download_cache::clear_download_cache,
+            download_cache::set_viewed_file,
+            network_policy::get_network_policy,
+            network_policy::set_network_policy,
+            live_stream::stream_url_live,
+            http::save_host_credentials,
+            http::list_credential_hosts,
+            http::remove_host_credentials,
             sort::sort_file_lines,
-            sort::sort_search_results
+            sort::sort_search_results,
+            sort_view::create_sorted_view,
+            sort_view::get_sorted_page,
+            sort_view::get_sorted_line_ids,
+            sort_view::order_line_ids,
+            sort_view::release_sorted_view
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,6 +1,6 @@
 import { writable } from 'svelte/store';
 import type { JsonLine, FileMetadata, ColumnInfo } from '$lib/types';
-import { getValue } from '$lib/utils/valueFormat';
+import { getValue, pointerChild, pointerSegments } from '$lib/utils/valueFormat';
 import { sortStore } from './sortStore';
 
 interface FileState {
@@ -66,13 +66,13 @@ function createFileStore() {
 					const keyCounts = new Map<string, number>();
 					const columnComplexity = new Map<string, boolean>();
 
-					// Recursive helper to find flat keys
+					// Recursive helper to find flat keys (as JSON Pointers)
 					const collectKeys = (obj: any, prefix: string = '', depth: number = 0) => {
 						if (depth > 2 || !obj || typeof obj !== 'object' || Array.isArray(obj)) return;
 
 						Object.keys(obj).forEach(key => {
 							const val = obj[key];
-							const newKey = prefix ? `${prefix}_${key}` : key;
+							const newKey = pointerChild(prefix, key);
 
 							// If value is simple or we hit max depth, count this key
 							if (
@@ -127,8 +127,8 @@ function createFileStore() {
 
 					// Sort keys by priority and then by frequency
 					const sortedKeys = Array.from(keyCounts.keys()).sort((a, b) => {
-						const aBase = a.split('_')[0].toLowerCase();
-						const bBase = b.split('_')[0].toLowerCase();
+						const aBase = pointerSegments(a)[0].toLowerCase();
+						const bBase = pointerSegments(b)[0].toLowerCase();
 
 						const aPrio = priorityKeys.indexOf(aBase);
 						const bPrio = priorityKeys.indexOf(bBase);
@@ -144,11 +144,14 @@ function createFileStore() {
 					}).slice(0, 100); // Increase cap to allow more columns
 
 					// Build ColumnInfo array
-					columnInfo = sortedKeys.map((path) => ({
-						path,
-						isSortable: columnComplexity.get(path) ?? false,
-						displayName: path.split('_').slice(1).join('_') || path
-					}));
+					columnInfo = sortedKeys.map((path) => {
+						const segments = pointerSegments(path);
+						return {
+							path,
+							isSortable: columnComplexity.get(path) ?? false,
+							displayName: segments.slice(1).join('/') || segments[0]
+						};
+					});
 				}
 
 				return {
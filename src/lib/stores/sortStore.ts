@@ -1,17 +1,27 @@
 import { writable } from 'svelte/store';
-import type { SortState } from '$lib/types';
+import { invoke } from '@tauri-apps/api/core';
+import type { SortedViewInfo, SortState } from '$lib/types';
 
 interface SortStore {
 	state: SortState;
 	isSorting: boolean;
 	error: string | null;
+	/** Backend sorted view the rows are paged from */
+	view: SortedViewInfo | null;
+}
+
+function releaseView(view: SortedViewInfo | null) {
+	if (view) {
+		invoke('release_sorted_view', { viewId: view.view_id }).catch(() => {});
+	}
 }
 
 function createSortStore() {
-	const { subscribe, set, update } = writable<SortStore>({
+	const { subscribe, update } = writable<SortStore>({
 		state: { column: null, direction: 'asc' },
 		isSorting: false,
 		error: null,
+		view: null,
 	});
 
 	return {
@@ -25,6 +35,14 @@ function createSortStore() {
 			update((state) => ({ ...state, isSorting: sorting })),
 		setError: (error: string | null) =>
 			update((state) => ({ ...state, error })),
+		// The previous view is released unless the backend handed the same one back
+		setView: (view: SortedViewInfo | null) =>
+			update((state) => {
+				if (state.view && state.view.view_id !== view?.view_id) {
+					releaseView(state.view);
+				}
+				return { ...state, view };
+			}),
 		toggleColumn: (column: string) =>
 			update((state) => {
 				const currentCol = state.state.column;
@@ -45,10 +63,14 @@ function createSortStore() {
 				}
 			}),
 		reset: () =>
-			set({
-				state: { column: null, direction: 'asc' },
-				isSorting: false,
-				error: null
+			update((state) => {
+				releaseView(state.view);
+				return {
+					state: { column: null, direction: 'asc' },
+					isSorting: false,
+					error: null,
+					view: null
+				};
 			})
 	};
 }
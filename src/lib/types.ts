@@ -17,22 +17,53 @@ export interface SearchQuery {
 	json_path?: string;
 	case_sensitive: boolean;
 	regex: boolean;
+	invert?: boolean;
+	within_line_ids?: number[];
+	scope?: SearchScope;
+	fields?: string[];
+	whole_word?: boolean;
+	fuzzy?: number;
 }
 
+export type SearchScope = 'Line' | 'Values' | 'Keys';
+
 export interface SearchResult {
 	line_id: number;
 	matches: string[];
 	context: string;
+	is_context?: boolean;
+	source?: string;
 }
 
 export interface SearchStats {
 	total_matches: number;
 	lines_searched: number;
+	group_counts?: GroupCount[];
+}
+
+export interface MultiSearchStats {
+	total_matches: number;
+	lines_searched: number;
+	files: FileSearchStats[];
+}
+
+export interface FileSearchStats {
+	path: string;
+	total_matches: number;
+	lines_searched: number;
+	error: string | null;
+}
+
+export interface GroupCount {
+	value: string | null;
+	count: number;
 }
 
 export interface ExportFilter {
 	line_ids?: number[];
 	search_query?: SearchQuery;
+	/** Sorted view to read the lines through, in its order */
+	view_id?: string;
 }
 
 export interface ExportStats {
@@ -50,10 +81,265 @@ export interface SortState {
 export interface SortColumn {
 	column: string;
 	direction: string;
+	value_type?: SortValueType;
+	collation?: Collation;
+	locale?: string;
+	expression?: string;
+}
+
+export type Collation = 'CaseInsensitive' | 'CaseSensitive' | 'Natural' | 'Locale';
+
+export type SortValueType = 'Number' | 'Date' | 'String' | 'Natural';
+
+export interface SortedViewInfo {
+	view_id: string;
+	total_rows: number;
+}
+
+export interface HttpOptions {
+	method?: string;
+	headers?: Record<string, string>;
+	body?: string;
+	auth?: HttpAuth;
+	timeout_secs?: number;
+	ca_cert_path?: string;
+	accept_invalid_certs?: boolean;
+}
+
+export type HttpAuth =
+	| { Bearer: { token: string } }
+	| { Basic: { username: string; password?: string } };
+
+export interface HostCredentials {
+	auth?: HttpAuth;
+	headers?: Record<string, string>;
+}
+
+export interface DownloadedFile {
+	path: string;
+	file_name: string;
+	url: string;
+	content_type: string | null;
+	decompressed: boolean;
+	from_cache: boolean;
+	resumed: boolean;
+}
+
+export interface NetworkPolicy {
+	allow_hosts: string[];
+	deny_hosts: string[];
+	block_private_ips: boolean;
+	max_redirects: number;
+}
+
+export interface CacheStats {
+	entries: number;
+	total_bytes: number;
+	removed: number;
+}
+
+export type PaginationMode = 'None' | 'NextCursor' | 'LinkHeader' | 'OffsetLimit';
+
+export interface Pagination {
+	mode: PaginationMode;
+	next_path?: string;
+	cursor_param?: string;
+	offset_param?: string;
+	limit_param?: string;
+	page_size?: number;
+}
+
+export interface ApiProgress {
+	pages: number;
+	records: number;
+}
+
+export interface ApiDownload {
+	path: string;
+	file_name: string;
+	url: string;
+	pages: number;
+	records: number;
+	truncated: boolean;
+}
+
+export type StreamFormat = 'Ndjson' | 'Sse';
+
+export interface LiveStreamSummary {
+	records: number;
+	format: StreamFormat;
+	recording_path: string | null;
+	cancelled: boolean;
+}
+
+export interface DownloadProgress {
+	received: number;
+	total: number | null;
 }
 
 export interface ColumnInfo {
 	path: string;
 	isSortable: boolean;
 	displayName: string;
-}
\ No newline at end of file
+}
+export interface StatsOptions {
+	fields?: string[];
+	exact_distinct_limit?: number;
+	top_n?: number;
+	percentiles?: number[];
+}
+
+export interface TypeCounts {
+	null: number;
+	boolean: number;
+	number: number;
+	string: number;
+	array: number;
+	object: number;
+}
+
+export interface NumericStats {
+	min: number;
+	max: number;
+	sum: number;
+	mean: number;
+	stddev: number;
+	percentiles: { percentile: number; value: number }[];
+	percentiles_exact: boolean;
+}
+
+export interface FieldStats {
+	path: string;
+	present: number;
+	missing: number;
+	nulls: number;
+	null_rate: number;
+	missing_rate: number;
+	types: TypeCounts;
+	distinct: number;
+	distinct_exact: boolean;
+	numeric: NumericStats | null;
+	string_lengths: { min: number; max: number; mean: number } | null;
+	top_values: { value: string; count: number }[];
+	top_values_exact: boolean;
+}
+
+export interface StatsReport {
+	records: number;
+	invalid_lines: number;
+	fields: FieldStats[];
+	fields_truncated: boolean;
+}
+
+export type HistogramKind = 'Numeric' | 'Time';
+
+export interface HistogramOptions {
+	kind?: HistogramKind;
+	width?: number;
+	interval?: string; // e.g. "30s", "5m", "1h", "1d"
+	target_buckets?: number;
+}
+
+export interface HistogramBucket {
+	start: number; // epoch milliseconds for time buckets
+	end: number;
+	count: number;
+}
+
+export interface Histogram {
+	field: string;
+	kind: HistogramKind;
+	width: number;
+	buckets: HistogramBucket[];
+	records: number;
+	missing: number;
+}
+
+export type AggregateFunction = 'Count' | 'Sum' | 'Avg' | 'Min' | 'Max' | 'Distinct';
+
+export interface Aggregation {
+	field?: string | null; // JSON Pointer; a record count when omitted with Count
+	function: AggregateFunction;
+}
+
+export interface AggregateOptions {
+	group_by: string[];
+	aggregations: Aggregation[];
+	pivot?: string | null;
+	max_groups?: number;
+}
+
+export interface AggregateTable {
+	columns: string[]; // JSON Pointers, e.g. "/status", "/count", "/bytes/sum"
+	rows: unknown[][];
+	records: number;
+	invalid_lines: number;
+	groups_truncated: boolean;
+}
+
+export interface SchemaOptions {
+	max_fields?: number;
+	max_changes?: number;
+}
+
+export interface FieldSchema {
+	path: string; // JSON Pointer, "*" for array items
+	types: string[];
+	present: number;
+	optional: boolean;
+	nullable: boolean;
+	examples: string[]; // as JSON
+	first_line: number;
+	last_line: number;
+}
+
+export type ChangeKind = 'TypeChanged' | 'Added' | 'Removed';
+
+export interface SchemaChange {
+	path: string;
+	line: number;
+	kind: ChangeKind;
+	from: string | null;
+	to: string | null;
+	message: string;
+}
+
+export interface SchemaReport {
+	records: number;
+	invalid_lines: number;
+	fields: FieldSchema[];
+	fields_truncated: boolean;
+	changes: SchemaChange[];
+	total_changes: number;
+	json_schema: Record<string, unknown>; // JSON Schema 2020-12
+}
+
+export type SchemaDraft = 'Draft7' | 'Draft202012';
+
+export interface ValidationOptions {
+	draft?: SchemaDraft; // from the schema's $schema when omitted
+	invalid_only?: boolean; // defaults to true
+	max_violations?: number;
+}
+
+export interface Violation {
+	instance_path: string;
+	schema_path: string;
+	keyword: string;
+	message: string;
+}
+
+export interface RecordValidation {
+	line_id: number;
+	valid: boolean;
+	violations: Violation[];
+	content: string;
+}
+
+export interface ValidationSummary {
+	records: number;
+	valid: number;
+	invalid: number;
+	invalid_lines: number;
+	violations: number;
+}
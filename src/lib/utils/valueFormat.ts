@@ -61,20 +61,39 @@ export function smartFormat(value: any): { text: string; isComplex: boolean } {
 	return { text: String(value), isComplex: false };
 }
 
-// Helper to safely get value for a nested column path (e.g. "user_name")
-export function getValue(parsed: any, colPath: string): { text: string; isComplex: boolean } {
-	if (!parsed || typeof parsed !== 'object') return { text: '', isComplex: false };
+// Column paths are JSON Pointers (e.g. "/user/name", "/user_id", "/tags/0"),
+// matching the paths the backend uses for sort, search and export.
+export function pointerChild(prefix: string, key: string): string {
+	return `${prefix}/${key.replace(/~/g, '~0').replace(/\//g, '~1')}`;
+}
+
+export function pointerSegments(path: string): string[] {
+	if (!path) return [];
+	return path
+		.slice(1)
+		.split('/')
+		.map((s) => s.replace(/~1/g, '/').replace(/~0/g, '~'));
+}
 
-	const parts = colPath.split('_');
+// Helper to safely resolve a raw value at a column path
+export function resolvePath(parsed: any, colPath: string): any {
 	let current = parsed;
 
-	for (const part of parts) {
+	for (const part of pointerSegments(colPath)) {
 		if (current === null || current === undefined || typeof current !== 'object') {
-			return { text: '', isComplex: false };
+			return undefined;
 		}
 		current = current[part];
 	}
 
+	return current;
+}
+
+// Helper to safely get value for a nested column path (e.g. "/user/name")
+export function getValue(parsed: any, colPath: string): { text: string; isComplex: boolean } {
+	if (!parsed || typeof parsed !== 'object') return { text: '', isComplex: false };
+
+	const current = resolvePath(parsed, colPath);
 	if (current === undefined || current === null) return { text: '', isComplex: false };
 	return smartFormat(current);
 }